@@ -0,0 +1,190 @@
+use crate::diff::RowChange;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One row touched by a write; `None` means the key was absent before or
+/// is absent after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditChange {
+    pub key: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl From<RowChange> for AuditChange {
+    fn from(change: RowChange) -> Self {
+        match change {
+            RowChange::Added { key, value } => AuditChange {
+                key,
+                before: None,
+                after: Some(value),
+            },
+            RowChange::Removed { key, value } => AuditChange {
+                key,
+                before: Some(value),
+                after: None,
+            },
+            RowChange::Changed { key, old, new } => AuditChange {
+                key,
+                before: Some(old),
+                after: Some(new),
+            },
+        }
+    }
+}
+
+/// A committed write to one table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub table: String,
+    /// The kind of write, ex: "put", "edit", "delete", "restore".
+    pub action: String,
+    pub changes: Vec<AuditChange>,
+    /// Why the changed rows could not be read; `changes` is empty then.
+    pub not_captured: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(table: &str, action: &str, changes: Vec<AuditChange>) -> Self {
+        AuditEntry {
+            table: table.to_string(),
+            action: action.to_string(),
+            changes,
+            not_captured: None,
+        }
+    }
+    /// A write whose changed rows could not be read, because of `error`.
+    pub fn not_captured(table: &str, action: &str, error: String) -> Self {
+        AuditEntry {
+            not_captured: Some(format!("changes not captured: {error}")),
+            ..AuditEntry::new(table, action, Vec::new())
+        }
+    }
+}
+
+/// Append-only journal file. Every entry is written as one JSON object per
+/// line together with the time, the OS user, the database path and the
+/// command line that caused it.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    user: String,
+    command: String,
+}
+
+impl Journal {
+    /// Opens (creating if needed) the journal at `path`, failing early if it
+    /// cannot be appended to.
+    pub fn open(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Journal {
+            path,
+            user: os_user(),
+            command: String::new(),
+        })
+    }
+    /// Sets the command line recorded with the following entries.
+    pub fn set_command(&mut self, line: &str) {
+        self.command = line.trim().to_string();
+    }
+    pub fn append(&self, dbpath: &str, entries: &[AuditEntry]) -> std::io::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let time = format_utc(SystemTime::now());
+        let mut out = String::new();
+        for entry in entries {
+            let changes: Vec<serde_json::Value> = entry
+                .changes
+                .iter()
+                .map(|c| serde_json::json!({ "key": c.key, "before": c.before, "after": c.after }))
+                .collect();
+            let mut line = serde_json::json!({
+                "time": time,
+                "user": self.user,
+                "db": dbpath,
+                "table": entry.table,
+                "command": self.command,
+                "action": entry.action,
+                "changes": changes,
+            });
+            if let Some(note) = &entry.not_captured {
+                line["note"] = note.as_str().into();
+            }
+            out.push_str(&line.to_string());
+            out.push('\n');
+        }
+        // one write per batch, so entries of one commit stay together
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(out.as_bytes())?;
+        file.sync_data()
+    }
+}
+
+fn os_user() -> String {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp, ex: "2024-03-01T12:30:05Z".
+pub fn format_utc(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_utc_timestamps() {
+        assert_eq!(format_utc(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_827_696);
+        assert_eq!(format_utc(leap_day), "2000-02-29T12:34:56Z");
+        let new_year = UNIX_EPOCH + Duration::from_secs(1_704_067_199);
+        assert_eq!(format_utc(new_year), "2023-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn row_changes_map_to_before_and_after() {
+        let change: AuditChange = RowChange::Changed {
+            key: "1".into(),
+            old: "a".into(),
+            new: "b".into(),
+        }
+        .into();
+        assert_eq!(change.before.as_deref(), Some("a"));
+        assert_eq!(change.after.as_deref(), Some("b"));
+        let removed: AuditChange = RowChange::Removed {
+            key: "1".into(),
+            value: "a".into(),
+        }
+        .into();
+        assert_eq!(removed.after, None);
+    }
+}
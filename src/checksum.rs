@@ -0,0 +1,78 @@
+use crate::dynread::{self, TableTypeDesc};
+use redb::ReadTransaction;
+use sha2::{Digest, Sha256};
+
+pub type TableDigest = [u8; 32];
+
+fn update_framed(hasher: &mut Sha256, data: &[u8]) {
+    hasher.update((data.len() as u64).to_le_bytes());
+    hasher.update(data);
+}
+
+/// Computes a digest over the persisted key/value type names and the
+/// ordered raw key/value bytes of table `name`. Every field is length
+/// framed, so two tables hash equal only if their content is identical.
+pub fn table_digest(
+    txn: &ReadTransaction,
+    name: &str,
+    desc: &TableTypeDesc,
+) -> Result<TableDigest, crate::Error> {
+    let mut hasher = Sha256::new();
+    update_framed(&mut hasher, desc.key.as_bytes());
+    update_framed(&mut hasher, desc.value.as_bytes());
+    dynread::visit_raw_rows_dyn(txn, name, desc, &mut |key, value| {
+        update_framed(&mut hasher, key);
+        update_framed(&mut hasher, value);
+    })?;
+    Ok(hasher.finalize().into())
+}
+
+/// Combines per-table digests into one. Tables are ordered by name, so the
+/// result does not depend on the order they were listed in.
+pub fn overall_digest(tables: &[(String, TableDigest)]) -> TableDigest {
+    let mut sorted: Vec<&(String, TableDigest)> = tables.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut hasher = Sha256::new();
+    for (name, digest) in sorted {
+        update_framed(&mut hasher, name.as_bytes());
+        hasher.update(digest);
+    }
+    hasher.finalize().into()
+}
+
+pub fn digest_to_hex(digest: &TableDigest) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overall_digest_ignores_listing_order() {
+        let a = ("a".to_string(), [1u8; 32]);
+        let b = ("b".to_string(), [2u8; 32]);
+        assert_eq!(
+            overall_digest(&[a.clone(), b.clone()]),
+            overall_digest(&[b, a])
+        );
+    }
+
+    #[test]
+    fn overall_digest_depends_on_names() {
+        let digest = [7u8; 32];
+        assert_ne!(
+            overall_digest(&[("a".to_string(), digest)]),
+            overall_digest(&[("b".to_string(), digest)])
+        );
+    }
+
+    #[test]
+    fn hex_is_lowercase_without_prefix() {
+        let mut digest = [0u8; 32];
+        digest[0] = 0xab;
+        let hex = digest_to_hex(&digest);
+        assert_eq!(hex.len(), 64);
+        assert!(hex.starts_with("ab00"));
+    }
+}
@@ -0,0 +1,552 @@
+//! Conversions between serde data formats and JSON, so documents an
+//! application serialized with msgpack, CBOR, bincode or postcard can be
+//! shown and edited as JSON and written back in their own format. The
+//! formats themselves are read and written by `rmp-serde`, `ciborium`,
+//! `bincode` 1.x and `postcard`; this module only maps their serde data
+//! model onto `serde_json::Value`.
+//!
+//! JSON has no byte strings; they are shown as `{"$bytes": "0x0102"}` and
+//! written back as byte strings. Integers keep their value but are written
+//! in the smallest form the format allows, and floats as 64 bit (CBOR: the
+//! shortest width that keeps the value), which the application's
+//! deserializer accepts for any float or integer width. Values JSON cannot
+//! hold (non-string map keys, NaN, msgpack extensions, CBOR tags) make the
+//! value undecodable.
+
+use crate::dynread::{bytes_to_hex, parse_hex_cell};
+use crate::layout::{Codec, Field, FieldKind};
+use crate::typecodec::TypeTree;
+use bincode::Options;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, SerializeMap, SerializeSeq, SerializeTuple, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value as Json};
+use std::fmt;
+
+const BYTES_KEY: &str = "$bytes";
+
+/// Decodes one serialized document; bincode and postcard take the
+/// serialized struct's `fields`.
+pub(crate) fn decode(codec: Codec, fields: &[Field], data: &[u8]) -> Result<Json, String> {
+    let mut rest = data;
+    let value = match codec {
+        Codec::Json => {
+            return serde_json::from_slice(data).map_err(|e| format!("invalid JSON: {e}"))
+        }
+        Codec::MsgPack => {
+            Document::deserialize(&mut rmp_serde::Deserializer::new(&mut rest))
+                .map_err(|e| e.to_string())?
+                .0
+        }
+        Codec::Cbor => {
+            ciborium::from_reader::<Document<Json>, _>(&mut rest)
+                .map_err(|e| e.to_string())?
+                .0
+        }
+        Codec::Bincode => {
+            return bincode_options()
+                .deserialize_seed(Fields(fields), data)
+                .map_err(|e| e.to_string())
+        }
+        Codec::Postcard => {
+            let mut de = postcard::Deserializer::from_bytes(data);
+            let value = Fields(fields)
+                .deserialize(&mut de)
+                .map_err(|e| e.to_string())?;
+            rest = de.finalize().map_err(|e| e.to_string())?;
+            value
+        }
+    };
+    match rest.len() {
+        0 => Ok(value),
+        left => Err(format!("{left} bytes left after the {codec} value")),
+    }
+}
+
+/// Encodes `value` in `codec`, the inverse of `decode`.
+pub(crate) fn encode(codec: Codec, fields: &[Field], value: &Json) -> Result<Vec<u8>, String> {
+    match codec {
+        Codec::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+        Codec::MsgPack => rmp_serde::to_vec(&Document(value)).map_err(|e| e.to_string()),
+        Codec::Cbor => {
+            let mut out = Vec::new();
+            ciborium::into_writer(&Document(value), &mut out).map_err(|e| e.to_string())?;
+            Ok(out)
+        }
+        Codec::Bincode => bincode_options()
+            .serialize(&cells(fields, value)?)
+            .map_err(|e| e.to_string()),
+        Codec::Postcard => postcard::to_allocvec(&cells(fields, value)?).map_err(|e| e.to_string()),
+    }
+}
+
+/// The options `bincode::serialize` uses, rejecting trailing bytes.
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new().with_fixint_encoding()
+}
+
+fn bytes_json(bytes: &[u8]) -> Json {
+    let mut map = Map::new();
+    map.insert(BYTES_KEY.to_string(), Json::String(bytes_to_hex(bytes)));
+    Json::Object(map)
+}
+
+/// The bytes of a `{"$bytes": "0x.."}` object, `None` for other values.
+fn json_bytes(value: &Json) -> Result<Option<Vec<u8>>, String> {
+    let hex = match value.as_object() {
+        Some(map) if map.len() == 1 => match map.get(BYTES_KEY) {
+            Some(hex) => hex,
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let hex = hex
+        .as_str()
+        .ok_or_else(|| format!("'{BYTES_KEY}' must be a 0x-prefixed hex string"))?;
+    parse_hex_cell(hex)
+        .map(Some)
+        .map_err(|e| format!("'{BYTES_KEY}': {}", e.message))
+}
+
+fn float<E: de::Error>(x: f64) -> Result<Json, E> {
+    Number::from_f64(x)
+        .map(Json::Number)
+        .ok_or_else(|| E::custom(format!("{x} cannot be shown as JSON")))
+}
+
+/// Widens a 32 bit float through its shortest text, so 0.1f32 shows as 0.1.
+fn float32<E: de::Error>(x: f32) -> Result<Json, E> {
+    float(x.to_string().parse().unwrap_or(f64::from(x)))
+}
+
+fn wide_uint(n: u128) -> Json {
+    match u64::try_from(n) {
+        Ok(n) => n.into(),
+        Err(_) => Json::String(n.to_string()),
+    }
+}
+
+fn wide_int(n: i128) -> Json {
+    match i64::try_from(n) {
+        Ok(n) => n.into(),
+        Err(_) => Json::String(n.to_string()),
+    }
+}
+
+// -- msgpack and CBOR --------------------------------------------------------
+
+/// A self-describing document as JSON.
+struct Document<J>(J);
+
+impl<'de> Deserialize<'de> for Document<Json> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(JsonVisitor).map(Document)
+    }
+}
+
+/// Builds JSON from whatever the format holds; 128 bit integers are left to
+/// `WideVisitor`, so CBOR bignums stay undecodable like other tags.
+struct JsonVisitor;
+
+impl<'de> Visitor<'de> for JsonVisitor {
+    type Value = Json;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a value JSON can hold")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Json, E> {
+        Ok(Json::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Json, E> {
+        Ok(Json::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Json, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Json, E> {
+        Ok(b.into())
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<Json, E> {
+        Ok(n.into())
+    }
+
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<Json, E> {
+        Ok(n.into())
+    }
+
+    fn visit_f32<E: de::Error>(self, x: f32) -> Result<Json, E> {
+        float32(x)
+    }
+
+    fn visit_f64<E: de::Error>(self, x: f64) -> Result<Json, E> {
+        float(x)
+    }
+
+    fn visit_char<E: de::Error>(self, c: char) -> Result<Json, E> {
+        Ok(Json::String(c.to_string()))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Json, E> {
+        Ok(Json::String(s.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, s: String) -> Result<Json, E> {
+        Ok(Json::String(s))
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Json, E> {
+        Ok(bytes_json(bytes))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Json, A::Error> {
+        let mut items = Vec::new();
+        while let Some(Document(item)) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Json::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Json, A::Error> {
+        let mut map = Map::new();
+        while let Some(Document(key)) = access.next_key()? {
+            let key = match key {
+                Json::String(key) => key,
+                key => return Err(de::Error::custom(format!("map key {key} is not a string"))),
+            };
+            map.insert(key, access.next_value::<Document<Json>>()?.0);
+        }
+        Ok(Json::Object(map))
+    }
+}
+
+impl Serialize for Document<&Json> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Json::Null => serializer.serialize_unit(),
+            Json::Bool(b) => serializer.serialize_bool(*b),
+            Json::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(u), _) => serializer.serialize_u64(u),
+                (None, Some(i)) => serializer.serialize_i64(i),
+                _ => serializer.serialize_f64(n.as_f64().unwrap_or_default()),
+            },
+            Json::String(s) => serializer.serialize_str(s),
+            Json::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(&Document(item))?;
+                }
+                seq.end()
+            }
+            Json::Object(map) => match json_bytes(self.0).map_err(ser::Error::custom)? {
+                Some(bytes) => serializer.serialize_bytes(&bytes),
+                None => {
+                    let mut out = serializer.serialize_map(Some(map.len()))?;
+                    for (key, item) in map {
+                        out.serialize_entry(key, &Document(item))?;
+                    }
+                    out.end()
+                }
+            },
+        }
+    }
+}
+
+// -- bincode and postcard ----------------------------------------------------
+
+/// The serialized struct, read as a tuple of its `fields`.
+struct Fields<'f>(&'f [Field]);
+
+impl<'de> DeserializeSeed<'de> for Fields<'_> {
+    type Value = Json;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Json, D::Error> {
+        deserializer.deserialize_tuple(self.0.len(), self)
+    }
+}
+
+impl<'de> Visitor<'de> for Fields<'_> {
+    type Value = Json;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a struct of {} fields", self.0.len())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Json, A::Error> {
+        let mut map = Map::new();
+        for (i, field) in self.0.iter().enumerate() {
+            let value = seq
+                .next_element_seed(FieldSeed(&field.kind))
+                .map_err(|e| de::Error::custom(format!("field '{}': {e}", field.name)))?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+            map.insert(field.name.clone(), value);
+        }
+        Ok(Json::Object(map))
+    }
+}
+
+/// One field, read with the deserializer call its type makes.
+struct FieldSeed<'f>(&'f FieldKind);
+
+impl<'de> DeserializeSeed<'de> for FieldSeed<'_> {
+    type Value = Json;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Json, D::Error> {
+        let tree = match self.0 {
+            FieldKind::Str(_) => return deserializer.deserialize_string(JsonVisitor),
+            FieldKind::Bytes(_) => return deserializer.deserialize_byte_buf(JsonVisitor),
+            FieldKind::Scalar(tree) => tree,
+        };
+        match tree {
+            TypeTree::Bool => deserializer.deserialize_bool(JsonVisitor),
+            TypeTree::F32 => deserializer.deserialize_f32(JsonVisitor),
+            TypeTree::F64 => deserializer.deserialize_f64(JsonVisitor),
+            TypeTree::Char => deserializer.deserialize_char(JsonVisitor),
+            TypeTree::U8 => deserializer.deserialize_u8(JsonVisitor),
+            TypeTree::U16 => deserializer.deserialize_u16(JsonVisitor),
+            TypeTree::U32 => deserializer.deserialize_u32(JsonVisitor),
+            TypeTree::U64 => deserializer.deserialize_u64(JsonVisitor),
+            TypeTree::U128 => deserializer.deserialize_u128(WideVisitor),
+            TypeTree::I8 => deserializer.deserialize_i8(JsonVisitor),
+            TypeTree::I16 => deserializer.deserialize_i16(JsonVisitor),
+            TypeTree::I32 => deserializer.deserialize_i32(JsonVisitor),
+            TypeTree::I64 => deserializer.deserialize_i64(JsonVisitor),
+            TypeTree::I128 => deserializer.deserialize_i128(WideVisitor),
+            other => Err(de::Error::custom(format!("{other} is not a scalar"))),
+        }
+    }
+}
+
+/// A 128 bit field, shown as a string past the range of JSON numbers.
+struct WideVisitor;
+
+impl Visitor<'_> for WideVisitor {
+    type Value = Json;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a 128 bit integer")
+    }
+
+    fn visit_u128<E: de::Error>(self, n: u128) -> Result<Json, E> {
+        Ok(wide_uint(n))
+    }
+
+    fn visit_i128<E: de::Error>(self, n: i128) -> Result<Json, E> {
+        Ok(wide_int(n))
+    }
+}
+
+/// A field value checked against its type, ready to serialize.
+enum Cell {
+    Bool(bool),
+    Uint(u128, usize),
+    Int(i128, usize),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    Str(String),
+    Bytes(Vec<u8>),
+}
+
+impl Serialize for Cell {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Cell::Bool(b) => serializer.serialize_bool(b),
+            Cell::Uint(n, 1) => serializer.serialize_u8(n as u8),
+            Cell::Uint(n, 2) => serializer.serialize_u16(n as u16),
+            Cell::Uint(n, 4) => serializer.serialize_u32(n as u32),
+            Cell::Uint(n, 8) => serializer.serialize_u64(n as u64),
+            Cell::Uint(n, _) => serializer.serialize_u128(n),
+            Cell::Int(n, 1) => serializer.serialize_i8(n as i8),
+            Cell::Int(n, 2) => serializer.serialize_i16(n as i16),
+            Cell::Int(n, 4) => serializer.serialize_i32(n as i32),
+            Cell::Int(n, 8) => serializer.serialize_i64(n as i64),
+            Cell::Int(n, _) => serializer.serialize_i128(n),
+            Cell::F32(x) => serializer.serialize_f32(x),
+            Cell::F64(x) => serializer.serialize_f64(x),
+            Cell::Char(c) => serializer.serialize_char(c),
+            Cell::Str(ref s) => serializer.serialize_str(s),
+            Cell::Bytes(ref bytes) => serializer.serialize_bytes(bytes),
+        }
+    }
+}
+
+/// The fields of a struct, serialized as a tuple in layout order.
+struct Cells(Vec<Cell>);
+
+impl Serialize for Cells {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(self.0.len())?;
+        for cell in &self.0 {
+            tuple.serialize_element(cell)?;
+        }
+        tuple.end()
+    }
+}
+
+/// Checks a JSON object against `fields`, the inverse of `Fields`.
+fn cells(fields: &[Field], value: &Json) -> Result<Cells, String> {
+    let map = value
+        .as_object()
+        .ok_or_else(|| format!("expected a JSON object of fields, got {value}"))?;
+    if let Some(unknown) = map
+        .keys()
+        .find(|key| !fields.iter().any(|field| field.name == **key))
+    {
+        return Err(format!("unknown field '{unknown}'"));
+    }
+    let mut cells = Vec::with_capacity(fields.len());
+    for field in fields {
+        let value = map
+            .get(&field.name)
+            .ok_or_else(|| format!("missing field '{}'", field.name))?;
+        let mismatch =
+            |expected: &str| format!("field '{}': expected {expected}, got {value}", field.name);
+        cells.push(match &field.kind {
+            FieldKind::Scalar(tree) => {
+                scalar_cell(tree, value).ok_or_else(|| mismatch(&tree.to_string()))?
+            }
+            FieldKind::Str(_) => Cell::Str(
+                value
+                    .as_str()
+                    .ok_or_else(|| mismatch("a string"))?
+                    .to_string(),
+            ),
+            FieldKind::Bytes(_) => {
+                Cell::Bytes(json_bytes(value)?.ok_or_else(|| mismatch("{\"$bytes\": \"0x..\"}"))?)
+            }
+        });
+    }
+    Ok(Cells(cells))
+}
+
+/// One scalar field; the caller reports the mismatch.
+fn scalar_cell(tree: &TypeTree, value: &Json) -> Option<Cell> {
+    let width = tree.fixed_width().unwrap_or(0);
+    let bits = 8 * width as u32;
+    // integers wider than JSON numbers are shown as strings
+    let int = || match value {
+        Json::Number(n) => n.as_i64().map(i128::from).or(n.as_u64().map(i128::from)),
+        Json::String(s) => s.parse().ok(),
+        _ => None,
+    };
+    let cell = match tree {
+        TypeTree::Bool => Cell::Bool(value.as_bool()?),
+        TypeTree::F32 => Cell::F32(value.as_f64()? as f32),
+        TypeTree::F64 => Cell::F64(value.as_f64()?),
+        TypeTree::Char => {
+            let mut chars = value.as_str()?.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Cell::Char(c),
+                _ => return None,
+            }
+        }
+        TypeTree::U8 | TypeTree::U16 | TypeTree::U32 | TypeTree::U64 | TypeTree::U128 => {
+            let n = match value {
+                Json::String(s) => s.parse::<u128>().ok(),
+                _ => int().and_then(|n| u128::try_from(n).ok()),
+            }
+            .filter(|&n| bits == 128 || n >> bits == 0)?;
+            Cell::Uint(n, width)
+        }
+        TypeTree::I8 | TypeTree::I16 | TypeTree::I32 | TypeTree::I64 | TypeTree::I128 => {
+            let n = int()
+                .filter(|&n| bits == 128 || (n >> (bits - 1) == 0 || n >> (bits - 1) == -1))?;
+            Cell::Int(n, width)
+        }
+        _ => return None,
+    };
+    Some(cell)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Layouts;
+    use serde_json::json;
+
+    fn record_fields() -> Vec<Field> {
+        let layouts = Layouts::parse(
+            r#"{"test::Record": {"codec": "postcard", "fields": [
+                {"name": "id", "type": "u32"},
+                {"name": "delta", "type": "i16"},
+                {"name": "name", "type": "str"},
+                {"name": "tag", "type": "char"},
+                {"name": "blob", "type": "bytes"}
+            ]}}"#,
+        )
+        .unwrap();
+        match &layouts.get("test::Record").unwrap().format {
+            crate::layout::Format::Codec { fields, .. } => fields.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn msgpack_roundtrips_through_json() {
+        // {"a": [1, -1, 300, -200], "b": bin [1, 2], "c": 1.5, "d": nil}
+        let data = [
+            0x84, 0xa1, b'a', 0x94, 0x01, 0xff, 0xcd, 0x01, 0x2c, 0xd1, 0xff, 0x38, 0xa1, b'b',
+            0xc4, 0x02, 0x01, 0x02, 0xa1, b'c', 0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0, 0xa1, b'd',
+            0xc0,
+        ];
+        let value = decode(Codec::MsgPack, &[], &data).unwrap();
+        assert_eq!(
+            value,
+            json!({"a": [1, -1, 300, -200], "b": {"$bytes": "0x0102"}, "c": 1.5, "d": null})
+        );
+        assert_eq!(encode(Codec::MsgPack, &[], &value).unwrap(), data);
+        assert!(decode(Codec::MsgPack, &[], &[0x92, 0x01]).is_err());
+        assert!(decode(Codec::MsgPack, &[], &[0x81, 0x01, 0x01])
+            .unwrap_err()
+            .contains("not a string"));
+    }
+
+    #[test]
+    fn cbor_roundtrips_through_json() {
+        // {"a": [1, -1, 500], "b": h'0102', "c": 1.5}
+        let data = [
+            0xa3, 0x61, b'a', 0x83, 0x01, 0x20, 0x19, 0x01, 0xf4, 0x61, b'b', 0x42, 0x01, 0x02,
+            0x61, b'c', 0xfb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0,
+        ];
+        let value = decode(Codec::Cbor, &[], &data).unwrap();
+        assert_eq!(
+            value,
+            json!({"a": [1, -1, 500], "b": {"$bytes": "0x0102"}, "c": 1.5})
+        );
+        // 1.5 is written back as a half float, which keeps its value
+        let mut half = data[..16].to_vec();
+        half.extend([0xf9, 0x3e, 0x00]);
+        assert_eq!(encode(Codec::Cbor, &[], &value).unwrap(), half);
+        assert_eq!(decode(Codec::Cbor, &[], &half).unwrap(), value);
+        // indefinite array, half float 1.5, tag
+        let value = decode(Codec::Cbor, &[], &[0x9f, 0xf9, 0x3e, 0x00, 0xff]).unwrap();
+        assert_eq!(value, json!([1.5]));
+        assert!(decode(Codec::Cbor, &[], &[0xc1, 0x01]).is_err());
+    }
+
+    #[test]
+    fn bincode_and_postcard_follow_the_fields() {
+        let fields = record_fields();
+        let value = json!({
+            "id": 300, "delta": -2, "name": "ab", "tag": "é", "blob": {"$bytes": "0xff"}
+        });
+        let bincode = [
+            0x2c, 0x01, 0, 0, 0xfe, 0xff, 2, 0, 0, 0, 0, 0, 0, 0, b'a', b'b', 0xc3, 0xa9, 1, 0, 0,
+            0, 0, 0, 0, 0, 0xff,
+        ];
+        assert_eq!(encode(Codec::Bincode, &fields, &value).unwrap(), bincode);
+        assert_eq!(decode(Codec::Bincode, &fields, &bincode).unwrap(), value);
+        let postcard = [0xac, 0x02, 0x03, 2, b'a', b'b', 2, 0xc3, 0xa9, 1, 0xff];
+        assert_eq!(encode(Codec::Postcard, &fields, &value).unwrap(), postcard);
+        assert_eq!(decode(Codec::Postcard, &fields, &postcard).unwrap(), value);
+
+        let err = encode(Codec::Postcard, &fields, &json!({"id": -1})).unwrap_err();
+        assert!(err.contains("field 'id'"), "{err}");
+        assert!(decode(Codec::Postcard, &fields, &postcard[..4]).is_err());
+    }
+}
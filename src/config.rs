@@ -0,0 +1,231 @@
+use crate::layout::{Codec, Layout, Layouts};
+use crate::Error;
+use serde_json::{json, Map, Value as Json};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// How the documents serialized in a table's `&[u8]` values are decoded,
+/// see the `decode` command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueDecoder {
+    pub codec: Codec,
+    /// Name of the loaded layout giving the fields for codecs that are not
+    /// self-describing (bincode, postcard).
+    pub schema: Option<String>,
+}
+
+impl ValueDecoder {
+    /// The layout values are decoded through; the schema must be loaded.
+    pub fn layout(&self) -> Result<Layout, String> {
+        let schema = match &self.schema {
+            Some(name) => Some(
+                Layouts::installed()
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| format!("layout '{name}' is not loaded, see 'layouts'"))?,
+            ),
+            None => None,
+        };
+        Layout::serialized(self.codec, schema.as_deref())
+    }
+}
+
+impl fmt::Display for ValueDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.schema {
+            Some(schema) => write!(f, "{} with schema {schema}", self.codec),
+            None => write!(f, "{}", self.codec),
+        }
+    }
+}
+
+/// Settings kept between sessions, stored as JSON:
+///
+/// ```json
+/// {"decoders": {"/data/app.redb": {"events": {"value": "msgpack"}}}}
+/// ```
+#[derive(Debug, Default)]
+pub struct Config {
+    /// File the settings are saved to; `None` keeps them in memory.
+    path: Option<PathBuf>,
+    /// Value decoders by database path, then table name.
+    decoders: BTreeMap<String, BTreeMap<String, ValueDecoder>>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/redbcli/config.json`, or
+    /// `~/.config/redbcli/config.json`.
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(dir.join("redbcli").join("config.json"))
+    }
+
+    /// Reads the config file at `path`, which `save` writes back to; a
+    /// missing file is an empty config.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Config, Error> {
+        let path = path.into();
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let decoders = Config::parse(&text).map_err(|message| Error::InvalidConfig {
+            path: path.display().to_string(),
+            message,
+        })?;
+        Ok(Config {
+            path: Some(path),
+            decoders,
+        })
+    }
+
+    fn parse(text: &str) -> Result<BTreeMap<String, BTreeMap<String, ValueDecoder>>, String> {
+        let mut decoders = BTreeMap::new();
+        if text.trim().is_empty() {
+            return Ok(decoders);
+        }
+        let root: Json = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let root = root.as_object().ok_or("expected an object")?;
+        let Some(by_db) = root.get("decoders") else {
+            return Ok(decoders);
+        };
+        let by_db = by_db.as_object().ok_or("'decoders' must be an object")?;
+        for (db, tables) in by_db {
+            let tables = tables
+                .as_object()
+                .ok_or(format!("decoders of '{db}' must be an object"))?;
+            let mut parsed = BTreeMap::new();
+            for (table, spec) in tables {
+                let text = |key: &str| spec.get(key).and_then(Json::as_str);
+                let codec = text("value").and_then(Codec::parse).ok_or(format!(
+                    "decoder of table '{table}' in '{db}': 'value' must be {}",
+                    Codec::NAMES
+                ))?;
+                let schema = text("schema").map(str::to_string);
+                parsed.insert(table.clone(), ValueDecoder { codec, schema });
+            }
+            decoders.insert(db.clone(), parsed);
+        }
+        Ok(decoders)
+    }
+
+    /// Where the config is saved, if anywhere.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Writes the config to its file, creating the directory if needed.
+    pub fn save(&self) -> Result<(), Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let by_db: Map<String, Json> = self
+            .decoders
+            .iter()
+            .map(|(db, tables)| {
+                let tables = tables
+                    .iter()
+                    .map(|(table, decoder)| {
+                        let mut spec = json!({"value": decoder.codec.name()});
+                        if let Some(schema) = &decoder.schema {
+                            spec["schema"] = json!(schema);
+                        }
+                        (table.clone(), spec)
+                    })
+                    .collect();
+                (db.clone(), Json::Object(tables))
+            })
+            .collect();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(&json!({ "decoders": by_db }))
+            .map_err(std::io::Error::other)?;
+        std::fs::write(path, text + "\n")?;
+        Ok(())
+    }
+
+    /// The decoder of table `table` in database `db`.
+    pub fn decoder(&self, db: &str, table: &str) -> Option<&ValueDecoder> {
+        self.decoders.get(db)?.get(table)
+    }
+
+    /// The decoders of database `db`, by table name.
+    pub fn decoders(&self, db: &str) -> impl Iterator<Item = (&String, &ValueDecoder)> {
+        self.decoders.get(db).into_iter().flatten()
+    }
+
+    /// Sets, or with `None` clears, the decoder of table `table` in `db`.
+    pub fn set_decoder(&mut self, db: &str, table: &str, decoder: Option<ValueDecoder>) {
+        match decoder {
+            Some(decoder) => {
+                self.decoders
+                    .entry(db.to_string())
+                    .or_default()
+                    .insert(table.to_string(), decoder);
+            }
+            None => {
+                if let Some(tables) = self.decoders.get_mut(db) {
+                    tables.remove(table);
+                    if tables.is_empty() {
+                        self.decoders.remove(db);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoders_survive_a_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("config.json");
+        let mut config = Config::load(&path).unwrap();
+        config.set_decoder(
+            "/a.redb",
+            "events",
+            Some(ValueDecoder {
+                codec: Codec::Postcard,
+                schema: Some("app::Event".into()),
+            }),
+        );
+        config.set_decoder(
+            "/a.redb",
+            "blobs",
+            Some(ValueDecoder {
+                codec: Codec::Cbor,
+                schema: None,
+            }),
+        );
+        config.set_decoder("/a.redb", "blobs", None);
+        config.save().unwrap();
+
+        let config = Config::load(&path).unwrap();
+        let decoder = config.decoder("/a.redb", "events").unwrap();
+        assert_eq!(decoder.to_string(), "postcard with schema app::Event");
+        assert_eq!(config.decoders("/a.redb").count(), 1);
+        assert!(config.decoder("/b.redb", "events").is_none());
+    }
+
+    #[test]
+    fn invalid_config_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{"decoders": {"/a.redb": {"t": {"value": "xml"}}}}"#,
+        )
+        .unwrap();
+        let err = Config::load(&path).unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig { .. }), "{err}");
+        assert!(err.to_string().contains("'value' must be"), "{err}");
+    }
+}
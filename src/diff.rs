@@ -0,0 +1,199 @@
+use std::collections::{HashMap, HashSet};
+
+/// One row that differs between two versions of a table. Keys and values
+/// are formatted cells, as produced by `dynread::read_table_dyn`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowChange {
+    Added {
+        key: String,
+        value: String,
+    },
+    Removed {
+        key: String,
+        value: String,
+    },
+    Changed {
+        key: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl RowChange {
+    pub fn key(&self) -> &str {
+        match self {
+            RowChange::Added { key, .. }
+            | RowChange::Removed { key, .. }
+            | RowChange::Changed { key, .. } => key,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableChange {
+    Created,
+    Dropped,
+    Modified,
+    /// The table differs or may differ, but its rows cannot be decoded.
+    NotComparable(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableDiff {
+    pub name: String,
+    pub change: TableChange,
+    pub rows: Vec<RowChange>,
+}
+
+/// Rows of one table, or why they could not be read.
+pub type TableRows = Result<Vec<(String, String)>, String>;
+
+/// Compares two versions of a table. Removed and changed rows keep the
+/// order of `before`, added rows the order of `after`.
+pub fn diff_rows(before: &[(String, String)], after: &[(String, String)]) -> Vec<RowChange> {
+    let after_map: HashMap<&str, &str> = after
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    let before_keys: HashSet<&str> = before.iter().map(|(k, _)| k.as_str()).collect();
+    let mut changes = Vec::new();
+    for (key, value) in before {
+        match after_map.get(key.as_str()) {
+            None => changes.push(RowChange::Removed {
+                key: key.clone(),
+                value: value.clone(),
+            }),
+            Some(new) if *new != value => changes.push(RowChange::Changed {
+                key: key.clone(),
+                old: value.clone(),
+                new: new.to_string(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (key, value) in after {
+        if !before_keys.contains(key.as_str()) {
+            changes.push(RowChange::Added {
+                key: key.clone(),
+                value: value.clone(),
+            });
+        }
+    }
+    changes
+}
+
+/// Compares two sets of tables by name. Only tables that differ are
+/// returned, ordered by name.
+pub fn diff_tables(
+    before: Vec<(String, TableRows)>,
+    after: Vec<(String, TableRows)>,
+) -> Vec<TableDiff> {
+    let mut before: HashMap<String, TableRows> = before.into_iter().collect();
+    let mut result = Vec::new();
+    for (name, after_rows) in after {
+        let diff = match (before.remove(&name), after_rows) {
+            (None, Ok(rows)) => TableDiff {
+                name,
+                change: TableChange::Created,
+                rows: diff_rows(&[], &rows),
+            },
+            (None, Err(_)) => TableDiff {
+                name,
+                change: TableChange::Created,
+                rows: Vec::new(),
+            },
+            (Some(Ok(old)), Ok(new)) => {
+                let rows = diff_rows(&old, &new);
+                if rows.is_empty() {
+                    continue;
+                }
+                TableDiff {
+                    name,
+                    change: TableChange::Modified,
+                    rows,
+                }
+            }
+            (Some(Err(e)), _) | (Some(_), Err(e)) => TableDiff {
+                name,
+                change: TableChange::NotComparable(e),
+                rows: Vec::new(),
+            },
+        };
+        result.push(diff);
+    }
+    for (name, before_rows) in before {
+        result.push(TableDiff {
+            name,
+            change: TableChange::Dropped,
+            rows: before_rows
+                .map(|rows| diff_rows(&rows, &[]))
+                .unwrap_or_default(),
+        });
+    }
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(data: &[(&str, &str)]) -> Vec<(String, String)> {
+        data.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn diff_rows_reports_all_kinds() {
+        let before = rows(&[("1", "a"), ("2", "b"), ("3", "c")]);
+        let after = rows(&[("1", "a"), ("3", "C"), ("4", "d")]);
+        assert_eq!(
+            diff_rows(&before, &after),
+            vec![
+                RowChange::Removed {
+                    key: "2".to_string(),
+                    value: "b".to_string()
+                },
+                RowChange::Changed {
+                    key: "3".to_string(),
+                    old: "c".to_string(),
+                    new: "C".to_string()
+                },
+                RowChange::Added {
+                    key: "4".to_string(),
+                    value: "d".to_string()
+                },
+            ]
+        );
+        assert!(diff_rows(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn diff_tables_skips_unchanged_and_sorts() {
+        let before = vec![
+            ("same".to_string(), Ok(rows(&[("1", "a")]))),
+            ("gone".to_string(), Ok(rows(&[("1", "a")]))),
+            ("edited".to_string(), Ok(rows(&[("1", "a")]))),
+        ];
+        let after = vec![
+            ("same".to_string(), Ok(rows(&[("1", "a")]))),
+            ("edited".to_string(), Ok(rows(&[("1", "b")]))),
+            ("new".to_string(), Ok(rows(&[]))),
+            ("opaque".to_string(), Err("unsupported".to_string())),
+        ];
+        let diff = diff_tables(before, after);
+        let summary: Vec<(&str, &TableChange)> =
+            diff.iter().map(|d| (d.name.as_str(), &d.change)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("edited", &TableChange::Modified),
+                ("gone", &TableChange::Dropped),
+                ("new", &TableChange::Created),
+                ("opaque", &TableChange::Created),
+            ]
+        );
+        assert_eq!(diff[1].rows.len(), 1);
+    }
+}
@@ -1,13 +1,28 @@
+use crate::dynvalue::DynValue;
+use crate::layout::Layout;
+use crate::lens::{Lens, Lenses};
+use crate::typecodec::{self, RuntimeKey, RuntimeValue, TableCodec, TypeTree};
+use crate::Error;
 use redb::{
-    Key, ReadTransaction, ReadableTable, TableDefinition, TableError, Value, WriteTransaction,
+    ReadTransaction, ReadableTable, TableDefinition, TableError, TableHandle, TypeName, Value,
+    WriteTransaction,
 };
-use std::borrow::Borrow;
-use std::fmt::Debug;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::{Bound, ControlFlow, RangeBounds};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct TableTypeDesc {
     pub key: String,
     pub value: String,
+    /// Layout the documents serialized in `&[u8]` values are decoded
+    /// through, set by the `decode` command; `None` shows them as hex.
+    pub value_codec: Option<Arc<Layout>>,
+    /// How the key and value cells are shown and typed, set by the `lens`
+    /// command.
+    pub lenses: Lenses,
 }
 
 pub fn type_name_of<T: Value + 'static>() -> String {
@@ -15,87 +30,132 @@ pub fn type_name_of<T: Value + 'static>() -> String {
 }
 
 /// Probes the persisted key/value types of a table by attempting a typed open.
-pub fn probe_table_type(txn: &ReadTransaction, name: &str) -> Result<TableTypeDesc, TableError> {
-    let definition: TableDefinition<&str, &str> = TableDefinition::new(name);
-    match txn.open_table(definition) {
-        Ok(_) => Ok(TableTypeDesc {
-            key: "&str".to_string(),
-            value: "&str".to_string(),
-        }),
-        Err(TableError::TableTypeMismatch { key, value, .. }) => Ok(TableTypeDesc {
-            key: key.name().to_string(),
-            value: value.name().to_string(),
-        }),
-        Err(e) => Err(e),
-    }
+pub fn probe_table_type(txn: &impl RowSource, name: &str) -> Result<TableTypeDesc, TableError> {
+    txn.probe_type(name)
 }
 
-// ---------------------------------------------------------------------------
-// Formatting (table cells -> display strings)
-// ---------------------------------------------------------------------------
-
-trait FmtCell {
-    fn fmt_cell(&self) -> String;
-}
-
-macro_rules! impl_fmt_cell_debug {
-    ($($t:ty),*) => {
-        $(impl FmtCell for $t {
-            fn fmt_cell(&self) -> String {
-                format!("{self:?}")
-            }
-        })*
-    };
+fn probe_result<T>(opened: Result<T, TableError>) -> Result<[TypeName; 2], TableError> {
+    match opened {
+        Ok(_) => Ok([<&str>::type_name(), <&str>::type_name()]),
+        Err(TableError::TableTypeMismatch { key, value, .. }) => Ok([key, value]),
+        Err(e) => Err(e),
+    }
 }
 
-impl_fmt_cell_debug!(
-    u8,
-    u16,
-    u32,
-    u64,
-    u128,
-    i8,
-    i16,
-    i32,
-    i64,
-    i128,
-    f32,
-    f64,
-    bool,
-    char,
-    ()
-);
+/// Receives the stored key and value bytes of one row.
+pub type RawVisitor<'v> = dyn FnMut(&[u8], &[u8]) -> ControlFlow<()> + 'v;
 
-impl FmtCell for &str {
-    fn fmt_cell(&self) -> String {
-        self.to_string()
+/// A transaction that table rows can be read from. Besides
+/// `ReadTransaction` this covers `WriteTransaction`, so a write can inspect
+/// its own uncommitted state (e.g. after restoring a savepoint).
+pub trait RowSource {
+    fn table_names(&self) -> Result<Vec<String>, redb::Error>;
+    /// The key and value type names persisted for table `name`, exactly as
+    /// redb compares them on open.
+    fn probe_names(&self, name: &str) -> Result<[TypeName; 2], TableError>;
+    fn probe_type(&self, name: &str) -> Result<TableTypeDesc, TableError> {
+        let [key, value] = self.probe_names(name)?;
+        Ok(TableTypeDesc {
+            key: key.name().to_string(),
+            value: value.name().to_string(),
+            ..Default::default()
+        })
     }
+    /// Calls `visit` with the stored key and value bytes of each row whose
+    /// key is within `start`..`end`, in key order, until it breaks. The
+    /// table is opened with the types of the entered `TableScope`.
+    fn visit_raw(
+        &self,
+        name: &str,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        visit: &mut RawVisitor<'_>,
+    ) -> Result<(), redb::Error>;
 }
 
-impl FmtCell for String {
-    fn fmt_cell(&self) -> String {
-        self.clone()
+impl RowSource for ReadTransaction {
+    fn table_names(&self) -> Result<Vec<String>, redb::Error> {
+        Ok(self
+            .list_tables()?
+            .map(|table| table.name().to_string())
+            .collect())
     }
-}
-
-impl FmtCell for &[u8] {
-    fn fmt_cell(&self) -> String {
-        bytes_to_hex(self)
+    fn probe_names(&self, name: &str) -> Result<[TypeName; 2], TableError> {
+        let definition: TableDefinition<&str, &str> = TableDefinition::new(name);
+        probe_result(self.open_table(definition))
+    }
+    fn visit_raw(
+        &self,
+        name: &str,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        visit: &mut RawVisitor<'_>,
+    ) -> Result<(), redb::Error> {
+        visit_range(
+            &self.open_table(typecodec::definition(name))?,
+            start,
+            end,
+            visit,
+        )
     }
 }
 
-impl<A: Debug, B: Debug> FmtCell for (A, B) {
-    fn fmt_cell(&self) -> String {
-        format!("{self:?}")
+impl RowSource for WriteTransaction {
+    fn table_names(&self) -> Result<Vec<String>, redb::Error> {
+        Ok(self
+            .list_tables()?
+            .map(|table| table.name().to_string())
+            .collect())
+    }
+    fn probe_names(&self, name: &str) -> Result<[TypeName; 2], TableError> {
+        // opening a missing table in a write transaction would create it
+        let exists = self
+            .list_tables()
+            .map_err(TableError::Storage)?
+            .any(|table| table.name() == name);
+        if !exists {
+            return Err(TableError::TableDoesNotExist(name.to_string()));
+        }
+        let definition: TableDefinition<&str, &str> = TableDefinition::new(name);
+        probe_result(self.open_table(definition))
+    }
+    fn visit_raw(
+        &self,
+        name: &str,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        visit: &mut RawVisitor<'_>,
+    ) -> Result<(), redb::Error> {
+        visit_range(
+            &self.open_table(typecodec::definition(name))?,
+            start,
+            end,
+            visit,
+        )
     }
 }
 
-impl<A: Debug, B: Debug, C: Debug> FmtCell for (A, B, C) {
-    fn fmt_cell(&self) -> String {
-        format!("{self:?}")
+fn visit_range(
+    table: &impl ReadableTable<RuntimeKey, RuntimeValue>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+    visit: &mut RawVisitor<'_>,
+) -> Result<(), redb::Error> {
+    // rows are handed out one at a time straight from the open range, so
+    // memory use does not grow with the table
+    let mut iter = table.range::<&[u8]>((start, end))?;
+    while let Some((key, value)) = iter.next().transpose()? {
+        if visit(key.value(), value.value()).is_break() {
+            break;
+        }
     }
+    Ok(())
 }
 
+// ---------------------------------------------------------------------------
+// Formatting (stored bytes -> display strings)
+// ---------------------------------------------------------------------------
+
 pub fn bytes_to_hex(data: &[u8]) -> String {
     let mut out = String::with_capacity(2 + data.len() * 2);
     out.push_str("0x");
@@ -109,28 +169,50 @@ pub fn bytes_to_hex(data: &[u8]) -> String {
 // Parsing (edited strings -> typed values)
 // ---------------------------------------------------------------------------
 
-/// Owned representation of a redb key/value type, used to parse an edited
-/// cell back into a typed argument for `insert`/`remove`.
-///
-/// Two parse entry points exist because formatting differs by context:
-/// - `parse_cell`: inverse of `FmtCell::fmt_cell` (standalone cell)
-/// - `parse_elem`: inverse of `Debug` formatting inside tuples (quoted
-///   strings, `[1, 2]` byte arrays)
-pub trait OwnedCell<K: Value + 'static>: Sized {
-    fn parse_cell(text: &str) -> Result<Self, String>;
-    fn parse_elem(text: &str) -> Result<Self, String>;
-    fn as_arg(&self) -> <K as Value>::SelfType<'_>;
+/// Why a cell could not be parsed; `position` is the byte offset in the
+/// cell text where the problem starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
 }
 
-fn unescape(text: &str) -> Result<String, String> {
+impl ParseError {
+    pub(crate) fn new(position: usize, message: impl Into<String>) -> Self {
+        ParseError {
+            position,
+            message: message.into(),
+        }
+    }
+    /// Moves the error from a sub-slice into the text it was cut from.
+    pub(crate) fn shifted(mut self, offset: usize) -> Self {
+        self.position += offset;
+        self
+    }
+    /// Attaches the cell text, ex: "invalid key '(1, x)'".
+    pub fn into_error(self, input: &str, context: String) -> crate::Error {
+        crate::Error::Parse {
+            input: input.to_string(),
+            position: self.position,
+            message: format!("{context}: {}", self.message),
+        }
+    }
+}
+
+/// Byte length of the leading whitespace `trim` would remove.
+pub(crate) fn lead(text: &str) -> usize {
+    text.len() - text.trim_start().len()
+}
+
+fn unescape(text: &str) -> Result<String, ParseError> {
     let mut out = String::with_capacity(text.len());
-    let mut chars = text.chars();
-    while let Some(c) = chars.next() {
+    let mut chars = text.char_indices();
+    while let Some((at, c)) = chars.next() {
         if c != '\\' {
             out.push(c);
             continue;
         }
-        match chars.next() {
+        match chars.next().map(|(_, c)| c) {
             Some('n') => out.push('\n'),
             Some('r') => out.push('\r'),
             Some('t') => out.push('\t'),
@@ -139,43 +221,49 @@ fn unescape(text: &str) -> Result<String, String> {
             Some('\'') => out.push('\''),
             Some('"') => out.push('"'),
             Some('u') => {
-                if chars.next() != Some('{') {
-                    return Err("invalid \\u escape".to_string());
+                if chars.next().map(|(_, c)| c) != Some('{') {
+                    return Err(ParseError::new(at, "invalid \\u escape"));
                 }
                 let mut hex = String::new();
                 let mut closed = false;
-                for h in chars.by_ref() {
+                for (_, h) in chars.by_ref() {
                     if h == '}' {
                         closed = true;
                         break;
                     }
                     hex.push(h);
                     if hex.len() > 6 {
-                        return Err("\\u escape too long".to_string());
+                        return Err(ParseError::new(at, "\\u escape too long"));
                     }
                 }
                 if !closed {
-                    return Err("unterminated \\u escape".to_string());
+                    return Err(ParseError::new(at, "unterminated \\u escape"));
                 }
-                let cp =
-                    u32::from_str_radix(&hex, 16).map_err(|_| "invalid \\u escape".to_string())?;
-                out.push(char::from_u32(cp).ok_or("invalid codepoint in \\u escape")?);
+                let cp = u32::from_str_radix(&hex, 16)
+                    .map_err(|_| ParseError::new(at, "invalid \\u escape"))?;
+                out.push(
+                    char::from_u32(cp)
+                        .ok_or_else(|| ParseError::new(at, "invalid codepoint in \\u escape"))?,
+                );
             }
-            other => return Err(format!("unknown escape {other:?}")),
+            other => return Err(ParseError::new(at, format!("unknown escape {other:?}"))),
         }
     }
     Ok(out)
 }
 
 /// Splits "a, b, c" on top-level commas, respecting brackets and quotes.
-fn split_top_level(text: &str) -> Result<Vec<String>, String> {
+/// Every element comes with its byte offset in `text`.
+pub(crate) fn split_top_level(text: &str) -> Result<Vec<(usize, String)>, ParseError> {
     let mut elems = Vec::new();
     let mut depth = 0i32;
     let mut in_single = false;
     let mut in_double = false;
     let mut escaped = false;
+    let mut quote_at = 0;
+    let mut start = 0;
     let mut cur = String::new();
-    for c in text.chars() {
+    for (at, c) in text.char_indices() {
         if escaped {
             cur.push(c);
             escaped = false;
@@ -189,521 +277,1163 @@ fn split_top_level(text: &str) -> Result<Vec<String>, String> {
         match c {
             '"' if !in_single => {
                 in_double = !in_double;
+                quote_at = at;
                 cur.push(c);
             }
             '\'' if !in_double => {
                 in_single = !in_single;
+                quote_at = at;
                 cur.push(c);
             }
-            '(' | '[' if !in_single && !in_double => {
+            '(' | '[' | '{' if !in_single && !in_double => {
                 depth += 1;
                 cur.push(c);
             }
-            ')' | ']' if !in_single && !in_double => {
+            ')' | ']' | '}' if !in_single && !in_double => {
                 depth -= 1;
                 cur.push(c);
             }
             ',' if depth == 0 && !in_single && !in_double => {
-                elems.push(cur.clone());
+                elems.push((start, cur.clone()));
                 cur.clear();
+                start = at + 1;
             }
             _ => cur.push(c),
         }
     }
     if in_single || in_double || escaped {
-        return Err("unbalanced quotes".to_string());
+        return Err(ParseError::new(quote_at, "unbalanced quotes"));
     }
-    elems.push(cur);
+    elems.push((start, cur));
     Ok(elems)
 }
 
-fn parse_char_text(text: &str) -> Result<char, String> {
+pub(crate) fn parse_char_text(text: &str) -> Result<char, ParseError> {
     let t = text.trim();
+    let at = lead(text);
     let inner = t
         .strip_prefix('\'')
         .and_then(|x| x.strip_suffix('\''))
-        .ok_or_else(|| format!("expected char like 'a', got '{t}'"))?;
-    let s = unescape(inner)?;
+        .ok_or_else(|| ParseError::new(at, format!("expected char like 'a', got '{t}'")))?;
+    let s = unescape(inner).map_err(|e| e.shifted(at + 1))?;
     let mut it = s.chars();
-    let c = it.next().ok_or("empty char")?;
+    let c = it.next().ok_or_else(|| ParseError::new(at, "empty char"))?;
     if it.next().is_some() {
-        return Err(format!("expected a single char, got '{inner}'"));
+        return Err(ParseError::new(
+            at,
+            format!("expected a single char, got '{inner}'"),
+        ));
     }
     Ok(c)
 }
 
-fn parse_quoted_str(text: &str) -> Result<String, String> {
+pub(crate) fn parse_quoted_str(text: &str) -> Result<String, ParseError> {
     let t = text.trim();
+    let at = lead(text);
     let inner = t
         .strip_prefix('"')
         .and_then(|x| x.strip_suffix('"'))
-        .ok_or_else(|| format!("expected quoted string like \"abc\", got '{t}'"))?;
-    unescape(inner)
-}
-
-macro_rules! impl_owned_scalar {
-    ($($t:ty),*) => {
-        $(
-            impl OwnedCell<$t> for $t {
-                fn parse_cell(text: &str) -> Result<Self, String> {
-                    text.trim()
-                        .parse::<$t>()
-                        .map_err(|_| format!("expected {}, got '{}'", type_name_of::<$t>(), text.trim()))
-                }
-                fn parse_elem(text: &str) -> Result<Self, String> {
-                    Self::parse_cell(text)
-                }
-                fn as_arg(&self) -> <$t as Value>::SelfType<'_> {
-                    *self
-                }
-            }
-        )*
-    };
-}
-
-impl_owned_scalar!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64, bool);
-
-impl OwnedCell<()> for () {
-    fn parse_cell(text: &str) -> Result<Self, String> {
-        if text.trim() == "()" {
-            Ok(())
-        } else {
-            Err(format!("expected '()', got '{}'", text.trim()))
-        }
-    }
-    fn parse_elem(text: &str) -> Result<Self, String> {
-        Self::parse_cell(text)
-    }
-    fn as_arg(&self) -> <() as Value>::SelfType<'_> {}
-}
-
-impl OwnedCell<char> for char {
-    fn parse_cell(text: &str) -> Result<Self, String> {
-        parse_char_text(text)
-    }
-    fn parse_elem(text: &str) -> Result<Self, String> {
-        Self::parse_cell(text)
-    }
-    fn as_arg(&self) -> <char as Value>::SelfType<'_> {
-        *self
-    }
+        .ok_or_else(|| {
+            ParseError::new(
+                at,
+                format!("expected quoted string like \"abc\", got '{t}'"),
+            )
+        })?;
+    unescape(inner).map_err(|e| e.shifted(at + 1))
 }
 
-impl OwnedCell<&'static str> for String {
-    fn parse_cell(text: &str) -> Result<Self, String> {
-        Ok(text.to_string())
-    }
-    fn parse_elem(text: &str) -> Result<Self, String> {
-        parse_quoted_str(text)
-    }
-    fn as_arg(&self) -> <&'static str as Value>::SelfType<'_> {
-        self.as_str()
-    }
-}
-
-impl OwnedCell<String> for String {
-    fn parse_cell(text: &str) -> Result<Self, String> {
-        Ok(text.to_string())
-    }
-    fn parse_elem(text: &str) -> Result<Self, String> {
-        parse_quoted_str(text)
-    }
-    fn as_arg(&self) -> <String as Value>::SelfType<'_> {
-        self.clone()
-    }
-}
-
-fn parse_hex_cell(text: &str) -> Result<Vec<u8>, String> {
+pub(crate) fn parse_hex_cell(text: &str) -> Result<Vec<u8>, ParseError> {
     let t = text.trim();
+    let at = lead(text);
     let hex = t
         .strip_prefix("0x")
-        .ok_or_else(|| format!("expected 0x-prefixed hex, got '{t}'"))?;
+        .ok_or_else(|| ParseError::new(at, format!("expected 0x-prefixed hex, got '{t}'")))?;
     if hex.len() % 2 != 0 {
-        return Err(format!("odd-length hex value '{hex}'"));
+        return Err(ParseError::new(
+            at + 2,
+            format!("odd-length hex value '{hex}'"),
+        ));
     }
     (0..hex.len())
         .step_by(2)
         .map(|i| {
-            u8::from_str_radix(&hex[i..i + 2], 16)
-                .map_err(|_| format!("invalid hex byte '{}'", &hex[i..i + 2]))
+            let pair = hex.get(i..i + 2).unwrap_or(&hex[i..]);
+            u8::from_str_radix(pair, 16)
+                .map_err(|_| ParseError::new(at + 2 + i, format!("invalid hex byte '{pair}'")))
         })
         .collect()
 }
 
-fn parse_byte_list(text: &str) -> Result<Vec<u8>, String> {
+/// Parses a raw bytes cell: `0x` hex, optionally followed by the `|ascii|`
+/// column `DynValue::Raw` displays, which is ignored.
+pub(crate) fn parse_raw_cell(text: &str) -> Result<Vec<u8>, ParseError> {
+    let t = text.trim_end();
+    match t.find(" |") {
+        Some(end) if t.ends_with('|') => parse_hex_cell(&t[..end]),
+        _ => parse_hex_cell(text),
+    }
+}
+
+pub(crate) fn parse_byte_list(text: &str) -> Result<Vec<u8>, ParseError> {
     let t = text.trim();
+    let at = lead(text);
     let inner = t
         .strip_prefix('[')
         .and_then(|x| x.strip_suffix(']'))
-        .ok_or_else(|| format!("expected byte list like [1, 2], got '{t}'"))?;
+        .ok_or_else(|| ParseError::new(at, format!("expected byte list like [1, 2], got '{t}'")))?;
     if inner.trim().is_empty() {
         return Ok(Vec::new());
     }
-    inner
-        .split(',')
-        .map(|part| {
-            part.trim()
-                .parse::<u8>()
-                .map_err(|_| format!("invalid byte '{part}'"))
-        })
-        .collect()
+    let mut offset = at + 1;
+    let mut bytes = Vec::new();
+    for part in inner.split(',') {
+        let byte = part
+            .trim()
+            .parse::<u8>()
+            .map_err(|_| ParseError::new(offset + lead(part), format!("invalid byte '{part}'")))?;
+        bytes.push(byte);
+        offset += part.len() + 1;
+    }
+    Ok(bytes)
 }
 
-impl OwnedCell<&'static [u8]> for Vec<u8> {
-    fn parse_cell(text: &str) -> Result<Self, String> {
-        parse_hex_cell(text)
+// ---------------------------------------------------------------------------
+// Table access through the runtime codec
+// ---------------------------------------------------------------------------
+
+fn unsupported(table: &str, desc: &TableTypeDesc, operation: &'static str) -> Error {
+    Error::UnsupportedType {
+        table: table.to_string(),
+        key: desc.key.clone(),
+        value: desc.value.clone(),
+        operation,
     }
-    fn parse_elem(text: &str) -> Result<Self, String> {
-        parse_byte_list(text)
+}
+
+/// The codec of a table whose probed types are `desc`; only types built
+/// from redb's built-in types can be decoded.
+fn table_codec(
+    name: &str,
+    desc: &TableTypeDesc,
+    operation: &'static str,
+) -> Result<TableCodec, Error> {
+    TableCodec::new(desc).ok_or_else(|| unsupported(name, desc, operation))
+}
+
+impl TableTypeDesc {
+    /// Whether both types can be decoded: built from redb's built-in types
+    /// or described by a loaded layout. Rows of other tables are only shown
+    /// as raw bytes, and can only be deleted.
+    pub fn is_decodable(&self) -> bool {
+        TableCodec::new(self).is_some()
     }
-    fn as_arg(&self) -> <&'static [u8] as Value>::SelfType<'_> {
-        self.as_slice()
+}
+
+/// The codec used to read a table: `table_codec` when its types can be
+/// decoded, otherwise raw bytes under the persisted type names.
+fn read_codec(txn: &impl RowSource, name: &str, desc: &TableTypeDesc) -> Result<TableCodec, Error> {
+    match TableCodec::new(desc) {
+        Some(codec) => Ok(codec),
+        None => raw_codec(txn, name),
     }
 }
 
-macro_rules! impl_owned_tuple2 {
-    ($k:ty, $o:ty, |$s:ident| $arg:expr) => {
-        impl OwnedCell<($k, $k)> for ($o, $o) {
-            fn parse_cell(text: &str) -> Result<Self, String> {
-                let inner = text
-                    .trim()
-                    .strip_prefix('(')
-                    .and_then(|x| x.strip_suffix(')'))
-                    .ok_or_else(|| format!("expected tuple like (a, b), got '{}'", text.trim()))?;
-                let elems = split_top_level(inner)?;
-                if elems.len() != 2 {
-                    return Err(format!(
-                        "expected 2 tuple elements, got {} in '{text}'",
-                        elems.len()
-                    ));
-                }
-                Ok((
-                    <$o as OwnedCell<$k>>::parse_elem(elems[0].trim())?,
-                    <$o as OwnedCell<$k>>::parse_elem(elems[1].trim())?,
-                ))
-            }
-            fn parse_elem(text: &str) -> Result<Self, String> {
-                <($o, $o) as OwnedCell<($k, $k)>>::parse_cell(text)
-            }
-            fn as_arg(&self) -> <($k, $k) as Value>::SelfType<'_> {
-                let $s = self;
-                $arg
-            }
+fn raw_codec(txn: &impl RowSource, name: &str) -> Result<TableCodec, Error> {
+    let names = txn.probe_names(name)?;
+    let codec = |widths| TableCodec::with_raw_fallback(names.clone(), widths);
+    // redb does not expose the persisted fixed widths, but a mismatching
+    // open reports the persisted width of the first side that differs, the
+    // key being checked before the value
+    let persisted_width = |codec: &TableCodec| {
+        let _scope = codec.enter();
+        let opened = txn.visit_raw(name, Bound::Unbounded, Bound::Unbounded, &mut |_, _| {
+            ControlFlow::Break(())
+        });
+        match opened {
+            Ok(()) => Ok(None),
+            Err(redb::Error::TypeDefinitionChanged { width, .. }) => Ok(Some(width)),
+            Err(e) => Err(Error::from(e)),
         }
     };
+    let mut widths = [None, None];
+    let raw = [codec(widths).key.is_raw(), codec(widths).value.is_raw()];
+    if let Some(first) = persisted_width(&codec(widths))? {
+        widths[usize::from(!raw[0])] = first;
+        // with both sides raw, a key mismatch now means the key is variable
+        // width after all and the first mismatch was the value's
+        match persisted_width(&codec(widths))? {
+            Some(None) if raw == [true, true] => widths = [None, first],
+            Some(second) if raw == [true, true] => widths[1] = second,
+            _ => {}
+        }
+    }
+    Ok(codec(widths))
 }
 
-macro_rules! impl_owned_tuple3 {
-    ($k:ty, $o:ty, |$s:ident| $arg:expr) => {
-        impl OwnedCell<($k, $k, $k)> for ($o, $o, $o) {
-            fn parse_cell(text: &str) -> Result<Self, String> {
-                let inner = text
-                    .trim()
-                    .strip_prefix('(')
-                    .and_then(|x| x.strip_suffix(')'))
-                    .ok_or_else(|| {
-                        format!("expected tuple like (a, b, c), got '{}'", text.trim())
-                    })?;
-                let elems = split_top_level(inner)?;
-                if elems.len() != 3 {
-                    return Err(format!(
-                        "expected 3 tuple elements, got {} in '{text}'",
-                        elems.len()
-                    ));
-                }
+/// Makes a raw key codec order keys as they are stored, so redb can find
+/// them without knowing the application's `Key::compare`.
+fn stored_key_order(
+    txn: &impl RowSource,
+    name: &str,
+    codec: TableCodec,
+) -> Result<TableCodec, Error> {
+    if !codec.key.is_raw() {
+        return Ok(codec);
+    }
+    let mut keys = Vec::new();
+    {
+        let _scope = codec.enter();
+        txn.visit_raw(name, Bound::Unbounded, Bound::Unbounded, &mut |key, _| {
+            keys.push(key.to_vec());
+            ControlFlow::Continue(())
+        })?;
+    }
+    Ok(codec.with_key_order(keys))
+}
+
+/// Stored bytes that do not decode as the table's declared type.
+fn undecodable(table: &str, message: String) -> Error {
+    Error::Redb(redb::Error::Corrupted(format!(
+        "table '{table}': {message}"
+    )))
+}
+
+/// Decodes a stored key or value. Bytes that do not match a layout or
+/// value decoder, which need not hold for every row, are kept as
+/// `DynValue::Bytes` so only that cell is shown as hex.
+fn decode_cell(tree: &TypeTree, data: &[u8]) -> Result<DynValue, String> {
+    match tree.decode(data) {
+        Err(_) if matches!(tree, TypeTree::Layout(_)) => Ok(DynValue::Bytes(data.to_vec())),
+        decoded => decoded,
+    }
+}
+
+fn format_cell(
+    tree: &TypeTree,
+    lens: Option<Lens>,
+    table: &str,
+    data: &[u8],
+) -> Result<String, Error> {
+    decode_cell(tree, data)
+        .map(|value| match lens {
+            Some(lens) => lens.render(&value),
+            None => value.to_string(),
+        })
+        .map_err(|e| undecodable(table, e))
+}
+
+/// Parses cell text, in the form of `lens` if set.
+fn parse_cell(tree: &TypeTree, lens: Option<Lens>, text: &str) -> Result<DynValue, ParseError> {
+    match lens {
+        Some(lens) => lens.parse_cell(tree, text),
+        None => tree.parse_cell(text),
+    }
+}
+
+/// Parses cell text with `parse_cell` and encodes it in redb's layout for
+/// `tree`.
+fn encode_cell(
+    tree: &TypeTree,
+    lens: Option<Lens>,
+    text: &str,
+    context: String,
+) -> Result<Vec<u8>, Error> {
+    parse_cell(tree, lens, text)
+        .and_then(|value| tree.encode(&value).map_err(|e| ParseError::new(0, e)))
+        .map_err(|e| e.into_error(text, context))
+}
+
+/// Reads a table with the types described by `desc`, which is obtained by
+/// probing the table definition. Any nesting of redb's built-in types and
+/// types with a loaded layout can be read; other types are formatted as
+/// their raw bytes, see `DynValue::Raw`.
+pub fn read_table_dyn(
+    txn: &impl RowSource,
+    name: &str,
+    desc: &TableTypeDesc,
+) -> Result<Vec<(String, String)>, Error> {
+    let mut rows = Vec::new();
+    visit_table_dyn(txn, name, desc, &mut |key, value| {
+        rows.push((key, value));
+        ControlFlow::Continue(())
+    })?;
+    Ok(rows)
+}
+
+/// Streaming form of `read_table_dyn`: calls `visit` with the formatted key
+/// and value of each row in key order, without collecting the table. Return
+/// `ControlFlow::Break` to stop early.
+pub fn visit_table_dyn(
+    txn: &impl RowSource,
+    name: &str,
+    desc: &TableTypeDesc,
+    visit: &mut dyn FnMut(String, String) -> ControlFlow<()>,
+) -> Result<(), Error> {
+    let codec = read_codec(txn, name, desc)?;
+    let _scope = codec.enter();
+    let mut failure = None;
+    txn.visit_raw(
+        name,
+        Bound::Unbounded,
+        Bound::Unbounded,
+        &mut |key, value| {
+            let row = format_cell(&codec.key, codec.lenses.key, name, key).and_then(|key| {
                 Ok((
-                    <$o as OwnedCell<$k>>::parse_elem(elems[0].trim())?,
-                    <$o as OwnedCell<$k>>::parse_elem(elems[1].trim())?,
-                    <$o as OwnedCell<$k>>::parse_elem(elems[2].trim())?,
+                    key,
+                    format_cell(&codec.value, codec.lenses.value, name, value)?,
                 ))
+            });
+            match row {
+                Ok((key, value)) => visit(key, value),
+                Err(e) => {
+                    failure = Some(e);
+                    ControlFlow::Break(())
+                }
             }
-            fn parse_elem(text: &str) -> Result<Self, String> {
-                <($o, $o, $o) as OwnedCell<($k, $k, $k)>>::parse_cell(text)
-            }
-            fn as_arg(&self) -> <($k, $k, $k) as Value>::SelfType<'_> {
-                let $s = self;
-                $arg
-            }
+        },
+    )?;
+    failure.map_or(Ok(()), Err)
+}
+
+/// Parses edited (key, value) cells into encoded rows; a single invalid
+/// cell fails the whole batch.
+type EncodedRows = Vec<(Vec<u8>, Vec<u8>)>;
+
+fn encode_rows(codec: &TableCodec, rows: &[(String, String)]) -> Result<EncodedRows, Error> {
+    rows.iter()
+        .map(|(key_text, value_text)| encode_row(codec, key_text, value_text))
+        .collect()
+}
+
+fn encode_row(
+    codec: &TableCodec,
+    key_text: &str,
+    value_text: &str,
+) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let key = encode_cell(
+        &codec.key,
+        codec.lenses.key,
+        key_text,
+        format!("invalid key '{key_text}'"),
+    )?;
+    let value = encode_cell(
+        &codec.value,
+        codec.lenses.value,
+        value_text,
+        format!("invalid value for key '{key_text}'"),
+    )?;
+    Ok((key, value))
+}
+
+/// Replaces the entire content of table `name` with `rows`, which are
+/// edited (key, value) cell strings as produced by `read_table_dyn`.
+/// All rows are validated before anything is written; on any error the
+/// transaction is left uncommitted and the table stays unchanged.
+pub fn write_table_dyn(
+    txn: &WriteTransaction,
+    name: &str,
+    desc: &TableTypeDesc,
+    rows: Vec<(String, String)>,
+) -> Result<(), Error> {
+    let codec = table_codec(name, desc, "edited")?;
+    let _scope = codec.enter();
+    let mut table = txn.open_table(typecodec::definition(name))?;
+    // Rows whose text is unchanged keep their stored bytes: formatting and
+    // parsing again need not give the same bytes (ex: a decoded f32 is
+    // written back as f64), and cells shown as hex may not parse at all.
+    let mut stored = HashMap::new();
+    {
+        let mut iter = table.range::<&[u8]>(..)?;
+        while let Some((key, value)) = iter.next().transpose()? {
+            let (key, value) = (key.value().to_vec(), value.value().to_vec());
+            let key_text = format_cell(&codec.key, codec.lenses.key, name, &key)?;
+            let value_text = format_cell(&codec.value, codec.lenses.value, name, &value)?;
+            stored.insert(key_text, (value_text, key, value));
         }
-    };
+    }
+    let embedded = desc.value_codec.is_some();
+    // Parse everything first: a single invalid cell aborts the whole edit
+    // before the table is touched.
+    let parsed = rows
+        .iter()
+        .map(|(key_text, value_text)| match stored.get(key_text) {
+            Some((current, key, value)) if same_cell(embedded, current, value_text) => {
+                Ok((key.clone(), value.clone()))
+            }
+            _ => encode_row(&codec, key_text, value_text),
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    for (_, key, _) in stored.into_values() {
+        table.remove(key.as_slice())?;
+    }
+    for (key, value) in parsed {
+        table.insert(key.as_slice(), value.as_slice())?;
+    }
+    Ok(())
 }
 
-macro_rules! impl_owned_tuples {
-    ($k:ty, $o:ty, |$s2:ident| $a2:expr, |$s3:ident| $a3:expr) => {
-        impl_owned_tuple2!($k, $o, |$s2| $a2);
-        impl_owned_tuple3!($k, $o, |$s3| $a3);
+/// Whether edited cell text leaves a value as it is. Documents of a table
+/// with a value decoder are edited as embedded JSON, so they are compared
+/// as JSON, a cell that is not JSON (hex) being a JSON string.
+fn same_cell(embedded: bool, current: &str, edited: &str) -> bool {
+    let json = |text: &str| {
+        serde_json::from_str(text).unwrap_or_else(|_| serde_json::Value::String(text.to_string()))
     };
+    current == edited || embedded && json(current) == json(edited)
 }
 
-impl_owned_tuples!(u8, u8, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(u16, u16, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(u32, u32, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(u64, u64, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(u128, u128, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(i8, i8, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(i16, i16, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(i32, i32, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(i64, i64, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(i128, i128, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(bool, bool, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(char, char, |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!((), (), |s| (s.0, s.1), |s| (s.0, s.1, s.2));
-impl_owned_tuples!(
-    &'static str,
-    String,
-    |s| (s.0.as_str(), s.1.as_str()),
-    |s| (s.0.as_str(), s.1.as_str(), s.2.as_str())
-);
-impl_owned_tuples!(String, String, |s| (s.0.clone(), s.1.clone()), |s| (
-    s.0.clone(),
-    s.1.clone(),
-    s.2.clone()
-));
-impl_owned_tuples!(
-    &'static [u8],
-    Vec<u8>,
-    |s| (s.0.as_slice(), s.1.as_slice()),
-    |s| (s.0.as_slice(), s.1.as_slice(), s.2.as_slice())
-);
+/// Calls `visit` with the serialized key and value bytes of every row of
+/// table `name`, in key order. The bytes are exactly what redb stores, so
+/// they are independent of how cells are formatted for display.
+pub fn visit_raw_rows_dyn(
+    txn: &ReadTransaction,
+    name: &str,
+    desc: &TableTypeDesc,
+    visit: &mut dyn FnMut(&[u8], &[u8]),
+) -> Result<(), Error> {
+    let codec = read_codec(txn, name, desc)?;
+    let _scope = codec.enter();
+    txn.visit_raw(
+        name,
+        Bound::Unbounded,
+        Bound::Unbounded,
+        &mut |key, value| {
+            visit(key, value);
+            ControlFlow::Continue(())
+        },
+    )?;
+    Ok(())
+}
+
+/// Previous value of each key touched by `apply_rows_dyn`, in the order the
+/// keys were written; `None` means the key did not exist.
+pub type BeforeImage = Vec<(String, Option<String>)>;
+
+/// Inserts (or overwrites) the `puts` rows and removes the `dels` keys of
+/// table `name`, all given as cell strings. Everything is parsed before
+/// the table is modified. Returns the previous value of every touched key,
+/// puts first, as cell text; see `row_image` to revert the change. Rows of tables whose types
+/// cannot be decoded can be removed by their raw `0x` key, not written.
+pub fn apply_rows_dyn(
+    txn: &WriteTransaction,
+    name: &str,
+    desc: &TableTypeDesc,
+    puts: Vec<(String, String)>,
+    dels: Vec<String>,
+) -> Result<BeforeImage, Error> {
+    let codec = match TableCodec::new(desc) {
+        Some(codec) => codec,
+        None if puts.is_empty() => stored_key_order(txn, name, raw_codec(txn, name)?)?,
+        None => return Err(unsupported(name, desc, "edited")),
+    };
+    // Same rule as `write_table_dyn`: parse everything before touching the table.
+    let parsed_puts = encode_rows(&codec, &puts)?;
+    let parsed_dels = dels
+        .iter()
+        .map(|key_text| {
+            encode_cell(
+                &codec.key,
+                codec.lenses.key,
+                key_text,
+                format!("invalid key '{key_text}'"),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let _scope = codec.enter();
+    let mut table = txn.open_table(typecodec::definition(name))?;
+    let mut before = Vec::with_capacity(puts.len() + dels.len());
+    for ((key_text, _), (key, value)) in puts.into_iter().zip(parsed_puts) {
+        let old = table.insert(key.as_slice(), value.as_slice())?;
+        let old = old
+            .map(|old| format_cell(&codec.value, codec.lenses.value, name, old.value()))
+            .transpose()?;
+        before.push((key_text, old));
+    }
+    for (key_text, key) in dels.into_iter().zip(parsed_dels) {
+        let old = table.remove(key.as_slice())?;
+        let old = old
+            .map(|old| format_cell(&codec.value, codec.lenses.value, name, old.value()))
+            .transpose()?;
+        before.push((key_text, old));
+    }
+    Ok(before)
+}
 
 // ---------------------------------------------------------------------------
-// Typed read/write dispatch over the registered type combinations
+// Stored images
 // ---------------------------------------------------------------------------
 
-macro_rules! registry_keys {
-    ($values:ident) => {
-        $values!(u8, u8);
-        $values!(u16, u16);
-        $values!(u32, u32);
-        $values!(u64, u64);
-        $values!(u128, u128);
-        $values!(i8, i8);
-        $values!(i16, i16);
-        $values!(i32, i32);
-        $values!(i64, i64);
-        $values!(i128, i128);
-        $values!(bool, bool);
-        $values!(char, char);
-        $values!((), ());
-        $values!(&str, String);
-        $values!(String, String);
-        $values!(&[u8], Vec<u8>);
-        $values!((u8, u8), (u8, u8));
-        $values!((u16, u16), (u16, u16));
-        $values!((u32, u32), (u32, u32));
-        $values!((u64, u64), (u64, u64));
-        $values!((u128, u128), (u128, u128));
-        $values!((i8, i8), (i8, i8));
-        $values!((i16, i16), (i16, i16));
-        $values!((i32, i32), (i32, i32));
-        $values!((i64, i64), (i64, i64));
-        $values!((i128, i128), (i128, i128));
-        $values!((bool, bool), (bool, bool));
-        $values!((char, char), (char, char));
-        $values!(((), ()), ((), ()));
-        $values!((&str, &str), (String, String));
-        $values!((String, String), (String, String));
-        $values!((&[u8], &[u8]), (Vec<u8>, Vec<u8>));
-        $values!((u8, u8, u8), (u8, u8, u8));
-        $values!((u16, u16, u16), (u16, u16, u16));
-        $values!((u32, u32, u32), (u32, u32, u32));
-        $values!((u64, u64, u64), (u64, u64, u64));
-        $values!((u128, u128, u128), (u128, u128, u128));
-        $values!((i8, i8, i8), (i8, i8, i8));
-        $values!((i16, i16, i16), (i16, i16, i16));
-        $values!((i32, i32, i32), (i32, i32, i32));
-        $values!((i64, i64, i64), (i64, i64, i64));
-        $values!((i128, i128, i128), (i128, i128, i128));
-        $values!((bool, bool, bool), (bool, bool, bool));
-        $values!((char, char, char), (char, char, char));
-        $values!(((), (), ()), ((), (), ()));
-        $values!((&str, &str, &str), (String, String, String));
-        $values!((String, String, String), (String, String, String));
-        $values!((&[u8], &[u8], &[u8]), (Vec<u8>, Vec<u8>, Vec<u8>));
-    };
+/// Rows of a table as stored bytes, with the persisted types they were
+/// stored under, so they can be written back byte for byte whatever lenses
+/// and decoders were set since. A `None` value marks an absent key.
+#[derive(Debug, Clone)]
+pub struct StoredImage {
+    pub desc: TableTypeDesc,
+    codec: TableCodec,
+    pub rows: Vec<(Vec<u8>, Option<Vec<u8>>)>,
 }
 
-macro_rules! registry_values {
-    ($pair:ident, $kt:ty, $ko:ty) => {
-        $pair!($kt, u8, $ko, u8);
-        $pair!($kt, u16, $ko, u16);
-        $pair!($kt, u32, $ko, u32);
-        $pair!($kt, u64, $ko, u64);
-        $pair!($kt, u128, $ko, u128);
-        $pair!($kt, i8, $ko, i8);
-        $pair!($kt, i16, $ko, i16);
-        $pair!($kt, i32, $ko, i32);
-        $pair!($kt, i64, $ko, i64);
-        $pair!($kt, i128, $ko, i128);
-        $pair!($kt, f32, $ko, f32);
-        $pair!($kt, f64, $ko, f64);
-        $pair!($kt, bool, $ko, bool);
-        $pair!($kt, char, $ko, char);
-        $pair!($kt, (), $ko, ());
-        $pair!($kt, &str, $ko, String);
-        $pair!($kt, String, $ko, String);
-        $pair!($kt, &[u8], $ko, Vec<u8>);
-    };
+impl PartialEq for StoredImage {
+    fn eq(&self, other: &Self) -> bool {
+        self.desc.key == other.desc.key
+            && self.desc.value == other.desc.value
+            && self.rows == other.rows
+    }
 }
 
-fn read_rows<K, V>(txn: &ReadTransaction, name: &str) -> Result<Vec<(String, String)>, redb::Error>
-where
-    K: Key + 'static + Borrow<<K as Value>::SelfType<'static>>,
-    V: Value + 'static,
-    for<'a> K::SelfType<'a>: FmtCell,
-    for<'a> V::SelfType<'a>: FmtCell,
-{
-    let definition: TableDefinition<'_, K, V> = TableDefinition::new(name);
-    let table = txn.open_table(definition)?;
-    let mut iter = table.range::<K>(..)?;
+/// Captures every row of table `name`, whose types are `desc`.
+pub fn table_image(
+    txn: &impl RowSource,
+    name: &str,
+    desc: &TableTypeDesc,
+) -> Result<StoredImage, Error> {
+    let codec = raw_codec(txn, name)?;
     let mut rows = Vec::new();
-    while let Some((key, value)) = iter.next().transpose()? {
-        rows.push((key.value().fmt_cell(), value.value().fmt_cell()));
+    {
+        let _scope = codec.enter();
+        txn.visit_raw(
+            name,
+            Bound::Unbounded,
+            Bound::Unbounded,
+            &mut |key, value| {
+                rows.push((key.to_vec(), Some(value.to_vec())));
+                ControlFlow::Continue(())
+            },
+        )?;
     }
-    Ok(rows)
+    Ok(StoredImage {
+        desc: desc.clone(),
+        codec,
+        rows,
+    })
 }
 
-/// Reads a table with the types described by `desc`, which is obtained by
-/// probing the table definition. Only type combinations covered by the
-/// registry below can be read; extend `registry_keys!` / `registry_values!`
-/// to support more.
-pub fn read_table_dyn(
-    txn: &ReadTransaction,
+/// Captures the rows of table `name` under `keys`, given as cell text.
+/// Refused for raw keys: their order is only known to the application, so
+/// a removed one could not be put back.
+pub fn row_image(
+    txn: &impl RowSource,
     name: &str,
     desc: &TableTypeDesc,
-) -> Result<Vec<(String, String)>, String> {
-    let key_name = desc.key.as_str();
-    let value_name = desc.value.as_str();
-
-    macro_rules! read_pair {
-        ($kt:ty, $vt:ty, $ko:ty, $vo:ty) => {
-            if key_name == type_name_of::<$kt>() && value_name == type_name_of::<$vt>() {
-                return read_rows::<$kt, $vt>(txn, name).map_err(|e| e.to_string());
-            }
-        };
+    keys: &[String],
+) -> Result<StoredImage, Error> {
+    let codec = raw_codec(txn, name)?;
+    if codec.key.is_raw() {
+        return Err(Error::refused(format!(
+            "keys of table '{name}' are raw bytes, a removed row could not be put back"
+        )));
     }
-    macro_rules! read_values {
-        ($kt:ty, $ko:ty) => {
-            registry_values!(read_pair, $kt, $ko);
-        };
+    let read = read_codec(txn, name, desc)?;
+    let mut rows = Vec::with_capacity(keys.len());
+    for key_text in keys {
+        let key = encode_cell(
+            &read.key,
+            read.lenses.key,
+            key_text,
+            format!("invalid key '{key_text}'"),
+        )?;
+        let mut value = None;
+        let _scope = codec.enter();
+        txn.visit_raw(
+            name,
+            Bound::Included(&key),
+            Bound::Included(&key),
+            &mut |_, stored| {
+                value = Some(stored.to_vec());
+                ControlFlow::Break(())
+            },
+        )?;
+        rows.push((key, value));
     }
-    registry_keys!(read_values);
-
-    Err(format!(
-        "table '{name}' has unsupported key/value types Table<{key_name}, {value_name}>"
-    ))
+    Ok(StoredImage {
+        desc: desc.clone(),
+        codec,
+        rows,
+    })
 }
 
-fn write_rows<K, V, KO, VO>(
+/// Writes `image` back into table `name`. With `whole`, the table is
+/// recreated under the image's types holding exactly its rows; otherwise
+/// only the image's keys are set back, or removed where they were absent.
+pub fn restore_image(
     txn: &WriteTransaction,
     name: &str,
-    rows: Vec<(String, String)>,
-) -> Result<(), String>
-where
-    K: Key + 'static + Borrow<<K as Value>::SelfType<'static>>,
-    V: Value + 'static,
-    KO: OwnedCell<K>,
-    VO: OwnedCell<V>,
-    for<'a> K::SelfType<'a>: FmtCell,
-{
-    // Parse everything first: a single invalid cell aborts the whole edit
-    // before the table is touched.
-    let mut parsed = Vec::with_capacity(rows.len());
-    for (key_text, value_text) in &rows {
-        let key = KO::parse_cell(key_text).map_err(|e| format!("invalid key '{key_text}': {e}"))?;
-        let value = VO::parse_cell(value_text)
-            .map_err(|e| format!("invalid value for key '{key_text}': {e}"))?;
-        parsed.push((key, value));
+    image: &StoredImage,
+    whole: bool,
+) -> Result<(), Error> {
+    let codec = if whole {
+        let handle: TableDefinition<&str, &str> = TableDefinition::new(name);
+        txn.delete_table(handle)?;
+        let keys = image.rows.iter().map(|(key, _)| key.clone());
+        image.codec.clone().with_key_order(keys)
+    } else {
+        match txn.probe_type(name) {
+            Ok(current) if current.key == image.desc.key && current.value == image.desc.value => {}
+            _ => {
+                return Err(Error::refused(format!(
+                    "table '{name}' no longer has types Table<{}, {}>, its rows cannot be put back",
+                    image.desc.key, image.desc.value
+                )))
+            }
+        }
+        image.codec.clone()
+    };
+    let _scope = codec.enter();
+    let mut table = txn.open_table(typecodec::definition(name))?;
+    for (key, value) in &image.rows {
+        match value {
+            Some(value) => table.insert(key.as_slice(), value.as_slice())?,
+            None => table.remove(key.as_slice())?,
+        };
     }
+    Ok(())
+}
 
-    let definition: TableDefinition<'_, K, V> = TableDefinition::new(name);
-    let mut table = txn
-        .open_table(definition)
-        .map_err(|e| format!("open table '{name}' failed: {e}"))?;
+// ---------------------------------------------------------------------------
+// Column views
+// ---------------------------------------------------------------------------
 
-    // Remove all existing entries (format key -> parse back is an exact
-    // round-trip for every registered type).
-    {
-        let existing: Vec<String> = {
-            let mut iter = table
-                .range::<K>(..)
-                .map_err(|e| format!("read table '{name}' failed: {e}"))?;
-            let mut keys = Vec::new();
-            while let Some((key, _)) = iter.next().transpose().map_err(|e| e.to_string())? {
-                keys.push(key.value().fmt_cell());
+/// How `visit_table_columns` lays out the rows of a table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnQuery {
+    /// Show each element of a tuple key or value as its own column, named
+    /// `key.0`, `key.1`, .. and `value.0`, ..
+    pub expand: bool,
+    /// Only rows whose cell in a column equals a text, ex: `("key.1", "7")`.
+    pub filter: Option<(String, String)>,
+    /// Order rows by a column instead of by key; rows with equal cells
+    /// keep their key order.
+    pub sort: Option<String>,
+    pub descending: bool,
+}
+
+/// A whole key or value, or one element of a tuple key or value.
+struct Column {
+    name: String,
+    of_value: bool,
+    element: Option<usize>,
+    tree: TypeTree,
+    lens: Option<Lens>,
+}
+
+impl Column {
+    fn cell<'v>(&self, key: &'v DynValue, value: &'v DynValue) -> &'v DynValue {
+        let whole = if self.of_value { value } else { key };
+        match (self.element, whole) {
+            (Some(i), DynValue::Tuple(items)) => &items[i],
+            _ => whole,
+        }
+    }
+    fn format(&self, cell: &DynValue) -> String {
+        match self.lens {
+            Some(lens) => lens.render(cell),
+            None => cell.to_string(),
+        }
+    }
+}
+
+/// The columns of a table read with `codec`. Lenses only apply to whole
+/// keys and values, so expanded elements are shown in their plain form.
+fn columns(codec: &TableCodec, expand: bool) -> Vec<Column> {
+    let sides = [
+        ("key", false, &codec.key, codec.lenses.key),
+        ("value", true, &codec.value, codec.lenses.value),
+    ];
+    let mut columns = Vec::new();
+    for (side, of_value, tree, lens) in sides {
+        match tree {
+            TypeTree::Tuple(elems) if expand => {
+                columns.extend(elems.iter().enumerate().map(|(i, elem)| Column {
+                    name: format!("{side}.{i}"),
+                    of_value,
+                    element: Some(i),
+                    tree: elem.clone(),
+                    lens: None,
+                }))
             }
-            keys
-        };
-        for key_text in existing {
-            let key = KO::parse_cell(&key_text)
-                .map_err(|e| format!("failed to re-parse existing key '{key_text}': {e}"))?;
-            table
-                .remove(key.as_arg())
-                .map_err(|e| format!("remove key '{key_text}' failed: {e}"))?;
+            tree => columns.push(Column {
+                name: side.to_string(),
+                of_value,
+                element: None,
+                tree: tree.clone(),
+                lens,
+            }),
         }
     }
+    columns
+}
 
-    for (key, value) in parsed {
-        table
-            .insert(key.as_arg(), value.as_arg())
-            .map_err(|e| format!("insert failed: {e}"))?;
+/// Finds column `name`, whole or element, whether or not rows are expanded.
+fn find_column(codec: &TableCodec, table: &str, name: &str) -> Result<Column, Error> {
+    let mut known = columns(codec, false);
+    known.extend(
+        columns(codec, true)
+            .into_iter()
+            .filter(|c| c.element.is_some()),
+    );
+    let names: Vec<String> = known.iter().map(|c| c.name.clone()).collect();
+    known.into_iter().find(|c| c.name == name).ok_or_else(|| {
+        Error::refused(format!(
+            "table '{table}' has no column '{name}', expected {}",
+            names.join(", ")
+        ))
+    })
+}
+
+/// Column headers of a table whose probed types are `desc`, as passed to
+/// the visitor of `visit_table_columns`.
+pub fn column_names(desc: &TableTypeDesc, expand: bool) -> Vec<String> {
+    match TableCodec::new(desc) {
+        Some(codec) => columns(&codec, expand)
+            .into_iter()
+            .map(|c| c.name)
+            .collect(),
+        None => vec!["key".to_string(), "value".to_string()],
     }
-    Ok(())
 }
 
-/// Replaces the entire content of table `name` with `rows`, which are
-/// edited (key, value) cell strings as produced by `read_table_dyn`.
-/// All rows are validated before anything is written; on any error the
-/// transaction is left uncommitted and the table stays unchanged.
-pub fn write_table_dyn(
-    txn: &WriteTransaction,
+/// Like `visit_table_dyn`, but calls `visit` with the cells of each row
+/// laid out by `query`, see `column_names`. Rows are streamed in key order
+/// unless `query` sorts them, which collects the matching rows first.
+pub fn visit_table_columns(
+    txn: &impl RowSource,
     name: &str,
     desc: &TableTypeDesc,
-    rows: Vec<(String, String)>,
-) -> Result<(), String> {
-    let key_name = desc.key.as_str();
-    let value_name = desc.value.as_str();
-
-    macro_rules! write_pair {
-        ($kt:ty, $vt:ty, $ko:ty, $vo:ty) => {
-            if key_name == type_name_of::<$kt>() && value_name == type_name_of::<$vt>() {
-                return write_rows::<$kt, $vt, $ko, $vo>(txn, name, rows);
+    query: &ColumnQuery,
+    visit: &mut dyn FnMut(Vec<String>) -> ControlFlow<()>,
+) -> Result<(), Error> {
+    let codec = read_codec(txn, name, desc)?;
+    let columns = columns(&codec, query.expand);
+    let filter = match &query.filter {
+        Some((column, text)) => {
+            let column = find_column(&codec, name, column)?;
+            let wanted = parse_cell(&column.tree, column.lens, text)
+                .map_err(|e| e.into_error(text, format!("invalid {} '{text}'", column.name)))?;
+            Some((column, wanted))
+        }
+        None => None,
+    };
+    let sort = query
+        .sort
+        .as_ref()
+        .map(|column| find_column(&codec, name, column))
+        .transpose()?;
+
+    let _scope = codec.enter();
+    let mut sorted = Vec::new();
+    let mut failure = None;
+    txn.visit_raw(
+        name,
+        Bound::Unbounded,
+        Bound::Unbounded,
+        &mut |key, value| {
+            let row = decode_cell(&codec.key, key)
+                .and_then(|key| Ok((key, decode_cell(&codec.value, value)?)));
+            let (key, value) = match row {
+                Ok(row) => row,
+                Err(e) => {
+                    failure = Some(undecodable(name, e));
+                    return ControlFlow::Break(());
+                }
+            };
+            if let Some((column, wanted)) = &filter {
+                if column.cell(&key, &value) != wanted {
+                    return ControlFlow::Continue(());
+                }
             }
+            let cells = columns
+                .iter()
+                .map(|column| column.format(column.cell(&key, &value)))
+                .collect();
+            match &sort {
+                // sorted by the cell's stored form, the order redb uses
+                Some(column) => {
+                    let cell = column.tree.encode(column.cell(&key, &value));
+                    sorted.push((cell.unwrap_or_default(), cells));
+                    ControlFlow::Continue(())
+                }
+                None => visit(cells),
+            }
+        },
+    )?;
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    if let Some(column) = &sort {
+        sorted.sort_by(|(a, _), (b, _)| {
+            let order = column.tree.compare(a, b);
+            if query.descending {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        for (_, cells) in sorted {
+            if visit(cells).is_break() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Typed access through `DynValue`
+// ---------------------------------------------------------------------------
+
+impl DynValue {
+    /// Parses cell text as a value of the redb type `type_name`, ex: "u64"
+    /// or "(&str,Option<u32>)"; the inverse of `Display`.
+    pub fn parse(text: &str, type_name: &str) -> Result<DynValue, Error> {
+        let tree = TypeTree::parse(type_name).map_err(|_| Error::Parse {
+            input: text.to_string(),
+            position: 0,
+            message: format!("type {type_name} is not supported"),
+        })?;
+        tree.parse_cell(text)
+            .map_err(|e| e.into_error(text, format!("invalid {type_name}")))
+    }
+}
+
+fn encode_dyn(tree: &TypeTree, value: &DynValue) -> Result<Vec<u8>, Error> {
+    tree.encode(value).map_err(|message| Error::Parse {
+        input: value.to_string(),
+        position: 0,
+        message,
+    })
+}
+
+/// A table addressed by name, read and written through `DynValue`s. The
+/// key/value types are probed when it is opened; keys and values of other
+/// types are rejected with `Error::Parse`.
+pub struct DynTable<'t, T> {
+    txn: &'t T,
+    name: String,
+    desc: TableTypeDesc,
+    codec: TableCodec,
+}
+
+impl<'t, T: RowSource> DynTable<'t, T> {
+    pub fn open(txn: &'t T, name: &str) -> Result<Self, Error> {
+        let desc = probe_table_type(txn, name)?;
+        let codec = table_codec(name, &desc, "read")?;
+        Ok(DynTable {
+            txn,
+            name: name.to_string(),
+            desc,
+            codec,
+        })
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn types(&self) -> &TableTypeDesc {
+        &self.desc
+    }
+    /// Parses cell text as a key of this table.
+    pub fn parse_key(&self, text: &str) -> Result<DynValue, Error> {
+        DynValue::parse(text, &self.desc.key)
+    }
+    /// Parses cell text as a value of this table.
+    pub fn parse_value(&self, text: &str) -> Result<DynValue, Error> {
+        DynValue::parse(text, &self.desc.value)
+    }
+    pub fn get(&self, key: &DynValue) -> Result<Option<DynValue>, Error> {
+        let rows = self.range((Bound::Included(key), Bound::Included(key)))?;
+        Ok(rows.into_iter().next().map(|(_, value)| value))
+    }
+    /// Rows whose keys fall into `range`, in key order.
+    pub fn range<'r>(
+        &self,
+        range: impl RangeBounds<&'r DynValue>,
+    ) -> Result<Vec<(DynValue, DynValue)>, Error> {
+        let codec = &self.codec;
+        let encode = |bound: Bound<&&DynValue>| -> Result<Bound<Vec<u8>>, Error> {
+            Ok(match bound {
+                Bound::Included(v) => Bound::Included(encode_dyn(&codec.key, v)?),
+                Bound::Excluded(v) => Bound::Excluded(encode_dyn(&codec.key, v)?),
+                Bound::Unbounded => Bound::Unbounded,
+            })
         };
+        let start = encode(range.start_bound())?;
+        let end = encode(range.end_bound())?;
+
+        let _scope = codec.enter();
+        let mut rows = Vec::new();
+        let mut failure = None;
+        let decode = |data: &[u8], tree: &TypeTree| {
+            tree.decode(data).map_err(|e| undecodable(&self.name, e))
+        };
+        self.txn.visit_raw(
+            &self.name,
+            start.as_ref().map(Vec::as_slice),
+            end.as_ref().map(Vec::as_slice),
+            &mut |key, value| {
+                match decode(key, &codec.key).and_then(|k| Ok((k, decode(value, &codec.value)?))) {
+                    Ok(row) => rows.push(row),
+                    Err(e) => {
+                        failure = Some(e);
+                        return ControlFlow::Break(());
+                    }
+                }
+                ControlFlow::Continue(())
+            },
+        )?;
+        failure.map_or(Ok(rows), Err)
+    }
+}
+
+impl DynTable<'_, WriteTransaction> {
+    /// Inserts or overwrites a row; returns the previous value.
+    pub fn insert(&self, key: &DynValue, value: &DynValue) -> Result<Option<DynValue>, Error> {
+        self.write(key, Some(value))
+    }
+    /// Removes a row; returns its value, `None` if the key was absent.
+    pub fn remove(&self, key: &DynValue) -> Result<Option<DynValue>, Error> {
+        self.write(key, None)
     }
-    macro_rules! write_values {
-        ($kt:ty, $ko:ty) => {
-            registry_values!(write_pair, $kt, $ko);
+    fn write(&self, key: &DynValue, value: Option<&DynValue>) -> Result<Option<DynValue>, Error> {
+        let codec = &self.codec;
+        let key = encode_dyn(&codec.key, key)?;
+        let value = value.map(|v| encode_dyn(&codec.value, v)).transpose()?;
+
+        let _scope = codec.enter();
+        let mut table = self.txn.open_table(typecodec::definition(&self.name))?;
+        let old = match value {
+            Some(value) => table.insert(key.as_slice(), value.as_slice())?,
+            None => table.remove(key.as_slice())?,
         };
+        old.map(|old| {
+            codec
+                .value
+                .decode(old.value())
+                .map_err(|e| undecodable(&self.name, e))
+        })
+        .transpose()
+    }
+}
+
+/// How `copy_table_dyn` treats a destination table that already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CopyMode {
+    /// Drop the destination table first, so it ends up identical to the source.
+    #[default]
+    Replace,
+    /// Insert source rows over the existing ones; rows only present in the
+    /// destination are kept.
+    Merge,
+}
+
+/// Copies table `src_name` of `src` into table `dst_name` of `dst`, keeping
+/// the key/value types described by `desc`. Rows are streamed across as
+/// stored bytes, so no formatting round-trip is involved. Returns the
+/// number of copied rows; nothing is visible until `dst` is committed.
+pub fn copy_table_dyn(
+    src: &ReadTransaction,
+    dst: &WriteTransaction,
+    src_name: &str,
+    dst_name: &str,
+    desc: &TableTypeDesc,
+    mode: CopyMode,
+) -> Result<u64, Error> {
+    let codec = table_codec(src_name, desc, "copied")?;
+    let _scope = codec.enter();
+    let source = src.open_table(typecodec::definition(src_name))?;
+    if mode == CopyMode::Replace {
+        dst.delete_table(typecodec::definition(dst_name))?;
     }
-    registry_keys!(write_values);
+    let mut target = dst.open_table(typecodec::definition(dst_name))?;
+    let mut iter = source.range::<&[u8]>(..)?;
+    let mut copied = 0u64;
+    while let Some((key, value)) = iter.next().transpose()? {
+        target.insert(key.value(), value.value())?;
+        copied += 1;
+    }
+    Ok(copied)
+}
 
-    Err(format!(
-        "table '{name}' has key/value types Table<{key_name}, {value_name}> that cannot be edited (unsupported)"
-    ))
+/// Copies table `name` of `src`, whose types cannot be decoded, into a new
+/// table of the same name in `dst`. Keys and values are copied byte for
+/// byte under the persisted type names, in stored key order. Returns the
+/// number of copied rows.
+pub fn copy_stored_table(
+    src: &ReadTransaction,
+    dst: &WriteTransaction,
+    name: &str,
+) -> Result<u64, Error> {
+    let codec = stored_key_order(src, name, raw_codec(src, name)?)?;
+    let _scope = codec.enter();
+    let source = src.open_table(typecodec::definition(name))?;
+    let mut target = dst.open_table(typecodec::definition(name))?;
+    let mut iter = source.range::<&[u8]>(..)?;
+    let mut copied = 0u64;
+    while let Some((key, value)) = iter.next().transpose()? {
+        target.insert(key.value(), value.value())?;
+        copied += 1;
+    }
+    Ok(copied)
+}
+
+/// Outcome of salvaging one table with `salvage_table_dyn`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SalvageOutcome {
+    pub copied: u64,
+    /// Describes the key range that could not be read, if any.
+    pub skipped: Option<String>,
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic while reading".to_string())
+}
+
+/// The codec salvage copies table `name` with: `TableCodec::new` when its
+/// types can be decoded, otherwise its stored bytes, as `copy_stored_table`
+/// copies them. A whole key scan stops at a damaged range, so a raw key is
+/// ordered by the keys readable from either end of the table.
+fn salvage_codec(
+    src: &ReadTransaction,
+    name: &str,
+    desc: &TableTypeDesc,
+) -> Result<TableCodec, Error> {
+    if let Some(codec) = TableCodec::new(desc) {
+        return Ok(codec);
+    }
+    let codec = raw_codec(src, name)?;
+    if !codec.key.is_raw() {
+        return Ok(codec);
+    }
+    let (mut head, mut tail) = (Vec::new(), Vec::new());
+    {
+        let _scope = codec.enter();
+        let source = src.open_table(typecodec::definition(name))?;
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| -> Result<(), redb::StorageError> {
+            for row in source.range::<&[u8]>(..)? {
+                head.push(row?.0.value().to_vec());
+            }
+            Ok(())
+        }));
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| -> Result<(), redb::StorageError> {
+            for row in source.range::<&[u8]>(..)?.rev() {
+                let key = row?.0.value().to_vec();
+                if head.last() == Some(&key) {
+                    break;
+                }
+                tail.push(key);
+            }
+            Ok(())
+        }));
+    }
+    Ok(codec.with_key_order(head.into_iter().chain(tail.into_iter().rev())))
+}
+
+/// Copies every readable row of table `name` from `src` into a table of the
+/// same name and types in `dst`; tables whose types cannot be decoded are
+/// copied as stored bytes. A damaged key range is skipped: rows are read
+/// forward up to the first failure, then backward from the end, and the
+/// gap between the two is reported in the outcome. An error means the
+/// table could not be read at all.
+pub fn salvage_table_dyn(
+    src: &ReadTransaction,
+    dst: &WriteTransaction,
+    name: &str,
+    desc: &TableTypeDesc,
+) -> Result<SalvageOutcome, Error> {
+    let codec = salvage_codec(src, name, desc)?;
+    let _scope = codec.enter();
+    let definition = typecodec::definition(name);
+    let source = src.open_table(definition)?;
+    let mut target = dst.open_table(definition)?;
+    let mut outcome = SalvageOutcome::default();
+    // keys are only shown in the report, so undecodable ones fall back to hex
+    let key_text = |key: &[u8]| {
+        codec
+            .key
+            .decode(key)
+            .map_or_else(|_| bytes_to_hex(key), |key| key.to_string())
+    };
+
+    // Forward pass: copy rows in key order until the first unreadable one.
+    // Damaged pages may make redb panic rather than return an error, so both
+    // count as a read failure.
+    let mut last_good: Option<Vec<u8>> = None;
+    let forward = panic::catch_unwind(AssertUnwindSafe(|| -> Result<(), String> {
+        let mut iter = source.range::<&[u8]>(..).map_err(|e| e.to_string())?;
+        while let Some(row) = iter.next().transpose().map_err(|e| e.to_string())? {
+            let (key, value) = row;
+            target
+                .insert(key.value(), value.value())
+                .map_err(|e| format!("insert failed: {e}"))?;
+            last_good = Some(key.value().to_vec());
+            outcome.copied += 1;
+        }
+        Ok(())
+    }));
+    let failure = match forward {
+        Ok(Ok(())) => return Ok(outcome),
+        Ok(Err(e)) => e,
+        Err(payload) => panic_message(payload),
+    };
+
+    // Backward pass: recover the tail behind the damaged range, stopping at
+    // the last key the forward pass already copied.
+    let mut first_tail: Option<Vec<u8>> = None;
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| -> Result<(), String> {
+        let iter = source.range::<&[u8]>(..).map_err(|e| e.to_string())?;
+        for row in iter.rev() {
+            let (key, value) = row.map_err(|e| e.to_string())?;
+            if let Some(last) = &last_good {
+                if <RuntimeKey as redb::Key>::compare(key.value(), last) != Ordering::Greater {
+                    break;
+                }
+            }
+            target
+                .insert(key.value(), value.value())
+                .map_err(|e| format!("insert failed: {e}"))?;
+            first_tail = Some(key.value().to_vec());
+            outcome.copied += 1;
+        }
+        Ok(())
+    }));
+
+    outcome.skipped = Some(format!(
+        "rows after {} and before {} are unreadable: {failure}",
+        last_good.map_or("the first key".to_string(), |key| format!(
+            "'{}'",
+            key_text(&key)
+        )),
+        first_tail.map_or("the end".to_string(), |key| format!("'{}'", key_text(&key))),
+    ));
+    Ok(outcome)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn cell(type_name: &str, text: &str) -> Result<DynValue, ParseError> {
+        TypeTree::parse(type_name).unwrap().parse_cell(text)
+    }
+
+    fn elem(type_name: &str, text: &str) -> Result<DynValue, ParseError> {
+        TypeTree::parse(type_name).unwrap().parse_elem(text)
+    }
+
+    fn tuple(items: Vec<DynValue>) -> DynValue {
+        DynValue::Tuple(items)
+    }
+
     #[test]
     fn hex_encoding() {
         assert_eq!(bytes_to_hex(&[]), "0x");
         assert_eq!(bytes_to_hex(&[0x01, 0xab, 0xff]), "0x01abff");
     }
 
-    #[test]
-    fn fmt_cell_variants() {
-        assert_eq!(42u64.fmt_cell(), "42");
-        assert_eq!((-7i32).fmt_cell(), "-7");
-        assert_eq!(().fmt_cell(), "()");
-        assert_eq!("hi".fmt_cell(), "hi");
-        assert_eq!(String::from("hi").fmt_cell(), "hi");
-        assert_eq!((1u128, 2u128).fmt_cell(), "(1, 2)");
-        assert_eq!((&[0u8, 255][..]).fmt_cell(), "0x00ff");
-    }
-
     #[test]
     fn type_names_match_redb_builtin() {
         assert_eq!(type_name_of::<&str>(), "&str");
@@ -714,146 +1444,160 @@ mod tests {
 
     #[test]
     fn parse_scalars() {
-        use OwnedCell as OC;
-        assert_eq!(<u64 as OC<u64>>::parse_cell("42").unwrap(), 42);
-        assert_eq!(<i32 as OC<i32>>::parse_cell(" -7 ").unwrap(), -7);
+        assert_eq!(cell("u64", "42").unwrap(), DynValue::U64(42));
+        assert_eq!(cell("i32", " -7 ").unwrap(), DynValue::I32(-7));
         assert_eq!(
-            <u128 as OC<u128>>::parse_cell("340282366920938463463374607431768211455").unwrap(),
-            u128::MAX
+            cell("u128", "340282366920938463463374607431768211455").unwrap(),
+            DynValue::U128(u128::MAX)
         );
-        assert!(<u64 as OC<u64>>::parse_cell("abc").is_err());
-        assert!(<bool as OC<bool>>::parse_cell("yes").is_err());
-        assert!(<bool as OC<bool>>::parse_cell("true").unwrap());
-        assert_eq!(<f64 as OC<f64>>::parse_cell("3.5").unwrap(), 3.5);
-        assert_eq!(<() as OC<()>>::parse_cell("()").unwrap(), ());
-        assert!(<() as OC<()>>::parse_cell("( )").is_err());
+        assert!(cell("u64", "abc").is_err());
+        assert!(cell("bool", "yes").is_err());
+        assert_eq!(cell("bool", "true").unwrap(), DynValue::Bool(true));
+        assert_eq!(cell("f64", "3.5").unwrap(), DynValue::F64(3.5));
+        assert_eq!(cell("()", "()").unwrap(), DynValue::Unit);
+        assert!(cell("()", "( )").is_err());
     }
 
     #[test]
     fn parse_chars() {
-        use OwnedCell as OC;
-        assert_eq!(<char as OC<char>>::parse_cell("'a'").unwrap(), 'a');
-        assert_eq!(<char as OC<char>>::parse_cell("'\\n'").unwrap(), '\n');
-        assert_eq!(<char as OC<char>>::parse_cell("'\\''").unwrap(), '\'');
-        assert_eq!(<char as OC<char>>::parse_cell("'\\u{41}'").unwrap(), 'A');
-        assert!(<char as OC<char>>::parse_cell("a").is_err());
-        assert!(<char as OC<char>>::parse_cell("'ab'").is_err());
+        assert_eq!(cell("char", "'a'").unwrap(), DynValue::Char('a'));
+        assert_eq!(cell("char", "'\\n'").unwrap(), DynValue::Char('\n'));
+        assert_eq!(cell("char", "'\\''").unwrap(), DynValue::Char('\''));
+        assert_eq!(cell("char", "'\\u{41}'").unwrap(), DynValue::Char('A'));
+        assert!(cell("char", "a").is_err());
+        assert!(cell("char", "'ab'").is_err());
     }
 
     #[test]
     fn parse_str_raw_and_quoted() {
-        use OwnedCell as OC;
         // standalone &str cells are raw text
         assert_eq!(
-            <String as OC<&'static str>>::parse_cell("hello, world").unwrap(),
-            "hello, world"
+            cell("&str", "hello, world").unwrap(),
+            DynValue::Str("hello, world".into())
         );
         // inside tuples they are Debug-quoted
         assert_eq!(
-            <String as OC<&'static str>>::parse_elem("\"a, b\"").unwrap(),
-            "a, b"
+            elem("&str", "\"a, b\"").unwrap(),
+            DynValue::Str("a, b".into())
         );
         assert_eq!(
-            <String as OC<&'static str>>::parse_elem("\"line\\nbreak\"").unwrap(),
-            "line\nbreak"
+            elem("String", "\"line\\nbreak\"").unwrap(),
+            DynValue::Str("line\nbreak".into())
         );
-        assert!(<String as OC<&'static str>>::parse_elem("no quotes").is_err());
+        assert!(elem("&str", "no quotes").is_err());
     }
 
     #[test]
     fn parse_bytes_hex_and_list() {
-        use OwnedCell as OC;
         assert_eq!(
-            <Vec<u8> as OC<&'static [u8]>>::parse_cell("0x01abff").unwrap(),
-            vec![1, 0xab, 0xff]
+            cell("&[u8]", "0x01abff").unwrap(),
+            DynValue::Bytes(vec![1, 0xab, 0xff])
+        );
+        assert_eq!(cell("&[u8]", "0x").unwrap(), DynValue::Bytes(vec![]));
+        assert!(cell("&[u8]", "01ab").is_err());
+        assert!(cell("&[u8]", "0xzz").is_err());
+        assert_eq!(
+            elem("&[u8]", "[1, 2, 255]").unwrap(),
+            DynValue::Bytes(vec![1, 2, 255])
+        );
+        assert_eq!(elem("&[u8]", "[]").unwrap(), DynValue::Bytes(vec![]));
+        assert!(elem("&[u8]", "[300]").is_err());
+    }
+
+    #[test]
+    fn raw_cells_ignore_the_ascii_column() {
+        assert_eq!(parse_raw_cell("0x6869 |hi|").unwrap(), b"hi");
+        assert_eq!(parse_raw_cell("0x0001").unwrap(), [0, 1]);
+        assert_eq!(parse_raw_cell("0x01zz |..|").unwrap_err().position, 4);
+    }
+
+    #[test]
+    fn parse_tuples() {
+        use DynValue as D;
+        assert_eq!(
+            cell("(u64,u64)", "(1, 2)").unwrap(),
+            tuple(vec![D::U64(1), D::U64(2)])
         );
         assert_eq!(
-            <Vec<u8> as OC<&'static [u8]>>::parse_cell("0x").unwrap(),
-            Vec::<u8>::new()
+            cell("(i32,i32,i32)", "(-1, 2, 3)").unwrap(),
+            tuple(vec![D::I32(-1), D::I32(2), D::I32(3)])
         );
-        assert!(<Vec<u8> as OC<&'static [u8]>>::parse_cell("01ab").is_err());
-        assert!(<Vec<u8> as OC<&'static [u8]>>::parse_cell("0xzz").is_err());
         assert_eq!(
-            <Vec<u8> as OC<&'static [u8]>>::parse_elem("[1, 2, 255]").unwrap(),
-            vec![1, 2, 255]
+            cell("(&str,&str)", r#"("a, b", "c")"#).unwrap(),
+            tuple(vec![D::Str("a, b".into()), D::Str("c".into())])
         );
         assert_eq!(
-            <Vec<u8> as OC<&'static [u8]>>::parse_elem("[]").unwrap(),
-            Vec::<u8>::new()
+            cell("(&[u8],&[u8])", "([1, 2], [3])").unwrap(),
+            tuple(vec![D::Bytes(vec![1, 2]), D::Bytes(vec![3])])
         );
-        assert!(<Vec<u8> as OC<&'static [u8]>>::parse_elem("[300]").is_err());
+        assert_eq!(
+            cell("(char,char)", "('a', '\\n')").unwrap(),
+            tuple(vec![D::Char('a'), D::Char('\n')])
+        );
+        // mixed element types are fine too
+        assert_eq!(
+            cell("(u8,&str)", "(1, \"x\")").unwrap(),
+            tuple(vec![D::U8(1), D::Str("x".into())])
+        );
+        assert!(cell("(u64,u64)", "(1)").is_err());
+        assert!(cell("(u64,u64)", "(1, 2, 3)").is_err());
+        assert!(cell("(u64,u64)", "1, 2").is_err());
+        assert!(cell("(u64,u64)", "(1, x)").is_err());
     }
 
     #[test]
-    fn parse_tuples() {
-        use OwnedCell as OC;
-        let pair: (u64, u64) = OC::<(u64, u64)>::parse_cell("(1, 2)").unwrap();
-        assert_eq!(pair, (1, 2));
-        let pair: (u128, u128) = OC::<(u128, u128)>::parse_cell("(1, 2)").unwrap();
-        assert_eq!(pair, (1, 2));
-        let triple: (i32, i32, i32) = OC::<(i32, i32, i32)>::parse_cell("(-1, 2, 3)").unwrap();
-        assert_eq!(triple, (-1, 2, 3));
-        let sp: (String, String) =
-            OC::<(&'static str, &'static str)>::parse_cell(r#"("a, b", "c")"#).unwrap();
-        assert_eq!(sp, ("a, b".to_string(), "c".to_string()));
-        let bp: (Vec<u8>, Vec<u8>) =
-            OC::<(&'static [u8], &'static [u8])>::parse_cell("([1, 2], [3])").unwrap();
-        assert_eq!(bp, (vec![1, 2], vec![3]));
-        let cp: (char, char) = OC::<(char, char)>::parse_cell("('a', '\\n')").unwrap();
-        assert_eq!(cp, ('a', '\n'));
-        assert!(<(u64, u64) as OC<(u64, u64)>>::parse_cell("(1)").is_err());
-        assert!(<(u64, u64) as OC<(u64, u64)>>::parse_cell("(1, 2, 3)").is_err());
-        assert!(<(u64, u64) as OC<(u64, u64)>>::parse_cell("1, 2").is_err());
-        assert!(<(u64, u64) as OC<(u64, u64)>>::parse_cell("(1, x)").is_err());
+    fn parse_errors_point_at_the_bad_element() {
+        assert_eq!(cell("u64", "  x").unwrap_err().position, 2);
+        assert_eq!(cell("(u64,u64)", "(1, x)").unwrap_err().position, 4);
+        assert_eq!(
+            cell("(&str,&str)", r#"("a", "b\q")"#).unwrap_err().position,
+            8
+        );
+        assert_eq!(cell("&[u8]", "0x01zz").unwrap_err().position, 4);
+        assert_eq!(elem("&[u8]", "[1, 300]").unwrap_err().position, 4);
+        assert_eq!(cell("Option<u8>", "Some(x)").unwrap_err().position, 5);
     }
 
     #[test]
     fn split_respects_quotes_and_nesting() {
         let parts = split_top_level("\"a, b\", 'c', [1, 2], (3, 4)").unwrap();
-        assert_eq!(parts, vec!["\"a, b\"", " 'c'", " [1, 2]", " (3, 4)"]);
+        assert_eq!(
+            parts,
+            vec![
+                (0, "\"a, b\"".to_string()),
+                (7, " 'c'".to_string()),
+                (12, " [1, 2]".to_string()),
+                (20, " (3, 4)".to_string()),
+            ]
+        );
         assert!(split_top_level("\"unterminated").is_err());
     }
 
     #[test]
     fn fmt_parse_roundtrip_per_type() {
-        use OwnedCell as OC;
-        // keys
-        assert_eq!(<u64 as OC<u64>>::parse_cell(&42u64.fmt_cell()).unwrap(), 42);
-        assert_eq!(<i8 as OC<i8>>::parse_cell(&(-7i8).fmt_cell()).unwrap(), -7);
-        assert_eq!(
-            <char as OC<char>>::parse_cell(&'\n'.fmt_cell()).unwrap(),
-            '\n'
-        );
-        assert_eq!(
-            <String as OC<&'static str>>::parse_cell(&"a,b (c) 'd'".fmt_cell()).unwrap(),
-            "a,b (c) 'd'"
-        );
-        let hex = bytes_to_hex(&[0xde, 0xad]);
-        assert_eq!(
-            <Vec<u8> as OC<&'static [u8]>>::parse_cell(&hex).unwrap(),
-            vec![0xde, 0xad]
-        );
-        // tuples roundtrip through fmt_cell + parse_cell
-        let t2 = (340282366920938463463374607431768211455u128, 7u128);
-        let parsed2 = <(u128, u128) as OC<(u128, u128)>>::parse_cell(&t2.fmt_cell()).unwrap();
-        assert_eq!(parsed2, t2);
-        let ts = ("x, y".to_string(), "z".to_string());
-        let parsed_s =
-            <(String, String) as OC<(&'static str, &'static str)>>::parse_cell(&ts.fmt_cell())
-                .unwrap();
-        assert_eq!(parsed_s, ts);
-        let tb = (vec![1u8, 2], vec![255]);
-        let parsed_b =
-            <(Vec<u8>, Vec<u8>) as OC<(&'static [u8], &'static [u8])>>::parse_cell(&tb.fmt_cell())
-                .unwrap();
-        assert_eq!(parsed_b, tb);
-        let t3 = (1u64, 2, 3);
-        let parsed3 = <(u64, u64, u64) as OC<(u64, u64, u64)>>::parse_cell(&t3.fmt_cell()).unwrap();
-        assert_eq!(parsed3, t3);
-        // float values roundtrip (shortest Debug repr reparses identically)
-        assert_eq!(
-            <f64 as OC<f64>>::parse_cell(&std::f64::consts::PI.fmt_cell()).unwrap(),
-            std::f64::consts::PI
-        );
+        for (type_name, text) in [
+            ("u64", "42"),
+            ("i8", "-7"),
+            ("char", "'\\n'"),
+            ("&str", "a,b (c) 'd'"),
+            ("&[u8]", "0xdead"),
+            (
+                "(u128,u128)",
+                "(340282366920938463463374607431768211455, 7)",
+            ),
+            ("(&str,&str)", "(\"x, y\", \"z\")"),
+            ("(&[u8],&[u8])", "([1, 2], [255])"),
+            ("(u64,u64,u64)", "(1, 2, 3)"),
+            ("f64", "3.141592653589793"),
+            ("Option<(u8,&str)>", "Some((1, \"a\"))"),
+            ("Vec<u16>", "[1, 2]"),
+            ("[&str;2]", "[\"a\", \"b\"]"),
+        ] {
+            assert_eq!(
+                cell(type_name, text).unwrap().to_string(),
+                text,
+                "{type_name}"
+            );
+        }
     }
 }
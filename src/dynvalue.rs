@@ -0,0 +1,327 @@
+use crate::dynread::bytes_to_hex;
+use std::fmt;
+
+/// A key or value of any table built from redb's built-in types or
+/// described by a loaded layout, for callers that want typed data instead
+/// of formatted cell strings.
+///
+/// `Display` renders the same text as a table cell, and `DynValue::parse`
+/// with the column's type name reads it back to an equal value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynValue {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    F32(f32),
+    F64(f64),
+    Bool(bool),
+    Char(char),
+    Unit,
+    /// `&str` and `String` columns.
+    Str(String),
+    /// `&[u8]` columns.
+    Bytes(Vec<u8>),
+    Tuple(Vec<DynValue>),
+    Option(Option<Box<DynValue>>),
+    /// Fixed arrays `[T;N]` and `Vec<T>`.
+    List(Vec<DynValue>),
+    /// A field list layout of an application defined type, see `layout`.
+    Struct(Vec<(String, DynValue)>),
+    /// An application defined type stored with a serde codec.
+    Json(serde_json::Value),
+    /// The stored bytes of a type that cannot be decoded.
+    Raw(Vec<u8>),
+}
+
+impl DynValue {
+    /// Short name of the variant, used in conversion errors.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DynValue::U8(_) => "u8",
+            DynValue::U16(_) => "u16",
+            DynValue::U32(_) => "u32",
+            DynValue::U64(_) => "u64",
+            DynValue::U128(_) => "u128",
+            DynValue::I8(_) => "i8",
+            DynValue::I16(_) => "i16",
+            DynValue::I32(_) => "i32",
+            DynValue::I64(_) => "i64",
+            DynValue::I128(_) => "i128",
+            DynValue::F32(_) => "f32",
+            DynValue::F64(_) => "f64",
+            DynValue::Bool(_) => "bool",
+            DynValue::Char(_) => "char",
+            DynValue::Unit => "()",
+            DynValue::Str(_) => "string",
+            DynValue::Bytes(_) => "bytes",
+            DynValue::Tuple(_) => "tuple",
+            DynValue::Option(_) => "option",
+            DynValue::List(_) => "list",
+            DynValue::Struct(_) => "struct",
+            DynValue::Json(_) => "json",
+            DynValue::Raw(_) => "raw",
+        }
+    }
+
+    /// Formats the value as a tuple element: strings are quoted and bytes
+    /// are listed, like `Debug` does for tuples.
+    fn fmt_elem(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DynValue::Str(s) => write!(f, "{s:?}"),
+            DynValue::Bytes(b) => write!(f, "{b:?}"),
+            DynValue::Json(value) => write!(f, "{value}"),
+            other => fmt::Display::fmt(other, f),
+        }
+    }
+}
+
+impl fmt::Display for DynValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DynValue::U8(v) => write!(f, "{v:?}"),
+            DynValue::U16(v) => write!(f, "{v:?}"),
+            DynValue::U32(v) => write!(f, "{v:?}"),
+            DynValue::U64(v) => write!(f, "{v:?}"),
+            DynValue::U128(v) => write!(f, "{v:?}"),
+            DynValue::I8(v) => write!(f, "{v:?}"),
+            DynValue::I16(v) => write!(f, "{v:?}"),
+            DynValue::I32(v) => write!(f, "{v:?}"),
+            DynValue::I64(v) => write!(f, "{v:?}"),
+            DynValue::I128(v) => write!(f, "{v:?}"),
+            DynValue::F32(v) => write!(f, "{v:?}"),
+            DynValue::F64(v) => write!(f, "{v:?}"),
+            DynValue::Bool(v) => write!(f, "{v:?}"),
+            DynValue::Char(v) => write!(f, "{v:?}"),
+            DynValue::Unit => f.write_str("()"),
+            DynValue::Str(s) => f.write_str(s),
+            DynValue::Bytes(b) => f.write_str(&bytes_to_hex(b)),
+            DynValue::Tuple(elems) => {
+                f.write_str("(")?;
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    elem.fmt_elem(f)?;
+                }
+                f.write_str(if elems.len() == 1 { ",)" } else { ")" })
+            }
+            DynValue::Option(None) => f.write_str("None"),
+            DynValue::Option(Some(inner)) => {
+                f.write_str("Some(")?;
+                inner.fmt_elem(f)?;
+                f.write_str(")")
+            }
+            DynValue::List(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    item.fmt_elem(f)?;
+                }
+                f.write_str("]")
+            }
+            DynValue::Struct(fields) => {
+                f.write_str("{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{name}: ")?;
+                    value.fmt_elem(f)?;
+                }
+                f.write_str("}")
+            }
+            // pretty, unless nested in another value
+            DynValue::Json(value) => write!(f, "{value:#}"),
+            // hex, then the printable ASCII bytes like a hex dump does
+            DynValue::Raw(b) => {
+                f.write_str(&bytes_to_hex(b))?;
+                if b.iter().any(|c| c.is_ascii_graphic()) {
+                    let ascii: String = b
+                        .iter()
+                        .map(|&c| if c.is_ascii_graphic() { c as char } else { '.' })
+                        .collect();
+                    write!(f, " |{ascii}|")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Converts a value read from redb into a `DynValue`.
+pub trait IntoDyn {
+    fn to_dyn(&self) -> DynValue;
+}
+
+/// Converts a `DynValue` into the owned form of a redb type, `None` if the
+/// variant does not match.
+pub trait FromDyn: Sized {
+    fn from_dyn(value: &DynValue) -> Option<Self>;
+}
+
+macro_rules! impl_dyn_scalar {
+    ($($t:ty => $variant:ident),*) => {
+        $(
+            impl IntoDyn for $t {
+                fn to_dyn(&self) -> DynValue {
+                    DynValue::$variant(*self)
+                }
+            }
+            impl FromDyn for $t {
+                fn from_dyn(value: &DynValue) -> Option<Self> {
+                    match value {
+                        DynValue::$variant(v) => Some(*v),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_dyn_scalar!(
+    u8 => U8, u16 => U16, u32 => U32, u64 => U64, u128 => U128,
+    i8 => I8, i16 => I16, i32 => I32, i64 => I64, i128 => I128,
+    f32 => F32, f64 => F64, bool => Bool, char => Char
+);
+
+impl IntoDyn for () {
+    fn to_dyn(&self) -> DynValue {
+        DynValue::Unit
+    }
+}
+
+impl FromDyn for () {
+    fn from_dyn(value: &DynValue) -> Option<Self> {
+        matches!(value, DynValue::Unit).then_some(())
+    }
+}
+
+impl IntoDyn for &str {
+    fn to_dyn(&self) -> DynValue {
+        DynValue::Str(self.to_string())
+    }
+}
+
+impl IntoDyn for String {
+    fn to_dyn(&self) -> DynValue {
+        DynValue::Str(self.clone())
+    }
+}
+
+impl FromDyn for String {
+    fn from_dyn(value: &DynValue) -> Option<Self> {
+        match value {
+            DynValue::Str(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl IntoDyn for &[u8] {
+    fn to_dyn(&self) -> DynValue {
+        DynValue::Bytes(self.to_vec())
+    }
+}
+
+impl FromDyn for Vec<u8> {
+    fn from_dyn(value: &DynValue) -> Option<Self> {
+        match value {
+            DynValue::Bytes(b) => Some(b.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<A: IntoDyn, B: IntoDyn> IntoDyn for (A, B) {
+    fn to_dyn(&self) -> DynValue {
+        DynValue::Tuple(vec![self.0.to_dyn(), self.1.to_dyn()])
+    }
+}
+
+impl<A: FromDyn, B: FromDyn> FromDyn for (A, B) {
+    fn from_dyn(value: &DynValue) -> Option<Self> {
+        match value {
+            DynValue::Tuple(elems) if elems.len() == 2 => {
+                Some((A::from_dyn(&elems[0])?, B::from_dyn(&elems[1])?))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<A: IntoDyn, B: IntoDyn, C: IntoDyn> IntoDyn for (A, B, C) {
+    fn to_dyn(&self) -> DynValue {
+        DynValue::Tuple(vec![self.0.to_dyn(), self.1.to_dyn(), self.2.to_dyn()])
+    }
+}
+
+impl<A: FromDyn, B: FromDyn, C: FromDyn> FromDyn for (A, B, C) {
+    fn from_dyn(value: &DynValue) -> Option<Self> {
+        match value {
+            DynValue::Tuple(elems) if elems.len() == 3 => Some((
+                A::from_dyn(&elems[0])?,
+                B::from_dyn(&elems[1])?,
+                C::from_dyn(&elems[2])?,
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_matches_cell_format() {
+        assert_eq!(DynValue::U64(42).to_string(), "42");
+        assert_eq!(DynValue::F64(1.0).to_string(), "1.0");
+        assert_eq!(DynValue::Char('\n').to_string(), "'\\n'");
+        assert_eq!(DynValue::Str("a, b".into()).to_string(), "a, b");
+        assert_eq!(DynValue::Bytes(vec![0, 255]).to_string(), "0x00ff");
+        let tuple = DynValue::Tuple(vec![DynValue::Str("x".into()), DynValue::Bytes(vec![1, 2])]);
+        assert_eq!(tuple.to_string(), "(\"x\", [1, 2])");
+        let nested = DynValue::Option(Some(Box::new(DynValue::List(vec![DynValue::Str(
+            "y".into(),
+        )]))));
+        assert_eq!(nested.to_string(), format!("{:?}", Some(["y"])));
+        assert_eq!(DynValue::Tuple(vec![DynValue::U8(1)]).to_string(), "(1,)");
+        let record = DynValue::Struct(vec![
+            ("id".into(), DynValue::U64(1)),
+            ("name".into(), DynValue::Str("a, b".into())),
+        ]);
+        assert_eq!(record.to_string(), "{id: 1, name: \"a, b\"}");
+        assert_eq!(
+            DynValue::Raw(vec![3, 0, b'b', b'o', b'b']).to_string(),
+            "0x0300626f62 |..bob|"
+        );
+        assert_eq!(DynValue::Raw(vec![0, 1]).to_string(), "0x0001");
+        let json = DynValue::Json(serde_json::json!({"kind": "start"}));
+        assert_eq!(json.to_string(), "{\n  \"kind\": \"start\"\n}");
+        let nested = DynValue::Option(Some(Box::new(json)));
+        assert_eq!(nested.to_string(), r#"Some({"kind":"start"})"#);
+        assert_eq!(
+            ("x", &[1u8, 2][..]).to_dyn().to_string(),
+            format!("{:?}", ("x", &[1u8, 2][..]))
+        );
+    }
+
+    #[test]
+    fn conversions_check_the_variant() {
+        assert_eq!(u32::from_dyn(&DynValue::U32(7)), Some(7));
+        assert_eq!(u32::from_dyn(&DynValue::U64(7)), None);
+        let pair = (1u64, 2u64).to_dyn();
+        assert_eq!(<(u64, u64)>::from_dyn(&pair), Some((1, 2)));
+        assert_eq!(<(u64, u64, u64)>::from_dyn(&pair), None);
+    }
+}
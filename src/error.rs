@@ -0,0 +1,210 @@
+use std::fmt;
+
+/// Errors reported by the library. Callers can tell a missing key or table
+/// from a parse error or real storage trouble by matching the variant; the
+/// CLI maps each variant to its own exit code, see `exit_code`.
+#[derive(Debug)]
+pub enum Error {
+    /// The database file does not exist.
+    DatabaseNotFound(String),
+    /// The database has no table of this name.
+    TableNotFound(String),
+    /// The table's key/value types are not built from redb's built-in
+    /// types (ex: an application defined `Value` without a loaded layout),
+    /// so it cannot be `operation` ("read", "edited", "copied").
+    UnsupportedType {
+        table: String,
+        key: String,
+        value: String,
+        operation: &'static str,
+    },
+    /// The table has no row with this key.
+    KeyNotFound(String),
+    /// A cell could not be parsed into its column type; `position` is the
+    /// byte offset in `input` where the problem starts.
+    Parse {
+        input: String,
+        position: usize,
+        message: String,
+    },
+    /// A layouts file could not be understood.
+    InvalidLayout {
+        path: String,
+        message: String,
+    },
+    /// The config file could not be understood.
+    InvalidConfig {
+        path: String,
+        message: String,
+    },
+    /// Another process holds the file lock of the database at this path
+    /// (empty when the error came from redb without one).
+    Locked(String),
+    /// The command is not possible in the current state, ex: a write in
+    /// read-only mode or while a snapshot is pinned.
+    Refused(String),
+    Io(std::io::Error),
+    Redb(redb::Error),
+}
+
+impl Error {
+    pub(crate) fn refused(message: impl Into<String>) -> Self {
+        Error::Refused(message.into())
+    }
+
+    /// Process exit code for this error; 2 is left to argument errors
+    /// reported by clap.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Refused(_) => 1,
+            Error::DatabaseNotFound(_) => 3,
+            Error::TableNotFound(_) => 4,
+            Error::KeyNotFound(_) => 5,
+            Error::UnsupportedType { .. } => 6,
+            Error::Parse { .. } => 7,
+            Error::Locked(_) => 8,
+            Error::Io(_) => 9,
+            Error::Redb(_) => 10,
+            Error::InvalidLayout { .. } => 11,
+            Error::InvalidConfig { .. } => 12,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DatabaseNotFound(path) => write!(f, "Database '{path}' not found"),
+            Error::TableNotFound(name) => write!(f, "Table '{name}' does not exist"),
+            Error::UnsupportedType {
+                table,
+                key,
+                value,
+                operation,
+            } => write!(
+                f,
+                "table '{table}' has key/value types Table<{key}, {value}> that cannot be {operation} (unsupported)"
+            ),
+            Error::KeyNotFound(key) => write!(f, "Key not found: {key}"),
+            Error::Parse {
+                position, message, ..
+            } => write!(f, "{message} (at position {position})"),
+            Error::InvalidLayout { path, message } => {
+                write!(f, "invalid layouts file '{path}': {message}")
+            }
+            Error::InvalidConfig { path, message } => {
+                write!(f, "invalid config file '{path}': {message}")
+            }
+            Error::Locked(path) if path.is_empty() => f.write_str(
+                "the database is locked by another process (a running service?)",
+            ),
+            Error::Locked(path) => write!(
+                f,
+                "'{path}' is locked by another process (a running service?), run 'set {path}' to inspect a temporary copy instead"
+            ),
+            Error::Refused(message) => f.write_str(message),
+            Error::Io(e) => e.fmt(f),
+            Error::Redb(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Redb(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<redb::Error> for Error {
+    fn from(e: redb::Error) -> Self {
+        match e {
+            redb::Error::TableDoesNotExist(name) => Error::TableNotFound(name),
+            redb::Error::Io(e) => Error::Io(e),
+            redb::Error::DatabaseAlreadyOpen => Error::Locked(String::new()),
+            e => Error::Redb(e),
+        }
+    }
+}
+
+macro_rules! from_redb {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Error {
+            fn from(e: $t) -> Self {
+                redb::Error::from(e).into()
+            }
+        })*
+    };
+}
+
+from_redb!(
+    redb::DatabaseError,
+    redb::TransactionError,
+    redb::TableError,
+    redb::StorageError,
+    redb::CommitError,
+    redb::CompactionError,
+    redb::SavepointError
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redb_errors_keep_their_meaning() {
+        let missing: Error = redb::TableError::TableDoesNotExist("t".to_string()).into();
+        assert!(matches!(missing, Error::TableNotFound(ref name) if name == "t"));
+        let locked: Error = redb::DatabaseError::DatabaseAlreadyOpen.into();
+        assert!(matches!(locked, Error::Locked(_)));
+        assert_eq!(locked.exit_code(), 8);
+        let locked: Error = redb::Error::DatabaseAlreadyOpen.into();
+        assert_eq!(locked.exit_code(), 8);
+    }
+
+    #[test]
+    fn exit_codes_are_distinct() {
+        let errors = [
+            Error::refused("no"),
+            Error::DatabaseNotFound("db".into()),
+            Error::TableNotFound("t".into()),
+            Error::KeyNotFound("k".into()),
+            Error::UnsupportedType {
+                table: "t".into(),
+                key: "u64".into(),
+                value: "Option<u64>".into(),
+                operation: "read",
+            },
+            Error::Parse {
+                input: "x".into(),
+                position: 0,
+                message: "bad".into(),
+            },
+            Error::InvalidLayout {
+                path: "layouts.json".into(),
+                message: "bad".into(),
+            },
+            Error::InvalidConfig {
+                path: "config.json".into(),
+                message: "bad".into(),
+            },
+            Error::Locked("db".into()),
+            Error::Io(std::io::Error::other("io")),
+            Error::Redb(redb::Error::Corrupted("bad".into())),
+        ];
+        let mut codes: Vec<u8> = errors.iter().map(Error::exit_code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&0) && !codes.contains(&2));
+    }
+}
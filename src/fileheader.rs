@@ -0,0 +1,75 @@
+//! Minimal reader for the redb file header, for values redb does not expose
+//! through its API. Layout (redb file format v3): a 64 byte header whose
+//! byte 9 is the "god byte" selecting the primary commit slot, followed by
+//! two 128 byte commit slots that each store the last committed
+//! transaction id at offset 104.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+const MAGICNUMBER: [u8; 9] = [b'r', b'e', b'd', b'b', 0x1A, 0x0A, 0xA9, 0x0D, 0x0A];
+const GOD_BYTE_OFFSET: usize = MAGICNUMBER.len();
+const PRIMARY_BIT: u8 = 1;
+const TRANSACTION_0_OFFSET: usize = 64;
+const TRANSACTION_SIZE: usize = 128;
+const TRANSACTION_ID_OFFSET: usize = 104;
+const HEADER_SIZE: usize = TRANSACTION_0_OFFSET + 2 * TRANSACTION_SIZE;
+
+/// Parses the id of the last committed transaction out of a raw header.
+pub fn parse_last_transaction_id(header: &[u8]) -> Result<u64, String> {
+    if header.len() < HEADER_SIZE {
+        return Err("file is too short to be a redb database".to_string());
+    }
+    if header[..MAGICNUMBER.len()] != MAGICNUMBER {
+        return Err("file is not a redb database (bad magic number)".to_string());
+    }
+    let primary = (header[GOD_BYTE_OFFSET] & PRIMARY_BIT) as usize;
+    let offset = TRANSACTION_0_OFFSET + primary * TRANSACTION_SIZE + TRANSACTION_ID_OFFSET;
+    let bytes: [u8; 8] = header[offset..offset + 8]
+        .try_into()
+        .map_err(|_| "truncated commit slot".to_string())?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Reads the id of the last committed transaction of the database at `path`.
+pub fn last_transaction_id(path: impl AsRef<Path>) -> Result<u64, String> {
+    let mut header = [0u8; HEADER_SIZE];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map_err(|e| format!("read database header failed: {e}"))?;
+    parse_last_transaction_id(&header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_with(primary: u8, slot0: u64, slot1: u64) -> Vec<u8> {
+        let mut header = vec![0u8; HEADER_SIZE];
+        header[..MAGICNUMBER.len()].copy_from_slice(&MAGICNUMBER);
+        header[GOD_BYTE_OFFSET] = primary;
+        let at0 = TRANSACTION_0_OFFSET + TRANSACTION_ID_OFFSET;
+        let at1 = at0 + TRANSACTION_SIZE;
+        header[at0..at0 + 8].copy_from_slice(&slot0.to_le_bytes());
+        header[at1..at1 + 8].copy_from_slice(&slot1.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn reads_primary_slot() {
+        assert_eq!(parse_last_transaction_id(&header_with(0, 5, 4)).unwrap(), 5);
+        assert_eq!(parse_last_transaction_id(&header_with(1, 5, 6)).unwrap(), 6);
+        // other god byte flags do not change the slot
+        assert_eq!(
+            parse_last_transaction_id(&header_with(0b110, 5, 6)).unwrap(),
+            5
+        );
+    }
+
+    #[test]
+    fn rejects_non_redb_data() {
+        assert!(parse_last_transaction_id(&[0u8; 8]).is_err());
+        assert!(parse_last_transaction_id(&[0u8; HEADER_SIZE]).is_err());
+    }
+}
@@ -1,10 +1,32 @@
-use clap::{self, Args, Parser, Subcommand};
+use crate::dynread::CopyMode;
+use clap::{self, Args, Parser, Subcommand, ValueEnum};
 #[derive(clap::Parser)]
 #[command(name = "readbcli")]
 #[command(about = "readbcli for control redb", long_about = None)]
 pub struct Binflags {
     #[arg(long, help = "redb database path", default_value = None)]
     pub path: Option<String>,
+    #[arg(long, help = "append every write to this audit journal", default_value = None)]
+    pub audit: Option<String>,
+    #[arg(long, help = "preview writes without committing them")]
+    pub dry_run: bool,
+    #[arg(
+        long,
+        help = "open through redb's read-only API and refuse every write"
+    )]
+    pub read_only: bool,
+    #[arg(
+        long,
+        help = "load layouts of application defined key/value types from this JSON file",
+        default_value = None
+    )]
+    pub layouts: Option<String>,
+    #[arg(
+        long,
+        help = "config file to keep settings in [default: ~/.config/redbcli/config.json]",
+        default_value = None
+    )]
+    pub config: Option<String>,
 }
 
 #[derive(Debug, Parser)]
@@ -43,6 +65,135 @@ pub enum Commands {
     #[command(about = "Delete a table", long_about = None)]
     Delete {
         tablename: String,
+        /// delete even if the table cannot be captured for `undo`
+        #[arg(long)]
+        force: bool,
+    },
+
+    #[command(about = "Insert or overwrite a row of the table in use ex:'put $key $value'", long_about = None)]
+    Put {
+        key: String,
+        value: String,
+    },
+    #[command(about = "Remove a row of the table in use ex:'del $key'", long_about = None)]
+    Del {
+        key: String,
+        /// delete a row even if it cannot be captured for `undo`, ex: one
+        /// under a raw key
+        #[arg(long)]
+        force: bool,
+    },
+
+    #[command(about = "Revert the last put, del, edit, create or delete of this session", long_about = None)]
+    Undo,
+
+    #[command(about = "Start a transaction that later writes join until commit or rollback", long_about = None)]
+    Begin,
+    #[command(about = "Commit the open transaction", long_about = None)]
+    Commit,
+    #[command(about = "Discard the open transaction", long_about = None)]
+    Rollback,
+
+    #[command(about = "Preview writes without committing them ex:'dryrun on'", long_about = None)]
+    Dryrun {
+        #[arg(value_enum)]
+        state: Switch,
+    },
+
+    #[command(about = "Pin one read snapshot for all following reads ex:'snapshot on'", long_about = None)]
+    Snapshot {
+        #[arg(value_enum)]
+        state: Switch,
+    },
+
+    #[command(about = "Close the database, releasing its file lock", long_about = None)]
+    Close,
+
+    #[command(about = "Open the database again, ex: after 'close' or when another process changed it", long_about = None)]
+    Reopen,
+
+    #[command(about = "Copy a table into another database ex:'copy-to /tmp/other.redb $tablename'", long_about = None)]
+    CopyTo {
+        /// destination database, created if missing
+        filepath: String,
+        tablename: String,
+        #[command(flatten)]
+        opts: CopyArgs,
+    },
+    #[command(about = "Copy a table from another database ex:'copy-from /tmp/other.redb $tablename'", long_about = None)]
+    CopyFrom {
+        /// source database
+        filepath: String,
+        tablename: String,
+        #[command(flatten)]
+        opts: CopyArgs,
+    },
+
+    #[command(about = "Print content hashes ex:'checksum $tablename' or 'checksum --all'", long_about = None)]
+    Checksum {
+        /// table to hash, defaults to the table in use
+        tablename: Option<String>,
+        /// hash every table of the database
+        #[arg(long, conflicts_with = "tablename")]
+        all: bool,
+    },
+
+    #[command(about = "Check database integrity, repairing it if possible", long_about = None)]
+    Check,
+
+    #[command(about = "Copy readable tables of a damaged database into a new one ex:'salvage broken.redb rescued.redb'", long_about = None)]
+    Salvage {
+        /// damaged database
+        damaged: String,
+        /// new database to create
+        dest: String,
+    },
+
+    #[command(about = "Compact the database file", long_about = None)]
+    Compact,
+
+    #[command(about = "Write a consistent copy of the database to a new file ex:'backup /tmp/backup.redb'", long_about = None)]
+    Backup {
+        /// new database to create
+        dest: String,
+        /// compact the copy after writing it
+        #[arg(long)]
+        compact: bool,
+    },
+
+    #[command(about = "Manage persistent savepoints ex:'savepoint create'", long_about = None)]
+    Savepoint(SavepointArgs),
+
+    #[command(about = "Load layouts of application defined types, or list the loaded ones ex:'layouts /etc/app/layouts.json'", long_about = None)]
+    Layouts {
+        /// JSON layouts file, replaces the layouts loaded before
+        filepath: Option<String>,
+    },
+
+    #[command(about = "Decode serialized &[u8] values of a table, or of every table this session ex:'decode events --value msgpack'", long_about = None)]
+    Decode {
+        /// table whose decoder is set and saved in the config file; without
+        /// one, `--value` applies to every table for this session
+        tablename: Option<String>,
+        /// json, msgpack, cbor, bincode, postcard, or none to show hex again
+        #[arg(long)]
+        value: Option<String>,
+        /// loaded layout giving the fields for bincode and postcard
+        #[arg(long)]
+        schema: Option<String>,
+    },
+
+    #[command(about = "Show the key or value cells of a table through a lens, or list the lenses ex:'lens events --key epoch-ms'", long_about = None)]
+    Lens {
+        /// table the lenses are set on, the current one if omitted
+        tablename: Option<String>,
+        /// hex, epoch-s, epoch-ms, epoch-ns, bytesize, ip, utf8, uuid, xxd,
+        /// escape, or none for the plain form
+        #[arg(long)]
+        key: Option<String>,
+        /// same choices as --key
+        #[arg(long)]
+        value: Option<String>,
     },
 
     #[command(about = "Create a database", long_about = None)]
@@ -52,6 +203,31 @@ pub enum Commands {
     Exit,
 }
 
+impl Commands {
+    /// Whether the command writes to the current database.
+    pub fn is_mutating(&self) -> bool {
+        match self {
+            Commands::Edit
+            | Commands::Create { .. }
+            | Commands::Delete { .. }
+            | Commands::Put { .. }
+            | Commands::Del { .. }
+            | Commands::Undo
+            | Commands::Begin
+            | Commands::Commit
+            | Commands::Rollback
+            | Commands::CopyFrom { .. }
+            | Commands::Check
+            | Commands::Compact => true,
+            Commands::Savepoint(args) => !matches!(
+                args.command,
+                SavepointCommands::List | SavepointCommands::Diff { .. }
+            ),
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Args)]
 #[command(args_conflicts_with_subcommands = true)]
 #[command(flatten_help = true)]
@@ -64,11 +240,124 @@ pub struct InfoArgs {
 pub enum InfoCommands {
     //show all tables
     #[command(about = "Show all table name ", long_about = None)]
-    Tables,
+    Tables(TablesArgs),
+    //show database
+    #[command(about = "Show database file information and storage statistics", long_about = None)]
+    Db,
     //show key
     #[command(short_flag='k',about = "Use key get data", long_about = None)]
     Key { key: String },
     //show table data
     #[command(short_flag='t',about = "Get table data", long_about = None)]
-    Table { tablename: String },
+    Table {
+        tablename: String,
+        #[command(flatten)]
+        columns: ColumnArgs,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct ColumnArgs {
+    /// show each element of a tuple key or value as its own column,
+    /// `key.0`, `key.1`, .. `value.0`, ..
+    #[arg(long, short = 'x')]
+    pub expand: bool,
+    /// only show rows whose cell in a column equals a value, ex: 'key.1=7'
+    #[arg(long = "where", value_name = "COLUMN=VALUE")]
+    pub filter: Option<String>,
+    /// sort rows by a column, ex: 'value' or 'key.2'
+    #[arg(long, value_name = "COLUMN")]
+    pub sort: Option<String>,
+    /// sort in descending order
+    #[arg(long, requires = "sort")]
+    pub desc: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Switch {
+    On,
+    Off,
+}
+
+#[derive(Debug, Args)]
+pub struct CopyArgs {
+    /// name of the table in the destination database
+    #[arg(long = "as")]
+    pub as_name: Option<String>,
+    /// keep existing destination rows instead of replacing the table
+    #[arg(long)]
+    pub merge: bool,
+}
+
+impl CopyArgs {
+    pub fn mode(&self) -> CopyMode {
+        if self.merge {
+            CopyMode::Merge
+        } else {
+            CopyMode::Replace
+        }
+    }
+}
+
+#[derive(Debug, Default, Args)]
+pub struct TablesArgs {
+    /// also show row count, stored/metadata/fragmented bytes and tree height
+    #[arg(long, short = 's')]
+    pub stats: bool,
+    /// only show tables whose name matches this glob, ex: 'user_*'
+    #[arg(long = "match", value_name = "GLOB")]
+    pub pattern: Option<String>,
+    /// sort by this column
+    #[arg(long, value_enum)]
+    pub sort: Option<TableColumn>,
+    /// sort in descending order
+    #[arg(long)]
+    pub desc: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TableColumn {
+    Name,
+    KeyType,
+    ValueType,
+    Rows,
+    Stored,
+    Metadata,
+    Fragmented,
+    Height,
+}
+
+impl TableColumn {
+    /// Whether sorting by this column needs per-table statistics.
+    pub fn needs_stats(self) -> bool {
+        !matches!(
+            self,
+            TableColumn::Name | TableColumn::KeyType | TableColumn::ValueType
+        )
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct SavepointArgs {
+    #[command(subcommand)]
+    pub command: SavepointCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SavepointCommands {
+    #[command(about = "Create a persistent savepoint of the whole database", long_about = None)]
+    Create,
+    #[command(about = "List persistent savepoints", long_about = None)]
+    List,
+    #[command(about = "Show the changes and roll the database back to a savepoint", long_about = None)]
+    Restore {
+        id: u64,
+        /// restore without asking for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+    #[command(about = "Show what restoring a savepoint would change", long_about = None)]
+    Diff { id: u64 },
+    #[command(about = "Delete a persistent savepoint", long_about = None)]
+    Delete { id: u64 },
 }
@@ -0,0 +1,807 @@
+use crate::codecs;
+use crate::dynread::{lead, split_top_level, ParseError};
+use crate::dynvalue::DynValue;
+use crate::typecodec::{scalar_cmp, TypeTree};
+use crate::Error;
+use serde_json::Value as Json;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// How an application defined key/value type (a custom `redb::Value`) lays
+/// out its bytes, so tables using it can be decoded and edited without the
+/// application's code. Layouts are loaded from a JSON file keyed by the
+/// type name the application's `Value::type_name` returns:
+///
+/// ```json
+/// {
+///   "app::Record": {
+///     "endian": "little",
+///     "fields": [
+///       {"name": "id", "type": "u64"},
+///       {"name": "name", "type": "str", "len": "u16"},
+///       {"name": "payload", "type": "bytes", "len": "rest"}
+///     ]
+///   },
+///   "app::Event": {"codec": "json"},
+///   "app::Point": {"codec": "bincode", "fields": [
+///     {"name": "x", "type": "i32"}, {"name": "y", "type": "i32"}
+///   ]}
+/// }
+/// ```
+///
+/// Codecs that are not self-describing (bincode, postcard) need the fields
+/// of the serialized struct; their `len` is ignored, the codec decides how
+/// lengths are stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub name: String,
+    pub format: Format,
+    fixed_width: Option<usize>,
+    order: Order,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// Fields stored back to back in declaration order.
+    Fields { endian: Endian, fields: Vec<Field> },
+    /// The whole value is one serde encoded document, shown as JSON;
+    /// `fields` is empty for self-describing codecs.
+    Codec { codec: Codec, fields: Vec<Field> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub kind: FieldKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldKind {
+    /// A fixed width integer, float, bool or char.
+    Scalar(TypeTree),
+    /// UTF-8 text after a length prefix.
+    Str(LenPrefix),
+    /// Raw bytes after a length prefix.
+    Bytes(LenPrefix),
+}
+
+/// Integer the length of a string or byte field is stored in; `Rest` takes
+/// every remaining byte and is only valid for the last field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LenPrefix {
+    U8,
+    U16,
+    U32,
+    U64,
+    Rest,
+}
+
+/// Serde data formats; see `codecs` for how they map to JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Json,
+    MsgPack,
+    Cbor,
+    /// bincode 1 with its default options: fixed width little endian
+    /// integers, u64 lengths.
+    Bincode,
+    /// postcard: varint integers and lengths.
+    Postcard,
+}
+
+impl Codec {
+    pub const NAMES: &'static str = "json, msgpack, cbor, bincode or postcard";
+
+    pub fn parse(name: &str) -> Option<Codec> {
+        match name {
+            "json" => Some(Codec::Json),
+            "msgpack" => Some(Codec::MsgPack),
+            "cbor" => Some(Codec::Cbor),
+            "bincode" => Some(Codec::Bincode),
+            "postcard" => Some(Codec::Postcard),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Json => "json",
+            Codec::MsgPack => "msgpack",
+            Codec::Cbor => "cbor",
+            Codec::Bincode => "bincode",
+            Codec::Postcard => "postcard",
+        }
+    }
+
+    /// Whether decoding needs the fields of the serialized struct.
+    pub fn needs_fields(self) -> bool {
+        matches!(self, Codec::Bincode | Codec::Postcard)
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How encoded keys of the type are ordered; it must agree with the
+/// application's `Key::compare` for keys written through redbcli to land in
+/// the right place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// Field by field in declaration order.
+    Fields,
+    /// Bytewise over the encoded value.
+    Bytes,
+}
+
+impl fmt::Display for Format {
+    /// Summarizes the layout, ex: `{id: u64, name: str(u16)}` or
+    /// `bincode codec of {id: u64, name: str}`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (fields, prefixed) = match self {
+            Format::Codec { codec, fields } if fields.is_empty() => {
+                return write!(f, "{codec} codec")
+            }
+            Format::Codec { codec, fields } => {
+                write!(f, "{codec} codec of ")?;
+                (fields, false)
+            }
+            Format::Fields { fields, .. } => (fields, true),
+        };
+        f.write_str("{")?;
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}: ", field.name)?;
+            let (kind, prefix) = match &field.kind {
+                FieldKind::Scalar(tree) => {
+                    write!(f, "{tree}")?;
+                    continue;
+                }
+                FieldKind::Str(prefix) => ("str", prefix),
+                FieldKind::Bytes(prefix) => ("bytes", prefix),
+            };
+            if !prefixed {
+                f.write_str(kind)?;
+                continue;
+            }
+            let prefix = match prefix {
+                LenPrefix::U8 => "u8",
+                LenPrefix::U16 => "u16",
+                LenPrefix::U32 => "u32",
+                LenPrefix::U64 => "u64",
+                LenPrefix::Rest => "rest",
+            };
+            write!(f, "{kind}({prefix})")?;
+        }
+        f.write_str("}")
+    }
+}
+
+impl LenPrefix {
+    fn width(self) -> usize {
+        match self {
+            LenPrefix::U8 => 1,
+            LenPrefix::U16 => 2,
+            LenPrefix::U32 => 4,
+            LenPrefix::U64 => 8,
+            LenPrefix::Rest => 0,
+        }
+    }
+}
+
+impl Endian {
+    /// Reorders `bytes` between this byte order and little endian.
+    fn to_le(self, mut bytes: Vec<u8>) -> Vec<u8> {
+        if self == Endian::Big {
+            bytes.reverse();
+        }
+        bytes
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Layouts file
+// ---------------------------------------------------------------------------
+
+/// The layouts of a layouts file, by type name.
+#[derive(Debug, Clone, Default)]
+pub struct Layouts {
+    by_name: BTreeMap<String, Arc<Layout>>,
+}
+
+static INSTALLED: RwLock<Option<Arc<Layouts>>> = RwLock::new(None);
+
+impl Layouts {
+    /// Reads and checks a layouts file.
+    pub fn load(path: &str) -> Result<Layouts, Error> {
+        let text = std::fs::read_to_string(path)?;
+        Layouts::parse(&text).map_err(|message| Error::InvalidLayout {
+            path: path.to_string(),
+            message,
+        })
+    }
+
+    /// Parses the JSON text of a layouts file.
+    pub fn parse(text: &str) -> Result<Layouts, String> {
+        let root: Json = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let entries = root
+            .as_object()
+            .ok_or("expected an object of type names to layouts")?;
+        let mut by_name = BTreeMap::new();
+        for (name, spec) in entries {
+            let layout = parse_layout(name, spec).map_err(|e| format!("'{name}': {e}"))?;
+            by_name.insert(name.clone(), Arc::new(layout));
+        }
+        Ok(Layouts { by_name })
+    }
+
+    /// Makes these layouts the ones type names are resolved against, for
+    /// every thread, replacing any loaded before.
+    pub fn install(self) {
+        *INSTALLED.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(self));
+    }
+
+    /// The installed layouts, empty if none were installed.
+    pub fn installed() -> Arc<Layouts> {
+        INSTALLED
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<Layout>> {
+        self.by_name.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Layout>> {
+        self.by_name.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+}
+
+/// The installed layout whose name `text` starts with, followed by the end
+/// of the text or a delimiter of a composite type name. The longest name
+/// wins, so `app::Id` does not shadow `app::Id2`.
+pub(crate) fn match_prefix(text: &str) -> Option<Arc<Layout>> {
+    let layouts = Layouts::installed();
+    layouts
+        .by_name
+        .iter()
+        .filter(|(name, _)| {
+            text.strip_prefix(name.as_str()).is_some_and(|after| {
+                after.is_empty() || after.starts_with([',', ')', ']', '>', ';', ' '])
+            })
+        })
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, layout)| layout.clone())
+}
+
+fn parse_layout(name: &str, spec: &Json) -> Result<Layout, String> {
+    if TypeTree::parse_builtin(name).is_ok() {
+        return Err("a layout cannot replace a redb built-in type".to_string());
+    }
+    let spec = spec.as_object().ok_or("expected an object")?;
+    let text = |key: &str| -> Result<Option<&str>, String> {
+        spec.get(key)
+            .map(|v| v.as_str().ok_or(format!("'{key}' must be a string")))
+            .transpose()
+    };
+    let (format, width, default_order) = match (spec.get("fields"), text("codec")?) {
+        (None, None) => return Err("expected 'fields' or 'codec'".to_string()),
+        (fields, Some(codec)) => {
+            let codec = Codec::parse(codec).ok_or(format!(
+                "unknown codec '{codec}', expected {}",
+                Codec::NAMES
+            ))?;
+            let fields = match (codec.needs_fields(), fields) {
+                (true, Some(fields)) => parse_fields(fields)?,
+                (true, None) => {
+                    return Err(format!(
+                        "the {codec} codec is not self-describing and needs 'fields'"
+                    ))
+                }
+                (false, Some(_)) => {
+                    return Err(format!(
+                        "the {codec} codec is self-describing, give either 'fields' or 'codec'"
+                    ))
+                }
+                (false, None) => Vec::new(),
+            };
+            (Format::Codec { codec, fields }, None, Order::Bytes)
+        }
+        (Some(fields), None) => {
+            let endian = match text("endian")?.unwrap_or("little") {
+                "little" => Endian::Little,
+                "big" => Endian::Big,
+                other => return Err(format!("unknown endian '{other}', expected little or big")),
+            };
+            let fields = parse_fields(fields)?;
+            let width = fields
+                .iter()
+                .map(|field| match &field.kind {
+                    FieldKind::Scalar(tree) => tree.fixed_width(),
+                    _ => None,
+                })
+                .sum();
+            (Format::Fields { endian, fields }, width, Order::Fields)
+        }
+    };
+    // the width redb checks on open; a type may declare itself variable
+    // width even when all its fields are fixed
+    let fixed_width = match spec.get("fixed_width") {
+        None => width,
+        Some(Json::Null) => None,
+        Some(declared) => {
+            let declared = declared
+                .as_u64()
+                .ok_or("'fixed_width' must be a number or null")?;
+            if width != Some(declared as usize) {
+                return Err(format!(
+                    "'fixed_width' is {declared} but the fields take {width:?} bytes"
+                ));
+            }
+            width
+        }
+    };
+    let order = match text("order")? {
+        None => default_order,
+        Some("bytes") => Order::Bytes,
+        Some("fields") if matches!(format, Format::Fields { .. }) => Order::Fields,
+        Some(other) => return Err(format!("unknown order '{other}'")),
+    };
+    Ok(Layout {
+        name: name.to_string(),
+        format,
+        fixed_width,
+        order,
+    })
+}
+
+fn parse_fields(fields: &Json) -> Result<Vec<Field>, String> {
+    let fields = fields.as_array().ok_or("'fields' must be a list")?;
+    let fields = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| parse_field(field, i + 1 == fields.len()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if fields.is_empty() {
+        return Err("'fields' is empty".to_string());
+    }
+    Ok(fields)
+}
+
+fn parse_field(spec: &Json, last: bool) -> Result<Field, String> {
+    let name = spec
+        .get("name")
+        .and_then(Json::as_str)
+        .ok_or("every field needs a 'name'")?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("field name '{name}' must be an identifier"));
+    }
+    let kind_name = spec
+        .get("type")
+        .and_then(Json::as_str)
+        .ok_or(format!("field '{name}' needs a 'type'"))?;
+    let prefix = || -> Result<LenPrefix, String> {
+        let prefix = match spec.get("len").map(|len| len.as_str()) {
+            None => LenPrefix::U32,
+            Some(Some("u8")) => LenPrefix::U8,
+            Some(Some("u16")) => LenPrefix::U16,
+            Some(Some("u32")) => LenPrefix::U32,
+            Some(Some("u64")) => LenPrefix::U64,
+            Some(Some("rest")) if last => LenPrefix::Rest,
+            Some(Some("rest")) => {
+                return Err(format!(
+                    "field '{name}': only the last field can take the rest"
+                ))
+            }
+            Some(_) => {
+                return Err(format!(
+                    "field '{name}': 'len' must be u8, u16, u32, u64 or rest"
+                ))
+            }
+        };
+        Ok(prefix)
+    };
+    let kind = match kind_name {
+        "str" => FieldKind::Str(prefix()?),
+        "bytes" => FieldKind::Bytes(prefix()?),
+        other => match TypeTree::parse_builtin(other) {
+            Ok(
+                tree @ (TypeTree::Bool
+                | TypeTree::Char
+                | TypeTree::U8
+                | TypeTree::U16
+                | TypeTree::U32
+                | TypeTree::U64
+                | TypeTree::U128
+                | TypeTree::I8
+                | TypeTree::I16
+                | TypeTree::I32
+                | TypeTree::I64
+                | TypeTree::I128
+                | TypeTree::F32
+                | TypeTree::F64),
+            ) => FieldKind::Scalar(tree),
+            _ => {
+                return Err(format!(
+                    "field '{name}': unknown type '{other}', expected an integer, float, bool, char, str or bytes"
+                ))
+            }
+        },
+    };
+    Ok(Field {
+        name: name.to_string(),
+        kind,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Values
+// ---------------------------------------------------------------------------
+
+fn take<'d>(data: &'d [u8], at: &mut usize, len: usize) -> Result<&'d [u8], String> {
+    let part = data
+        .get(*at..)
+        .and_then(|rest| rest.get(..len))
+        .ok_or_else(|| format!("value is truncated: need {len} bytes at offset {at}"))?;
+    *at += len;
+    Ok(part)
+}
+
+impl Layout {
+    /// Layout of `&[u8]` values holding a document serialized with `codec`;
+    /// codecs that are not self-describing take the fields of `schema`.
+    pub fn serialized(codec: Codec, schema: Option<&Layout>) -> Result<Layout, String> {
+        let (name, fields) = match (codec.needs_fields(), schema) {
+            (false, None) => (codec.to_string(), Vec::new()),
+            (false, Some(_)) => {
+                return Err(format!(
+                    "the {codec} codec is self-describing and takes no schema"
+                ))
+            }
+            (true, None) => {
+                return Err(format!(
+                "the {codec} codec is not self-describing, give the layout of its fields as schema"
+            ))
+            }
+            (true, Some(schema)) => match &schema.format {
+                Format::Fields { fields, .. } | Format::Codec { fields, .. }
+                    if !fields.is_empty() =>
+                {
+                    (format!("{codec} {}", schema.name), fields.clone())
+                }
+                _ => return Err(format!("layout '{}' has no fields", schema.name)),
+            },
+        };
+        Ok(Layout {
+            name,
+            format: Format::Codec { codec, fields },
+            fixed_width: None,
+            order: Order::Bytes,
+        })
+    }
+
+    /// Byte width of every encoded value, `None` for variable width types.
+    pub fn fixed_width(&self) -> Option<usize> {
+        self.fixed_width
+    }
+
+    /// Decodes a stored value into a `Struct` (field layouts) or `Json`
+    /// (codecs).
+    pub fn decode(&self, data: &[u8]) -> Result<DynValue, String> {
+        let (endian, fields) = match &self.format {
+            Format::Codec { codec, fields } => {
+                return codecs::decode(*codec, fields, data).map(DynValue::Json)
+            }
+            Format::Fields { endian, fields } => (*endian, fields),
+        };
+        let mut at = 0;
+        let mut values = Vec::with_capacity(fields.len());
+        for field in fields {
+            let value = match &field.kind {
+                FieldKind::Scalar(tree) => {
+                    let width = tree.fixed_width().unwrap_or(0);
+                    tree.decode(&endian.to_le(take(data, &mut at, width)?.to_vec()))?
+                }
+                FieldKind::Str(prefix) | FieldKind::Bytes(prefix) => {
+                    let len = match prefix {
+                        LenPrefix::Rest => data.len().saturating_sub(at),
+                        prefix => {
+                            let mut le =
+                                endian.to_le(take(data, &mut at, prefix.width())?.to_vec());
+                            le.resize(8, 0);
+                            let len = u64::from_le_bytes(le.try_into().expect("8 bytes"));
+                            usize::try_from(len)
+                                .map_err(|_| format!("length {len} is too large"))?
+                        }
+                    };
+                    let bytes = take(data, &mut at, len)?.to_vec();
+                    match field.kind {
+                        FieldKind::Str(_) => DynValue::Str(
+                            String::from_utf8(bytes)
+                                .map_err(|e| format!("field '{}' is not UTF-8: {e}", field.name))?,
+                        ),
+                        _ => DynValue::Bytes(bytes),
+                    }
+                }
+            };
+            values.push((field.name.clone(), value));
+        }
+        if at != data.len() {
+            return Err(format!(
+                "{} bytes left after the last field",
+                data.len() - at
+            ));
+        }
+        Ok(DynValue::Struct(values))
+    }
+
+    /// Encodes a value in this layout, the inverse of `decode`.
+    pub fn encode(&self, value: &DynValue) -> Result<Vec<u8>, String> {
+        let mismatch = || format!("expected a {}, got a {}", self.name, value.kind());
+        let (endian, fields, values) = match (&self.format, value) {
+            (Format::Codec { codec, fields }, DynValue::Json(json)) => {
+                return codecs::encode(*codec, fields, json)
+            }
+            (Format::Fields { endian, fields }, DynValue::Struct(values)) => {
+                (*endian, fields, values)
+            }
+            _ => return Err(mismatch()),
+        };
+        if values.len() != fields.len()
+            || fields
+                .iter()
+                .zip(values)
+                .any(|(field, (name, _))| field.name != *name)
+        {
+            return Err(mismatch());
+        }
+        let mut out = Vec::new();
+        for (field, (_, value)) in fields.iter().zip(values) {
+            match (&field.kind, value) {
+                (FieldKind::Scalar(tree), value) => out.extend(endian.to_le(tree.encode(value)?)),
+                (FieldKind::Str(prefix), DynValue::Str(s)) => {
+                    write_len(*prefix, endian, s.len(), &mut out)?;
+                    out.extend(s.as_bytes());
+                }
+                (FieldKind::Bytes(prefix), DynValue::Bytes(b)) => {
+                    write_len(*prefix, endian, b.len(), &mut out)?;
+                    out.extend(b);
+                }
+                (_, value) => return Err(format!("field '{}' got a {}", field.name, value.kind())),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Orders two encoded keys by the layout's `order`.
+    pub fn compare(&self, a: &[u8], b: &[u8]) -> Result<Ordering, String> {
+        if self.order == Order::Bytes {
+            return Ok(a.cmp(b));
+        }
+        match (self.decode(a)?, self.decode(b)?) {
+            (DynValue::Struct(a), DynValue::Struct(b)) => Ok(a
+                .iter()
+                .zip(&b)
+                .map(|((_, x), (_, y))| match (x, y) {
+                    (DynValue::Str(x), DynValue::Str(y)) => x.cmp(y),
+                    (DynValue::Bytes(x), DynValue::Bytes(y)) => x.cmp(y),
+                    (x, y) => scalar_cmp(x, y),
+                })
+                .find(|order| order.is_ne())
+                .unwrap_or(Ordering::Equal)),
+            _ => Err("only field layouts compare by fields".to_string()),
+        }
+    }
+
+    /// Parses the text `Display` renders for a value of this layout:
+    /// `{id: 1, name: "x"}` for field layouts, JSON for codecs.
+    pub fn parse_text(&self, text: &str) -> Result<DynValue, ParseError> {
+        let fields = match &self.format {
+            Format::Codec { .. } => return parse_json(text).map(DynValue::Json),
+            Format::Fields { fields, .. } => fields,
+        };
+        let t = text.trim();
+        let at = lead(text);
+        let body = t
+            .strip_prefix('{')
+            .and_then(|x| x.strip_suffix('}'))
+            .ok_or_else(|| {
+                ParseError::new(at, format!("expected {{field: value, ..}}, got '{t}'"))
+            })?;
+        let mut given: Vec<Option<DynValue>> = vec![None; fields.len()];
+        if !body.trim().is_empty() {
+            for (offset, part) in split_top_level(body).map_err(|e| e.shifted(at + 1))? {
+                let part_at = at + 1 + offset;
+                let (name, value) = part.split_once(':').ok_or_else(|| {
+                    ParseError::new(part_at + lead(&part), "expected 'field: value'")
+                })?;
+                let index = fields
+                    .iter()
+                    .position(|field| field.name == name.trim())
+                    .ok_or_else(|| {
+                        ParseError::new(
+                            part_at + lead(name),
+                            format!("{} has no field '{}'", self.name, name.trim()),
+                        )
+                    })?;
+                let value_at = part_at + name.len() + 1;
+                let value = fields[index]
+                    .parse_value(value)
+                    .map_err(|e| e.shifted(value_at))?;
+                if given[index].replace(value).is_some() {
+                    return Err(ParseError::new(
+                        part_at + lead(name),
+                        format!("field '{}' is given twice", fields[index].name),
+                    ));
+                }
+            }
+        }
+        fields
+            .iter()
+            .zip(given)
+            .map(|(field, value)| {
+                value
+                    .map(|value| (field.name.clone(), value))
+                    .ok_or_else(|| ParseError::new(at, format!("missing field '{}'", field.name)))
+            })
+            .collect::<Result<_, _>>()
+            .map(DynValue::Struct)
+    }
+}
+
+impl Field {
+    fn parse_value(&self, text: &str) -> Result<DynValue, ParseError> {
+        match &self.kind {
+            FieldKind::Scalar(tree) => tree.parse_elem(text),
+            FieldKind::Str(_) => TypeTree::Str.parse_elem(text),
+            FieldKind::Bytes(_) => TypeTree::Bytes.parse_elem(text),
+        }
+    }
+}
+
+fn write_len(
+    prefix: LenPrefix,
+    endian: Endian,
+    len: usize,
+    out: &mut Vec<u8>,
+) -> Result<(), String> {
+    if prefix == LenPrefix::Rest {
+        return Ok(());
+    }
+    let width = prefix.width();
+    let le = (len as u64).to_le_bytes();
+    if le[width..].iter().any(|&b| b != 0) {
+        return Err(format!("length {len} does not fit its {width} byte prefix"));
+    }
+    out.extend(endian.to_le(le[..width].to_vec()));
+    Ok(())
+}
+
+/// Parses JSON text, pointing errors at the byte where serde_json stopped.
+fn parse_json(text: &str) -> Result<Json, ParseError> {
+    serde_json::from_str(text).map_err(|e| {
+        let line_start: usize = text
+            .split_inclusive('\n')
+            .take(e.line().saturating_sub(1))
+            .map(str::len)
+            .sum();
+        ParseError::new(line_start + e.column().saturating_sub(1), e.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> Layout {
+        let layouts = Layouts::parse(
+            r#"{"test::Record": {"endian": "big", "fields": [
+                {"name": "id", "type": "u32"},
+                {"name": "name", "type": "str", "len": "u8"},
+                {"name": "tail", "type": "bytes", "len": "rest"}
+            ]}}"#,
+        )
+        .unwrap();
+        (**layouts.get("test::Record").unwrap()).clone()
+    }
+
+    #[test]
+    fn fields_roundtrip_in_declared_layout() {
+        let layout = record();
+        assert_eq!(layout.fixed_width(), None);
+        let data = [0, 0, 1, 2, 2, b'h', b'i', 0xff];
+        let value = layout.decode(&data).unwrap();
+        assert_eq!(value.to_string(), "{id: 258, name: \"hi\", tail: [255]}");
+        assert_eq!(layout.encode(&value).unwrap(), data);
+        let parsed = layout
+            .parse_text("{tail: [], name: \"x, y\", id: 1}")
+            .unwrap();
+        assert_eq!(parsed.to_string(), "{id: 1, name: \"x, y\", tail: []}");
+        assert_eq!(
+            layout.encode(&parsed).unwrap(),
+            [0, 0, 0, 1, 4, b'x', b',', b' ', b'y']
+        );
+        assert!(layout.decode(&[0, 0, 1]).is_err());
+        assert!(layout.decode(&[0, 0, 0, 1, 5, b'x']).is_err());
+    }
+
+    #[test]
+    fn parse_errors_name_the_field() {
+        let layout = record();
+        let err = layout.parse_text("{id: 1, name: \"a\"}").unwrap_err();
+        assert_eq!(err.message, "missing field 'tail'");
+        let err = layout
+            .parse_text("{id: x, name: \"a\", tail: []}")
+            .unwrap_err();
+        assert_eq!(err.position, 5);
+        let err = layout.parse_text("{idx: 1}").unwrap_err();
+        assert_eq!(err.message, "test::Record has no field 'idx'");
+    }
+
+    #[test]
+    fn fixed_width_and_order() {
+        let layouts = Layouts::parse(
+            r#"{"test::Pair": {"fields": [
+                {"name": "a", "type": "i16"}, {"name": "b", "type": "u8"}
+            ]}}"#,
+        )
+        .unwrap();
+        let pair = layouts.get("test::Pair").unwrap();
+        assert_eq!(pair.fixed_width(), Some(3));
+        // -1 sorts before 1 by fields, after it bytewise
+        let (neg, pos) = ([0xff, 0xff, 0], [1, 0, 0]);
+        assert_eq!(pair.compare(&neg, &pos).unwrap(), Ordering::Less);
+    }
+
+    #[test]
+    fn json_codec() {
+        let layouts = Layouts::parse(r#"{"test::Event": {"codec": "json"}}"#).unwrap();
+        let event = layouts.get("test::Event").unwrap();
+        let value = event.decode(br#"{"kind": "start"}"#).unwrap();
+        assert_eq!(value.to_string(), "{\n  \"kind\": \"start\"\n}");
+        assert_eq!(event.encode(&value).unwrap(), br#"{"kind":"start"}"#);
+        assert_eq!(event.parse_text("{\"a\": }").unwrap_err().position, 6);
+    }
+
+    #[test]
+    fn bad_layouts_are_rejected() {
+        for (text, message) in [
+            (r#"{"u64": {"codec": "json"}}"#, "built-in"),
+            (r#"{"x::A": {}}"#, "expected 'fields' or 'codec'"),
+            (r#"{"x::A": {"codec": "xml"}}"#, "unknown codec"),
+            (
+                r#"{"x::A": {"fields": [{"name": "a", "type": "u7"}]}}"#,
+                "unknown type",
+            ),
+            (
+                r#"{"x::A": {"fields": [{"name": "a", "type": "str", "len": "rest"}, {"name": "b", "type": "u8"}]}}"#,
+                "only the last field",
+            ),
+            (
+                r#"{"x::A": {"fixed_width": 4, "fields": [{"name": "a", "type": "u8"}]}}"#,
+                "'fixed_width' is 4",
+            ),
+        ] {
+            let err = Layouts::parse(text).unwrap_err();
+            assert!(err.contains(message), "{text}: {err}");
+        }
+    }
+}
@@ -0,0 +1,653 @@
+use crate::dynread::{bytes_to_hex, lead, ParseError};
+use crate::dynvalue::DynValue;
+use crate::typecodec::TypeTree;
+use std::fmt::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Another way to show the cells of a key or value column, set with the
+/// `lens` command. A lens only changes the text: every rendering parses
+/// back to the same value, and text in the plain form is still accepted,
+/// so `put` and `edit` take both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lens {
+    /// Integers as `0x` hex.
+    Hex,
+    /// Integer seconds since the Unix epoch as ISO-8601 UTC, ex:
+    /// `2026-10-18T00:00:00Z`.
+    EpochSecs,
+    /// Like `EpochSecs` for milliseconds, with 3 fractional digits.
+    EpochMillis,
+    /// Like `EpochSecs` for nanoseconds, with 9 fractional digits.
+    EpochNanos,
+    /// Integer byte counts followed by a binary unit, ex: `1536 B (1.5 KiB)`.
+    ByteSize,
+    /// `u32` and `u128` integers and 4 or 16 bytes as IP addresses.
+    Ip,
+    /// Bytes as quoted text when they are valid UTF-8.
+    Utf8,
+    /// `u128` integers and 16 bytes as UUIDs.
+    Uuid,
+    /// Bytes as an `xxd` style hex dump.
+    Xxd,
+    /// Strings quoted, with control characters escaped.
+    Escape,
+}
+
+/// The lenses of a table's key and value columns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lenses {
+    pub key: Option<Lens>,
+    pub value: Option<Lens>,
+}
+
+impl Lens {
+    pub const NAMES: &'static str =
+        "hex, epoch-s, epoch-ms, epoch-ns, bytesize, ip, utf8, uuid, xxd, escape";
+
+    pub fn parse(name: &str) -> Option<Lens> {
+        let lens = match name {
+            "hex" => Lens::Hex,
+            "epoch-s" => Lens::EpochSecs,
+            "epoch-ms" => Lens::EpochMillis,
+            "epoch-ns" => Lens::EpochNanos,
+            "bytesize" => Lens::ByteSize,
+            "ip" => Lens::Ip,
+            "utf8" => Lens::Utf8,
+            "uuid" => Lens::Uuid,
+            "xxd" => Lens::Xxd,
+            "escape" => Lens::Escape,
+            _ => return None,
+        };
+        Some(lens)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lens::Hex => "hex",
+            Lens::EpochSecs => "epoch-s",
+            Lens::EpochMillis => "epoch-ms",
+            Lens::EpochNanos => "epoch-ns",
+            Lens::ByteSize => "bytesize",
+            Lens::Ip => "ip",
+            Lens::Utf8 => "utf8",
+            Lens::Uuid => "uuid",
+            Lens::Xxd => "xxd",
+            Lens::Escape => "escape",
+        }
+    }
+
+    /// Whether the lens can show values of `tree`.
+    pub fn fits(self, tree: &TypeTree) -> bool {
+        let int = matches!(
+            tree,
+            TypeTree::U8
+                | TypeTree::U16
+                | TypeTree::U32
+                | TypeTree::U64
+                | TypeTree::U128
+                | TypeTree::I8
+                | TypeTree::I16
+                | TypeTree::I32
+                | TypeTree::I64
+                | TypeTree::I128
+        );
+        // byte strings of any length, or of one of `widths`
+        let bytes = |widths: &[usize]| match tree {
+            TypeTree::Bytes => true,
+            TypeTree::Vec(elem) => **elem == TypeTree::U8,
+            TypeTree::Array(elem, n) => {
+                **elem == TypeTree::U8 && (widths.is_empty() || widths.contains(n))
+            }
+            _ => false,
+        };
+        match self {
+            Lens::Hex | Lens::EpochSecs | Lens::EpochMillis | Lens::EpochNanos | Lens::ByteSize => {
+                int
+            }
+            Lens::Ip => matches!(tree, TypeTree::U32 | TypeTree::U128) || bytes(&[4, 16]),
+            Lens::Uuid => *tree == TypeTree::U128 || bytes(&[16]),
+            Lens::Utf8 | Lens::Xxd => bytes(&[]),
+            Lens::Escape => matches!(tree, TypeTree::Str | TypeTree::String),
+        }
+    }
+
+    /// Renders `value`, or its plain `Display` form where the lens does not
+    /// apply, ex: bytes that are not UTF-8 or a time past year 9999.
+    pub fn render(self, value: &DynValue) -> String {
+        self.try_render(value).unwrap_or_else(|| value.to_string())
+    }
+
+    fn try_render(self, value: &DynValue) -> Option<String> {
+        match self {
+            Lens::Hex => match value {
+                DynValue::U128(v) => Some(format!("{v:#x}")),
+                value => {
+                    let n = int_of(value)?;
+                    Some(match n < 0 {
+                        true => format!("-{:#x}", n.unsigned_abs()),
+                        false => format!("{n:#x}"),
+                    })
+                }
+            },
+            Lens::EpochSecs | Lens::EpochMillis | Lens::EpochNanos => {
+                let (per_second, digits) = self.epoch_unit();
+                iso8601(int_of(value)?, per_second, digits)
+            }
+            Lens::ByteSize => Some(byte_size(int_of(value)?)),
+            Lens::Ip => match value {
+                DynValue::U32(v) => Some(Ipv4Addr::from(*v).to_string()),
+                DynValue::U128(v) => Some(Ipv6Addr::from(*v).to_string()),
+                value => {
+                    let bytes = bytes_of(value)?;
+                    match bytes.len() {
+                        4 => Some(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?).to_string()),
+                        16 => Some(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?).to_string()),
+                        _ => None,
+                    }
+                }
+            },
+            Lens::Utf8 => String::from_utf8(bytes_of(value)?)
+                .ok()
+                .map(|text| format!("{text:?}")),
+            Lens::Uuid => {
+                let bytes: [u8; 16] = match value {
+                    DynValue::U128(v) => v.to_be_bytes(),
+                    value => bytes_of(value)?.try_into().ok()?,
+                };
+                Some(uuid(&bytes))
+            }
+            Lens::Xxd => Some(bytes_of(value)?)
+                .filter(|bytes| !bytes.is_empty())
+                .map(|bytes| xxd(&bytes)),
+            Lens::Escape => match value {
+                DynValue::Str(s) => Some(format!("{s:?}")),
+                _ => None,
+            },
+        }
+    }
+
+    /// Parses cell text given in the lens's form, or failing that in the
+    /// plain form `TypeTree::parse_cell` takes.
+    pub fn parse_cell(self, tree: &TypeTree, text: &str) -> Result<DynValue, ParseError> {
+        match self.plain_text(tree, text) {
+            Ok(plain) => tree
+                .parse_cell(&plain)
+                .map_err(|e| ParseError::new(lead(text), e.message)),
+            Err(message) => tree
+                .parse_cell(text)
+                .map_err(|_| ParseError::new(lead(text), message)),
+        }
+    }
+
+    /// Turns text in the lens's form into the plain cell text of `tree`.
+    fn plain_text(self, tree: &TypeTree, text: &str) -> Result<String, String> {
+        let t = text.trim();
+        match self {
+            Lens::Hex => {
+                let (negative, rest) = match t.strip_prefix('-') {
+                    Some(rest) => (true, rest),
+                    None => (false, t),
+                };
+                let n = rest
+                    .strip_prefix("0x")
+                    .and_then(|hex| u128::from_str_radix(hex, 16).ok())
+                    .ok_or(format!("expected 0x hex, got '{t}'"))?;
+                Ok(if negative {
+                    format!("-{n}")
+                } else {
+                    n.to_string()
+                })
+            }
+            Lens::EpochSecs | Lens::EpochMillis | Lens::EpochNanos => {
+                let (per_second, digits) = self.epoch_unit();
+                parse_iso8601(t, per_second, digits).map(|n| n.to_string())
+            }
+            Lens::ByteSize => parse_byte_size(t).map(|n| n.to_string()),
+            Lens::Ip => {
+                let ip: IpAddr = t
+                    .parse()
+                    .map_err(|_| format!("expected an IP address, got '{t}'"))?;
+                match (tree, ip) {
+                    (TypeTree::U32, IpAddr::V4(ip)) => Ok(u32::from(ip).to_string()),
+                    (TypeTree::U128, IpAddr::V6(ip)) => Ok(u128::from(ip).to_string()),
+                    (TypeTree::U32 | TypeTree::U128, _) => {
+                        Err(format!("'{t}' is the wrong IP version for {tree}"))
+                    }
+                    (_, IpAddr::V4(ip)) => Ok(bytes_text(tree, &ip.octets())),
+                    (_, IpAddr::V6(ip)) => Ok(bytes_text(tree, &ip.octets())),
+                }
+            }
+            // unquoted text is taken as is, unless it is in the plain form
+            Lens::Utf8 if !t.starts_with('"') => match t.starts_with("0x") || t.starts_with('[') {
+                true => Err(format!("expected text like \"abc\", got '{t}'")),
+                false => Ok(bytes_text(tree, text.as_bytes())),
+            },
+            Lens::Utf8 | Lens::Escape => {
+                if !t.starts_with('"') {
+                    return Err(format!("expected quoted text like \"abc\", got '{t}'"));
+                }
+                let text = match TypeTree::Str.parse_elem(text) {
+                    Ok(DynValue::Str(text)) => text,
+                    Ok(_) => unreachable!("strings parse as Str"),
+                    Err(e) => return Err(e.message),
+                };
+                Ok(match self {
+                    Lens::Utf8 => bytes_text(tree, text.as_bytes()),
+                    _ => text,
+                })
+            }
+            Lens::Uuid => {
+                let hex: String = t.chars().filter(|&c| c != '-').collect();
+                let n = Some(hex)
+                    .filter(|hex| hex.len() == 32)
+                    .and_then(|hex| u128::from_str_radix(&hex, 16).ok())
+                    .ok_or(format!("expected a UUID, got '{t}'"))?;
+                Ok(match tree {
+                    TypeTree::U128 => n.to_string(),
+                    tree => bytes_text(tree, &n.to_be_bytes()),
+                })
+            }
+            Lens::Xxd => parse_xxd(text).map(|bytes| bytes_text(tree, &bytes)),
+        }
+    }
+
+    fn epoch_unit(self) -> (i128, usize) {
+        match self {
+            Lens::EpochMillis => (1_000, 3),
+            Lens::EpochNanos => (1_000_000_000, 9),
+            _ => (1, 0),
+        }
+    }
+}
+
+impl fmt::Display for Lens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn int_of(value: &DynValue) -> Option<i128> {
+    match *value {
+        DynValue::U8(v) => Some(v.into()),
+        DynValue::U16(v) => Some(v.into()),
+        DynValue::U32(v) => Some(v.into()),
+        DynValue::U64(v) => Some(v.into()),
+        DynValue::U128(v) => i128::try_from(v).ok(),
+        DynValue::I8(v) => Some(v.into()),
+        DynValue::I16(v) => Some(v.into()),
+        DynValue::I32(v) => Some(v.into()),
+        DynValue::I64(v) => Some(v.into()),
+        DynValue::I128(v) => Some(v),
+        _ => None,
+    }
+}
+
+fn bytes_of(value: &DynValue) -> Option<Vec<u8>> {
+    match value {
+        DynValue::Bytes(bytes) => Some(bytes.clone()),
+        DynValue::List(items) => items
+            .iter()
+            .map(|item| match item {
+                DynValue::U8(b) => Some(*b),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+/// Plain cell text of `bytes` for a byte string type: `0x` hex for `&[u8]`,
+/// a `[1, 2]` list for arrays and `Vec<u8>`.
+fn bytes_text(tree: &TypeTree, bytes: &[u8]) -> String {
+    match tree {
+        TypeTree::Bytes => bytes_to_hex(bytes),
+        _ => format!("{bytes:?}"),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Times
+// ---------------------------------------------------------------------------
+
+/// Year, month and day of a day count since 1970-01-01, proleptic
+/// Gregorian (Howard Hinnant's `civil_from_days`).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// `n` units of `1 / per_second` seconds since the epoch, with `digits`
+/// fractional digits; `None` outside years 0 to 9999.
+fn iso8601(n: i128, per_second: i128, digits: usize) -> Option<String> {
+    let secs = n.div_euclid(per_second);
+    let fraction = n.rem_euclid(per_second);
+    let days = i64::try_from(secs.div_euclid(86_400)).ok()?;
+    let time = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    if !(0..=9999).contains(&year) {
+        return None;
+    }
+    let mut out = format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    );
+    if digits > 0 {
+        let _ = write!(out, ".{fraction:0digits$}");
+    }
+    out.push('Z');
+    Some(out)
+}
+
+fn parse_iso8601(t: &str, per_second: i128, digits: usize) -> Result<i128, String> {
+    let bad = || format!("expected a UTC time like 2026-10-18T00:00:00Z, got '{t}'");
+    let number = |part: Option<&str>, len: usize| -> Result<i64, String> {
+        part.filter(|part| part.len() == len && part.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|part| part.parse().ok())
+            .ok_or_else(bad)
+    };
+    let (date, time) = t
+        .strip_suffix('Z')
+        .and_then(|body| body.split_once('T'))
+        .ok_or_else(bad)?;
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (time, None),
+    };
+    let mut date = date.split('-');
+    let (year, month, day) = (
+        number(date.next(), 4)?,
+        number(date.next(), 2)?,
+        number(date.next(), 2)?,
+    );
+    let mut time = time.split(':');
+    let (hour, minute, second) = (
+        number(time.next(), 2)?,
+        number(time.next(), 2)?,
+        number(time.next(), 2)?,
+    );
+    if date.next().is_some() || time.next().is_some() {
+        return Err(bad());
+    }
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return Err(format!("'{t}' is not a valid date and time"));
+    }
+    let fraction = match fraction {
+        None => 0,
+        Some(f) if (1..=digits).contains(&f.len()) && f.bytes().all(|b| b.is_ascii_digit()) => {
+            format!("{f:0<digits$}")
+                .parse::<i128>()
+                .map_err(|_| bad())?
+        }
+        Some(_) if digits == 0 => return Err(format!("'{t}': this column stores whole seconds")),
+        Some(_) => return Err(format!("'{t}': at most {digits} fractional digits")),
+    };
+    let secs = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second;
+    Ok(i128::from(secs) * per_second + fraction)
+}
+
+// ---------------------------------------------------------------------------
+// Sizes, UUIDs, dumps
+// ---------------------------------------------------------------------------
+
+const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// The exact count, followed by the largest binary unit it reaches, so the
+/// text parses back to the same count.
+fn byte_size(n: i128) -> String {
+    let unit = (1..UNITS.len())
+        .rev()
+        .find(|&k| n.unsigned_abs() >= 1u128 << (10 * k));
+    match unit {
+        None => format!("{n} B"),
+        Some(k) => {
+            let scaled = format!("{:.2}", n as f64 / (1u128 << (10 * k)) as f64);
+            let scaled = scaled.trim_end_matches('0').trim_end_matches('.');
+            format!("{n} B ({scaled} {})", UNITS[k])
+        }
+    }
+}
+
+/// Parses `1536`, `1536 B`, `1536 B (1.5 KiB)` or an exact `1.5 KiB`.
+fn parse_byte_size(t: &str) -> Result<i128, String> {
+    let bad = || format!("expected a size like 1536 B or 1.5 KiB, got '{t}'");
+    let text = match t.find(" (") {
+        Some(end) if t.ends_with(')') => &t[..end],
+        _ => t,
+    };
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(text.len());
+    let (number, unit) = (&text[..split], text[split..].trim());
+    let k = match unit {
+        "" => 0,
+        unit => UNITS
+            .iter()
+            .position(|u| u.eq_ignore_ascii_case(unit))
+            .ok_or_else(bad)?,
+    };
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let mantissa: i128 = format!("{whole}{fraction}").parse().map_err(|_| bad())?;
+    let scale = 10i128.checked_pow(fraction.len() as u32).ok_or_else(bad)?;
+    let bytes = mantissa.checked_mul(1 << (10 * k)).ok_or_else(bad)?;
+    if bytes % scale != 0 {
+        return Err(format!("'{t}' is not a whole number of bytes"));
+    }
+    Ok(bytes / scale)
+}
+
+fn uuid(bytes: &[u8; 16]) -> String {
+    let hex = bytes_to_hex(bytes);
+    let hex = &hex[2..];
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Lines of `offset: hex  ascii` over 16 bytes each, like `xxd`.
+fn xxd(bytes: &[u8]) -> String {
+    let lines: Vec<String> = bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut hex = String::new();
+            for (j, b) in chunk.iter().enumerate() {
+                if j > 0 && j.is_multiple_of(2) {
+                    hex.push(' ');
+                }
+                let _ = write!(hex, "{b:02x}");
+            }
+            let ascii: String = chunk
+                .iter()
+                .map(|&c| {
+                    if c.is_ascii_graphic() || c == b' ' {
+                        c as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:08x}: {hex:<39}  {ascii}", i * 16)
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Reads the bytes back from an `xxd` dump; the ascii column is ignored.
+fn parse_xxd(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    for (n, line) in text.lines().map(str::trim).enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let (offset, rest) = line
+            .split_once(": ")
+            .ok_or(format!("line {}: expected 'offset: hex  ascii'", n + 1))?;
+        let offset = usize::from_str_radix(offset, 16)
+            .map_err(|_| format!("line {}: invalid offset '{offset}'", n + 1))?;
+        if offset != out.len() {
+            return Err(format!(
+                "line {}: offset {offset:08x}, expected {:08x}",
+                n + 1,
+                out.len()
+            ));
+        }
+        let hex: String = rest
+            .split("  ")
+            .next()
+            .unwrap_or_default()
+            .split(' ')
+            .collect();
+        if !hex.len().is_multiple_of(2) {
+            return Err(format!("line {}: odd number of hex digits", n + 1));
+        }
+        for i in (0..hex.len()).step_by(2) {
+            let pair = hex.get(i..i + 2).unwrap_or_default();
+            out.push(
+                u8::from_str_radix(pair, 16)
+                    .map_err(|_| format!("line {}: invalid hex byte '{pair}'", n + 1))?,
+            );
+        }
+    }
+    if out.is_empty() {
+        return Err(format!("expected an xxd dump, got '{}'", text.trim()));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders `value` in `type_name`'s column and parses it back.
+    fn roundtrip(lens: Lens, type_name: &str, value: DynValue) -> String {
+        let tree = TypeTree::parse(type_name).unwrap();
+        assert!(lens.fits(&tree), "{lens} on {type_name}");
+        let text = lens.render(&value);
+        assert_eq!(lens.parse_cell(&tree, &text).unwrap(), value, "{text}");
+        text
+    }
+
+    #[test]
+    fn integer_lenses() {
+        assert_eq!(roundtrip(Lens::Hex, "u32", DynValue::U32(255)), "0xff");
+        assert_eq!(roundtrip(Lens::Hex, "i64", DynValue::I64(-16)), "-0x10");
+        assert_eq!(
+            roundtrip(Lens::EpochSecs, "u64", DynValue::U64(1_792_281_600)),
+            "2026-10-18T00:00:00Z"
+        );
+        assert_eq!(
+            roundtrip(Lens::EpochMillis, "i64", DynValue::I64(-1)),
+            "1969-12-31T23:59:59.999Z"
+        );
+        assert_eq!(
+            roundtrip(
+                Lens::EpochNanos,
+                "u64",
+                DynValue::U64(951_782_400_000_000_005)
+            ),
+            "2000-02-29T00:00:00.000000005Z"
+        );
+        assert_eq!(
+            roundtrip(Lens::ByteSize, "u64", DynValue::U64(1536)),
+            "1536 B (1.5 KiB)"
+        );
+        assert_eq!(roundtrip(Lens::ByteSize, "u16", DynValue::U16(12)), "12 B");
+        assert_eq!(
+            roundtrip(Lens::Ip, "u32", DynValue::U32(0x7f00_0001)),
+            "127.0.0.1"
+        );
+
+        let u64s = TypeTree::U64;
+        let at = |text| Lens::EpochMillis.parse_cell(&u64s, text);
+        assert_eq!(at("1970-01-01T00:00:01.5Z").unwrap(), DynValue::U64(1500));
+        assert!(at("2026-02-30T00:00:00Z").is_err());
+        assert!(at("1970-01-01T00:00:00.0001Z").is_err());
+        // the plain form is still accepted
+        assert_eq!(at("42").unwrap(), DynValue::U64(42));
+        let size = |text| Lens::ByteSize.parse_cell(&u64s, text);
+        assert_eq!(size("2 MiB").unwrap(), DynValue::U64(2 << 20));
+        assert!(size("1.0001 KiB").is_err());
+        // out of range for the column
+        let err = Lens::Hex.parse_cell(&TypeTree::U8, "0x100").unwrap_err();
+        assert!(err.message.contains("expected u8"), "{}", err.message);
+    }
+
+    #[test]
+    fn byte_and_string_lenses() {
+        let bytes = |b: &[u8]| DynValue::Bytes(b.to_vec());
+        assert_eq!(roundtrip(Lens::Utf8, "&[u8]", bytes(b"a\tb")), "\"a\\tb\"");
+        assert_eq!(roundtrip(Lens::Utf8, "&[u8]", bytes(&[0xff])), "0xff");
+        let text = Lens::Utf8.parse_cell(&TypeTree::Bytes, "plain words");
+        assert_eq!(text.unwrap(), bytes(b"plain words"));
+        assert_eq!(
+            roundtrip(
+                Lens::Ip,
+                "[u8;4]",
+                DynValue::List(vec![DynValue::U8(10); 4])
+            ),
+            "10.10.10.10"
+        );
+        assert_eq!(
+            roundtrip(Lens::Uuid, "&[u8]", bytes(&[0xab; 16])),
+            "abababab-abab-abab-abab-abababababab"
+        );
+        assert_eq!(
+            roundtrip(Lens::Uuid, "u128", DynValue::U128(1)),
+            "00000000-0000-0000-0000-000000000001"
+        );
+        let dump = roundtrip(Lens::Xxd, "Vec<u8>", bytes_list(b"hello, redb world!"));
+        assert_eq!(
+            dump,
+            "00000000: 6865 6c6c 6f2c 2072 6564 6220 776f 726c  hello, redb worl\n\
+             00000010: 6421                                     d!"
+        );
+        assert_eq!(
+            roundtrip(Lens::Escape, "&str", DynValue::Str("a\nb\u{1}".into())),
+            "\"a\\nb\\u{1}\""
+        );
+        assert!(!Lens::Uuid.fits(&TypeTree::parse("[u8;4]").unwrap()));
+        assert!(!Lens::Hex.fits(&TypeTree::Str));
+    }
+
+    fn bytes_list(bytes: &[u8]) -> DynValue {
+        DynValue::List(bytes.iter().map(|&b| DynValue::U8(b)).collect())
+    }
+}
@@ -1,9 +1,25 @@
+use flags::TableColumn;
 use std::collections::HashMap;
 use std::io::Write;
+pub mod audit;
+pub mod checksum;
+mod codecs;
+pub mod config;
+pub mod diff;
 pub mod dynread;
+pub mod dynvalue;
+pub mod error;
+pub mod fileheader;
 pub mod flags;
+pub mod layout;
+pub mod lens;
 pub mod pretty_print;
 pub mod redbcontrol;
+pub mod typecodec;
+
+pub use dynread::DynTable;
+pub use dynvalue::DynValue;
+pub use error::Error;
 
 macro_rules! write_io {
     ($func_name:ident, $prefix:literal) => {
@@ -25,6 +41,17 @@ pub struct TableMeta {
     pub name: String,
     pub key_type: String,
     pub value_type: String,
+    /// Only filled in when statistics were requested.
+    pub stats: Option<TableStatsMeta>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TableStatsMeta {
+    pub rows: u64,
+    pub stored_bytes: u64,
+    pub metadata_bytes: u64,
+    pub fragmented_bytes: u64,
+    pub tree_height: u32,
 }
 
 #[derive(Debug, Default)]
@@ -32,6 +59,41 @@ pub struct TableInfo {
     pub tables: Vec<TableMeta>,
 }
 
+impl TableInfo {
+    /// Keeps only the tables whose name matches the glob `pattern`.
+    pub fn retain_matching(&mut self, pattern: &str) -> Result<(), String> {
+        let pattern =
+            glob::Pattern::new(pattern).map_err(|e| format!("invalid pattern '{pattern}': {e}"))?;
+        self.tables.retain(|t| pattern.matches(&t.name));
+        Ok(())
+    }
+
+    /// Sorts the tables by `column`. Tables without statistics sort first
+    /// when ordering by a statistics column.
+    pub fn sort_by_column(&mut self, column: TableColumn, descending: bool) {
+        let stat = |t: &TableMeta, f: fn(&TableStatsMeta) -> u64| t.stats.as_ref().map(f);
+        self.tables.sort_by(|a, b| match column {
+            TableColumn::Name => a.name.cmp(&b.name),
+            TableColumn::KeyType => a.key_type.cmp(&b.key_type),
+            TableColumn::ValueType => a.value_type.cmp(&b.value_type),
+            TableColumn::Rows => stat(a, |s| s.rows).cmp(&stat(b, |s| s.rows)),
+            TableColumn::Stored => stat(a, |s| s.stored_bytes).cmp(&stat(b, |s| s.stored_bytes)),
+            TableColumn::Metadata => {
+                stat(a, |s| s.metadata_bytes).cmp(&stat(b, |s| s.metadata_bytes))
+            }
+            TableColumn::Fragmented => {
+                stat(a, |s| s.fragmented_bytes).cmp(&stat(b, |s| s.fragmented_bytes))
+            }
+            TableColumn::Height => {
+                stat(a, |s| s.tree_height as u64).cmp(&stat(b, |s| s.tree_height as u64))
+            }
+        });
+        if descending {
+            self.tables.reverse();
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct KvInfo {
     pub kvdatas: Vec<(String, String)>,
@@ -39,6 +101,76 @@ pub struct KvInfo {
     pub value_type: String,
 }
 
+#[derive(Debug, Default)]
+pub struct ChecksumInfo {
+    pub tables: Vec<(String, String)>,
+    pub overall: String,
+}
+
+#[derive(Debug, Default)]
+pub struct DbInfo {
+    pub path: String,
+    pub file_size: u64,
+    pub tables: usize,
+    pub multimap_tables: usize,
+    /// `None` when the header could not be read.
+    pub last_transaction: Option<u64>,
+    pub page_size: usize,
+    pub tree_height: u32,
+    pub allocated_pages: u64,
+    pub leaf_pages: u64,
+    pub branch_pages: u64,
+    pub stored_bytes: u64,
+    pub metadata_bytes: u64,
+    pub fragmented_bytes: u64,
+}
+
+#[derive(Debug)]
+pub struct CompactReport {
+    pub size_before: u64,
+    pub size_after: u64,
+    pub elapsed: std::time::Duration,
+    /// `false` when redb found nothing to compact.
+    pub compacted: bool,
+}
+
+#[derive(Debug)]
+pub struct BackupReport {
+    pub tables: usize,
+    pub rows: u64,
+    /// Multimap tables, which are not supported yet and were left out.
+    pub skipped: Vec<String>,
+    /// Size of the backup file once written (and compacted, if asked).
+    pub size: u64,
+    pub elapsed: std::time::Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct SalvagedTable {
+    pub name: String,
+    pub key_type: String,
+    pub value_type: String,
+    pub rows: u64,
+    /// Why the table, or part of it, could not be recovered.
+    pub problem: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct SalvageReport {
+    pub tables: Vec<SalvagedTable>,
+}
+
+#[derive(Debug, Default)]
+pub struct DiffInfo {
+    pub tables: Vec<diff::TableDiff>,
+}
+
+/// Changes a command would have made, collected in dry-run mode.
+#[derive(Debug, Default)]
+pub struct PreviewInfo {
+    pub entries: Vec<audit::AuditEntry>,
+}
+
 pub type StrKvData = HashMap<String, String>;
 
 /// Serializes table rows (formatted key/value cells) into a deterministic
@@ -69,6 +201,32 @@ pub fn json_to_rows(json: &str) -> Result<Vec<(String, String)>, String> {
     Ok(rows)
 }
 
+/// Like `rows_to_json`, for tables whose values are JSON documents (a value
+/// decoder is set): values are embedded as JSON instead of strings.
+pub fn rows_to_json_embedded(rows: &[(String, String)]) -> Result<String, String> {
+    let mut map = serde_json::Map::new();
+    for (k, v) in rows {
+        let value =
+            serde_json::from_str(v).unwrap_or_else(|_| serde_json::Value::String(v.clone()));
+        map.insert(k.clone(), value);
+    }
+    serde_json::to_string_pretty(&serde_json::Value::Object(map)).map_err(|e| e.to_string())
+}
+
+/// Parses JSON written by `rows_to_json_embedded` back into rows, each
+/// value as its JSON text.
+pub fn json_to_rows_embedded(json: &str) -> Result<Vec<(String, String)>, String> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("invalid json: {e}"))?;
+    let object = value
+        .as_object()
+        .ok_or_else(|| "edited data must be a json object".to_string())?;
+    Ok(object
+        .iter()
+        .map(|(k, v)| (k.clone(), v.to_string()))
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,30 +1,133 @@
 use clap::Parser;
-use redb::Database;
+use redbcli::audit::Journal;
+use redbcli::checksum;
+use redbcli::config::{Config, ValueDecoder};
+use redbcli::dynread::{self, ColumnQuery};
 use redbcli::flags::{Binflags, Cli};
-use redbcli::pretty_print::PrintTable;
+use redbcli::layout::{Codec, Layouts};
+use redbcli::lens::{Lens, Lenses};
+use redbcli::pretty_print::{human_bytes, KvPager, PrintTable};
 use redbcli::redbcontrol::DealTable;
 use redbcli::{
-    flags::{Commands, InfoCommands},
-    redbcontrol::{CommonDbManager, DealData},
+    flags::{Commands, InfoCommands, SavepointCommands, Switch, TablesArgs},
+    redbcontrol::{CommonDbManager, DealData, UndoRecord},
+};
+use redbcli::{
+    write_io_error, write_io_info, write_io_success, ChecksumInfo, DiffInfo, Error, KvInfo,
+    PreviewInfo, TableInfo, TableMeta, TableStatsMeta,
 };
-use redbcli::{write_io_error, write_io_success, KvInfo, TableInfo, TableMeta};
 use rustyline::error::ReadlineError;
 use rustyline::DefaultEditor;
-use std::io::Write;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::ops::ControlFlow;
 use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
 #[derive(Default)]
 pub struct CliStatus {
     tablename: String,
     filepath: String,
     dbm: CommonDbManager,
+    /// Before-image of the last mutating command, consumed by `undo`.
+    last_undo: Option<UndoRecord>,
+}
+
+/// A failed command. Library errors keep their variant, so the exit status
+/// can tell ex: a missing table from a parse error.
+#[derive(Debug)]
+enum CliError {
+    Lib(Error),
+    Other(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Lib(e) => e.exit_code(),
+            CliError::Other(_) => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Lib(e) => e.fmt(f),
+            CliError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<Error> for CliError {
+    fn from(e: Error) -> Self {
+        CliError::Lib(e)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(e: std::io::Error) -> Self {
+        CliError::Lib(Error::Io(e))
+    }
+}
+
+impl From<clap::Error> for CliError {
+    fn from(e: clap::Error) -> Self {
+        CliError::Other(e.to_string())
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Other(message)
+    }
+}
+
+impl From<&str> for CliError {
+    fn from(message: &str) -> Self {
+        CliError::Other(message.to_string())
+    }
+}
+
+fn print_repair_progress(progress: f64) {
+    let _ = write_io_info(format!("repairing database ... {:.0}%", progress * 100.0));
 }
 
-fn main() -> Result<(), String> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => ExitCode::from(code),
+        Err(err) => {
+            let _ = write_io_error(err.to_string());
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+/// Runs the session and returns the exit status: 0, or when the commands
+/// come from a pipe or file, the code of the last command that failed.
+fn run() -> Result<u8, CliError> {
     let mut clistatus = CliStatus::default();
+    clistatus.dbm.on_repair = Some(print_repair_progress);
     let parse_flags = Binflags::parse();
     if let Some(db_path) = parse_flags.path {
         clistatus.filepath = db_path;
     }
+    clistatus.dbm.dry_run = parse_flags.dry_run;
+    if let Some(audit_path) = parse_flags.audit {
+        let journal = Journal::open(&audit_path)
+            .map_err(|e| format!("cannot open audit journal {audit_path}: {e}"))?;
+        clistatus.dbm.audit = Some(journal);
+    }
+    if let Some(layouts_path) = parse_flags.layouts {
+        Layouts::load(&layouts_path)?.install();
+    }
+    if let Some(config_path) = parse_flags
+        .config
+        .map(PathBuf::from)
+        .or_else(Config::default_path)
+    {
+        clistatus.dbm.config = Config::load(config_path)?;
+    }
     let history_path = PathBuf::from("/tmp/redbcli");
     if !history_path.exists() {
         std::fs::create_dir_all(&history_path).expect("create history dir failed");
@@ -41,37 +144,68 @@ fn main() -> Result<(), String> {
     if rl.load_history(&file_history).is_err() {
         println!("No previous history.");
     }
+    clistatus.dbm.read_only = parse_flags.read_only;
     if !clistatus.filepath.is_empty() {
-        clistatus
-            .dbm
-            .setdbpath(clistatus.filepath.clone())
-            .map_err(|e| e.to_string())?;
+        let path = clistatus.filepath.clone();
+        open_database(&mut clistatus, path)?;
     }
     if !clistatus.tablename.is_empty() {
-        clistatus
-            .dbm
-            .settablename(clistatus.tablename.clone())
-            .map_err(|e| e.to_string())?;
+        clistatus.dbm.settablename(clistatus.tablename.clone())?;
     }
 
+    let mut last_failure = 0;
     loop {
+        let txn = if clistatus.dbm.in_transaction() {
+            " TXN:[open]"
+        } else {
+            ""
+        };
+        let snap = match clistatus.dbm.snapshot_age() {
+            Some(age) => format!(" SNAP:[{}]", format_age(age)),
+            None => String::new(),
+        };
+        let dry = if clistatus.dbm.dry_run {
+            " DRY-RUN"
+        } else {
+            ""
+        };
+        let ro = match &clistatus.dbm.inspected_copy {
+            Some(_) => " RO:[copy]",
+            None if clistatus.dbm.read_only => " RO",
+            None => "",
+        };
         let prompt = format!(
-            "\nDB:[{}] TAB:[{}] \n>> ",
-            clistatus.filepath, clistatus.tablename
+            "\nDB:[{}] TAB:[{}]{}{}{}{} \n>> ",
+            clistatus.filepath, clistatus.tablename, ro, txn, snap, dry
         );
         let readline = rl.readline(&prompt);
 
         match readline {
             Ok(line) => {
                 let _ = rl.add_history_entry(line.as_str());
-                match respond(&line, &mut clistatus) {
+                if let Some(journal) = clistatus.dbm.audit.as_mut() {
+                    journal.set_command(&line);
+                }
+                let undo = clistatus.last_undo.clone();
+                let result = respond(&line, &mut clistatus);
+                if clistatus.dbm.dry_run {
+                    // nothing was written, so there is nothing new to undo
+                    clistatus.last_undo = undo;
+                    let entries = clistatus.dbm.take_preview();
+                    if !entries.is_empty() {
+                        PreviewInfo { entries }.print_data();
+                        write_io_info("dry run, nothing was committed".to_string())?;
+                    }
+                }
+                match result {
                     Ok(quit) => {
                         if quit {
                             break;
                         }
                     }
                     Err(err) => {
-                        write_io_error(err)?;
+                        last_failure = err.exit_code();
+                        write_io_error(err.to_string())?;
                     }
                 }
             }
@@ -89,51 +223,102 @@ fn main() -> Result<(), String> {
             }
         }
     }
+    if clistatus.dbm.in_transaction() {
+        clistatus.dbm.rollback_transaction()?;
+        write_io_info("open transaction rolled back".to_string())?;
+    }
     let _ = rl.save_history(&file_history);
+    if std::io::stdin().is_terminal() {
+        Ok(0)
+    } else {
+        Ok(last_failure)
+    }
+}
+
+/// Formats a snapshot age for the prompt, ex: "42s", "3m05s", "1h02m".
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Switches to the database at `path`. If another process holds its lock,
+/// offers to inspect a temporary copy instead.
+fn open_database(status: &mut CliStatus, path: String) -> Result<(), CliError> {
+    match status.dbm.setdbpath(path.clone()) {
+        Ok(()) => {}
+        Err(Error::Locked(_)) => {
+            write_io_info(format!(
+                "{path} is locked by another process (a running service?), it cannot be opened while the lock is held"
+            ))?;
+            if !confirm("inspect a temporary read-only copy instead?")? {
+                return Err(Error::Locked(path).into());
+            }
+            status.dbm.inspect_copy(&path)?;
+            write_io_info(
+                "inspecting a copy, later changes of the other process are not visible".to_string(),
+            )?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+    status.filepath = path;
+    status.last_undo = None;
     Ok(())
 }
 
-fn respond(line: &str, status: &mut CliStatus) -> Result<bool, String> {
+/// Asks a yes/no question on stdin; anything but "y"/"yes" means no.
+fn confirm(question: &str) -> Result<bool, String> {
+    print!("{question} [y/N] ");
+    std::io::stdout().flush().map_err(|e| e.to_string())?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| e.to_string())?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn respond(line: &str, status: &mut CliStatus) -> Result<bool, CliError> {
     let args = shlex::split(line).ok_or("error: Invalid quoting")?;
-    let cli = Cli::try_parse_from(args).map_err(|e| e.to_string())?;
+    let cli = Cli::try_parse_from(args)?;
+    if status.dbm.is_read_only() && cli.command.is_mutating() {
+        return Err("the database is opened read-only, this command would write to it".into());
+    }
 
     match cli.command {
         Commands::Set { filepath } => {
+            open_database(status, filepath)?;
             write_io_success("set database success!".to_string())?;
-            status.filepath = filepath;
-            status
-                .dbm
-                .setdbpath(status.filepath.clone())
-                .map_err(|e| e.to_string())?;
             Ok(false)
         }
 
         Commands::Use { tablename } => {
             if status.filepath.is_empty() {
-                return Err("you must set file path first !!".to_string());
+                return Err("you must set file path first !!".into());
             }
             status.tablename = tablename.clone();
-            status
-                .dbm
-                .settablename(tablename.clone())
-                .map_err(|e| e.to_string())?;
+            status.dbm.settablename(tablename.clone())?;
             write_io_success(format!("Use table {}", tablename))?;
             Ok(false)
         }
         Commands::Edit => {
             if status.tablename.is_empty() {
-                return Err("you must set table first !!".to_string());
+                return Err("you must set table first !!".into());
             }
-            let (_desc, rows) = status
-                .dbm
-                .get_all_dyn(&status.tablename)
-                .map_err(|e| e.to_string())?;
-            let json_data = redbcli::rows_to_json(&rows)?;
+            let (desc, rows) = status.dbm.get_all_dyn(&status.tablename)?;
+            let image = status.dbm.table_image(&status.tablename)?;
+            // decoded documents are edited as JSON, not as JSON strings
+            let embedded = desc.value_codec.is_some();
+            let json_data = if embedded {
+                redbcli::rows_to_json_embedded(&rows)?
+            } else {
+                redbcli::rows_to_json(&rows)?
+            };
 
-            let mut temp_file = tempfile::NamedTempFile::new().map_err(|e| e.to_string())?;
-            temp_file
-                .write_all(json_data.as_bytes())
-                .map_err(|e| e.to_string())?;
+            let mut temp_file = tempfile::NamedTempFile::new()?;
+            temp_file.write_all(json_data.as_bytes())?;
 
             let temp_path = temp_file
                 .path()
@@ -148,53 +333,74 @@ fn respond(line: &str, status: &mut CliStatus) -> Result<bool, String> {
                 .stdin(std::process::Stdio::inherit())
                 .stdout(std::process::Stdio::inherit())
                 .stderr(std::process::Stdio::inherit())
-                .spawn()
-                .map_err(|e| e.to_string())?;
+                .spawn()?;
 
-            let vim_status = child.wait().map_err(|e| e.to_string())?;
+            let vim_status = child.wait()?;
 
             if !vim_status.success() {
-                return Err("Vim exited with an error".to_string());
+                return Err("Vim exited with an error".into());
             }
 
-            let modified_data = std::fs::read_to_string(temp_path).map_err(|e| e.to_string())?;
+            let modified_data = std::fs::read_to_string(temp_path)?;
             if modified_data == json_data {
                 println!("No changed!");
                 return Ok(false);
             }
-            let new_rows = redbcli::json_to_rows(&modified_data)?;
-            status
-                .dbm
-                .update_all_dyn(&status.tablename, new_rows)
-                .map_err(|e| e.to_string())?;
+            let new_rows = if embedded {
+                redbcli::json_to_rows_embedded(&modified_data)?
+            } else {
+                redbcli::json_to_rows(&modified_data)?
+            };
+            status.dbm.update_all_dyn(&status.tablename, new_rows)?;
+            status.last_undo = Some(image);
             write_io_success("Save data to update the database".to_string())?;
             Ok(false)
         }
 
         Commands::Info(subcmd) => {
-            let sub_cmd = subcmd.command.unwrap_or(InfoCommands::Tables);
+            let sub_cmd = subcmd
+                .command
+                .unwrap_or(InfoCommands::Tables(TablesArgs::default()));
             match sub_cmd {
-                InfoCommands::Tables => {
-                    let result = status.dbm.list_table_types().map_err(|e| e.to_string())?;
+                InfoCommands::Tables(args) => {
+                    let result = status.dbm.list_table_types()?;
+                    let with_stats = args.stats || args.sort.is_some_and(|c| c.needs_stats());
+                    let mut stats: HashMap<String, TableStatsMeta> = if with_stats {
+                        status.dbm.list_table_stats()?.into_iter().collect()
+                    } else {
+                        HashMap::new()
+                    };
                     let tables = result
                         .into_iter()
                         .map(|(name, desc)| TableMeta {
+                            stats: stats.remove(&name),
                             name,
                             key_type: desc.key,
                             value_type: desc.value,
                         })
                         .collect();
-                    TableInfo { tables }.print_data();
+                    let mut info = TableInfo { tables };
+                    if let Some(pattern) = &args.pattern {
+                        info.retain_matching(pattern)?;
+                    }
+                    if let Some(column) = args.sort {
+                        info.sort_by_column(column, args.desc);
+                    }
+                    info.print_data();
+                    Ok(false)
+                }
+                InfoCommands::Db => {
+                    if status.filepath.is_empty() {
+                        return Err("you must set file path first !!".into());
+                    }
+                    status.dbm.db_info()?.print_data();
                     Ok(false)
                 }
                 InfoCommands::Key { key } => {
                     if status.tablename.is_empty() {
-                        return Err("you must use table to select !!".to_string());
+                        return Err("you must use table to select !!".into());
                     }
-                    let result = status
-                        .dbm
-                        .get_by_key(key.clone())
-                        .map_err(|e| e.to_string())?;
+                    let result = status.dbm.get_by_key(key.clone())?;
                     let kvdatas = vec![(key, result)];
                     KvInfo {
                         kvdatas,
@@ -204,41 +410,503 @@ fn respond(line: &str, status: &mut CliStatus) -> Result<bool, String> {
                     .print_data();
                     Ok(false)
                 }
-                InfoCommands::Table { tablename } => {
-                    let (desc, rows) = status
+                InfoCommands::Table { tablename, columns } => {
+                    let filter = columns
+                        .filter
+                        .map(|filter| match filter.split_once('=') {
+                            Some((column, text)) => Ok((column.to_string(), text.to_string())),
+                            None => {
+                                Err(format!("invalid filter '{filter}', expected COLUMN=VALUE"))
+                            }
+                        })
+                        .transpose()?;
+                    let query = ColumnQuery {
+                        expand: columns.expand,
+                        filter,
+                        sort: columns.sort,
+                        descending: columns.desc,
+                    };
+                    let desc = status.dbm.table_type(&tablename)?;
+                    if !desc.is_decodable() {
+                        write_io_info(format!(
+                            "no codec or layout for Table<{}, {}>, showing raw bytes as hex |ascii|",
+                            desc.key, desc.value
+                        ))?;
+                    }
+                    let names = dynread::column_names(&desc, query.expand);
+                    let mut pager = KvPager::new(&desc.key, &desc.value, names);
+                    status
                         .dbm
-                        .get_all_dyn(&tablename)
-                        .map_err(|e| e.to_string())?;
+                        .visit_columns_dyn(&tablename, &desc, &query, &mut |row| {
+                            pager.push(row);
+                            ControlFlow::Continue(())
+                        })?;
+                    pager.finish();
                     status.tablename = tablename.clone();
                     status.dbm.tablename = tablename;
-                    KvInfo {
-                        kvdatas: rows,
-                        key_type: desc.key,
-                        value_type: desc.value,
-                    }
-                    .print_data();
                     Ok(false)
                 }
             }
         }
 
         Commands::Create { tablename } => {
-            let _ = status.dbm.create_table(tablename);
-            write_io_success("create table success".to_string())?;
+            let exists = status.dbm.list_table()?.contains(&tablename);
+            if exists {
+                write_io_info(format!("table {tablename} already exists"))?;
+                return Ok(false);
+            }
+            status.dbm.create_table(tablename.clone())?;
+            status.last_undo = Some(UndoRecord::Table {
+                table: tablename.clone(),
+                before: None,
+            });
+            if status.dbm.dry_run {
+                write_io_info(format!("would create table {tablename}"))?;
+            } else {
+                write_io_success("create table success".to_string())?;
+            }
+            Ok(false)
+        }
+        Commands::Delete { tablename, force } => {
+            if !status.dbm.list_table()?.contains(&tablename) {
+                return Err(Error::TableNotFound(tablename).into());
+            }
+            // capture the table first: an accidental delete must be undoable
+            let image = match status.dbm.table_image(&tablename) {
+                Ok(record) => Some(record),
+                Err(e) if force => {
+                    write_io_info(format!("delete of {tablename} cannot be undone: {e}"))?;
+                    None
+                }
+                Err(e) => {
+                    return Err(Error::Refused(format!(
+                        "delete of {tablename} could not be undone ({e}), use 'delete {tablename} --force' to delete it anyway"
+                    ))
+                    .into())
+                }
+            };
+            status.dbm.delete_table(tablename.clone())?;
+            status.last_undo = image;
+            if status.dbm.dry_run {
+                write_io_info(format!("would delete table {tablename}"))?;
+            } else {
+                write_io_success("delete table success".to_string())?;
+            }
+            Ok(false)
+        }
+        Commands::Put { key, value } => {
+            if status.tablename.is_empty() {
+                return Err("you must use table to select !!".into());
+            }
+            let image = status.dbm.row_image(&status.tablename, &key)?;
+            status.dbm.put_dyn(&status.tablename, key, value)?;
+            status.last_undo = Some(image);
+            write_io_success("put success".to_string())?;
+            Ok(false)
+        }
+        Commands::Del { key, force } => {
+            if status.tablename.is_empty() {
+                return Err("you must use table to select !!".into());
+            }
+            // capture the row first, like 'delete' does the table
+            let image = match status.dbm.row_image(&status.tablename, &key) {
+                Ok(record) => Some(record),
+                Err(Error::Refused(e)) if force => {
+                    write_io_info(format!("del of {key} cannot be undone: {e}"))?;
+                    None
+                }
+                Err(Error::Refused(e)) => {
+                    return Err(Error::Refused(format!(
+                        "del of {key} could not be undone ({e}), use 'del {key} --force' to delete it anyway"
+                    ))
+                    .into())
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let old = status.dbm.remove_dyn(&status.tablename, key.clone())?;
+            if old.is_none() {
+                return Err(Error::KeyNotFound(key).into());
+            }
+            status.last_undo = image;
+            write_io_success("del success".to_string())?;
+            Ok(false)
+        }
+        Commands::Undo => {
+            let record = status
+                .last_undo
+                .take()
+                .ok_or("nothing to undo in this session")?;
+            let table = match &record {
+                UndoRecord::Rows { table, .. } | UndoRecord::Table { table, .. } => table.clone(),
+            };
+            if let Err(e) = status.dbm.undo(record.clone()) {
+                status.last_undo = Some(record);
+                return Err(e.into());
+            }
+            write_io_success(format!("reverted the last change to table {table}"))?;
+            Ok(false)
+        }
+        Commands::Begin => {
+            if status.filepath.is_empty() {
+                return Err("you must set file path first !!".into());
+            }
+            status.dbm.begin_transaction()?;
+            write_io_success("transaction started".to_string())?;
             Ok(false)
         }
-        Commands::Delete { tablename } => {
-            let _ = status.dbm.delete_table(tablename);
-            write_io_success("delete table success".to_string())?;
+        Commands::Commit => {
+            status.dbm.commit_transaction()?;
+            write_io_success("transaction committed".to_string())?;
+            Ok(false)
+        }
+        Commands::Rollback => {
+            status.dbm.rollback_transaction()?;
+            // the before-image may describe a change that was just discarded
+            status.last_undo = None;
+            write_io_success("transaction rolled back".to_string())?;
+            Ok(false)
+        }
+        Commands::Dryrun { state } => {
+            status.dbm.set_dry_run(state == Switch::On)?;
+            write_io_success(format!(
+                "dry-run {}",
+                if status.dbm.dry_run { "on" } else { "off" }
+            ))?;
+            Ok(false)
+        }
+        Commands::Snapshot { state } => {
+            match state {
+                Switch::On => {
+                    if status.filepath.is_empty() {
+                        return Err("you must set file path first !!".into());
+                    }
+                    status.dbm.pin_snapshot()?;
+                    write_io_success(
+                        "snapshot pinned, writes are refused until 'snapshot off'".to_string(),
+                    )?;
+                }
+                Switch::Off => {
+                    if !status.dbm.release_snapshot() {
+                        return Err("no snapshot is pinned".into());
+                    }
+                    write_io_success("snapshot released".to_string())?;
+                }
+            }
+            Ok(false)
+        }
+        Commands::Close => {
+            if !status.dbm.close_db()? {
+                return Err("no database is open".into());
+            }
+            write_io_success(format!("{} closed", status.filepath))?;
+            Ok(false)
+        }
+        Commands::Reopen => {
+            if status.filepath.is_empty() {
+                return Err("you must set file path first !!".into());
+            }
+            status.dbm.reopen_db()?;
+            write_io_success(format!("{} reopened", status.filepath))?;
+            Ok(false)
+        }
+        Commands::CopyTo {
+            filepath,
+            tablename,
+            opts,
+        } => {
+            if status.filepath.is_empty() {
+                return Err("you must set file path first !!".into());
+            }
+            let dest_name = opts.as_name.clone().unwrap_or_else(|| tablename.clone());
+            let copied =
+                status
+                    .dbm
+                    .copy_table_to(&filepath, &tablename, &dest_name, opts.mode())?;
+            write_io_success(format!(
+                "copied {copied} rows of table {tablename} to {filepath} as {dest_name}"
+            ))?;
+            Ok(false)
+        }
+        Commands::CopyFrom {
+            filepath,
+            tablename,
+            opts,
+        } => {
+            if status.filepath.is_empty() {
+                return Err("you must set file path first !!".into());
+            }
+            let dest_name = opts.as_name.clone().unwrap_or_else(|| tablename.clone());
+            let copied =
+                status
+                    .dbm
+                    .copy_table_from(&filepath, &tablename, &dest_name, opts.mode())?;
+            write_io_success(format!(
+                "copied {copied} rows of table {tablename} from {filepath} as {dest_name}"
+            ))?;
+            Ok(false)
+        }
+        Commands::Checksum { tablename, all } => {
+            let names = if all {
+                status.dbm.list_table()?
+            } else {
+                match tablename {
+                    Some(name) => vec![name],
+                    None if !status.tablename.is_empty() => vec![status.tablename.clone()],
+                    None => return Err("you must use table or pass --all !!".into()),
+                }
+            };
+            let digests = status.dbm.checksum_tables(&names)?;
+            ChecksumInfo {
+                overall: checksum::digest_to_hex(&checksum::overall_digest(&digests)),
+                tables: digests
+                    .iter()
+                    .map(|(name, digest)| (name.clone(), checksum::digest_to_hex(digest)))
+                    .collect(),
+            }
+            .print_data();
+            Ok(false)
+        }
+        Commands::Check => {
+            if status.filepath.is_empty() {
+                return Err("you must set file path first !!".into());
+            }
+            if status.dbm.check_integrity()? {
+                write_io_success("database passed the integrity check".to_string())?;
+            } else {
+                write_io_success(
+                    "database failed the integrity check and was repaired".to_string(),
+                )?;
+            }
+            Ok(false)
+        }
+        Commands::Salvage { damaged, dest } => {
+            let report = status.dbm.salvage(&damaged, &dest)?;
+            report.print_data();
+            let skipped = report.tables.iter().filter(|t| t.problem.is_some()).count();
+            if skipped > 0 {
+                write_io_info(format!(
+                    "{skipped} table(s) could not be fully recovered, see above"
+                ))?;
+            }
+            write_io_success(format!("salvaged data written to {dest}"))?;
+            Ok(false)
+        }
+        Commands::Compact => {
+            if status.filepath.is_empty() {
+                return Err("you must set file path first !!".into());
+            }
+            let report = status.dbm.compact()?;
+            if !report.compacted {
+                write_io_info("nothing to compact".to_string())?;
+            }
+            write_io_success(format!(
+                "compacted {} -> {} in {:.2?}",
+                human_bytes(report.size_before),
+                human_bytes(report.size_after),
+                report.elapsed
+            ))?;
+            Ok(false)
+        }
+        Commands::Backup { dest, compact } => {
+            if status.filepath.is_empty() {
+                return Err("you must set file path first !!".into());
+            }
+            let report = status.dbm.backup(&dest, compact)?;
+            if !report.skipped.is_empty() {
+                write_io_info(format!(
+                    "multimap tables are not supported yet, left out: {}",
+                    report.skipped.join(", ")
+                ))?;
+            }
+            write_io_success(format!(
+                "backed up {} tables ({} rows) to {dest}, {} in {:.2?}",
+                report.tables,
+                report.rows,
+                human_bytes(report.size),
+                report.elapsed
+            ))?;
+            Ok(false)
+        }
+        Commands::Savepoint(args) => {
+            if status.filepath.is_empty() {
+                return Err("you must set file path first !!".into());
+            }
+            match args.command {
+                SavepointCommands::Create => {
+                    let id = status.dbm.create_savepoint()?;
+                    write_io_success(format!("created savepoint {id}"))?;
+                }
+                SavepointCommands::List => {
+                    let ids = status.dbm.list_savepoints()?;
+                    if ids.is_empty() {
+                        write_io_info("no savepoints".to_string())?;
+                    }
+                    for id in ids {
+                        println!("{id}");
+                    }
+                }
+                SavepointCommands::Diff { id } => {
+                    let tables = status.dbm.savepoint_diff(id)?;
+                    DiffInfo { tables }.print_data();
+                }
+                SavepointCommands::Restore { id, yes } => {
+                    let tables = status.dbm.savepoint_diff(id)?;
+                    DiffInfo { tables }.print_data();
+                    if !yes && !confirm(&format!("restore savepoint {id}?"))? {
+                        write_io_info("restore cancelled".to_string())?;
+                        return Ok(false);
+                    }
+                    status.dbm.restore_savepoint(id)?;
+                    write_io_success(format!("restored savepoint {id}"))?;
+                }
+                SavepointCommands::Delete { id } => {
+                    if !status.dbm.delete_savepoint(id)? {
+                        return Err(format!("savepoint {id} does not exist").into());
+                    }
+                    write_io_success(format!("deleted savepoint {id}"))?;
+                }
+            }
+            Ok(false)
+        }
+        Commands::Layouts { filepath } => {
+            match filepath {
+                Some(path) => {
+                    let layouts = Layouts::load(&path)?;
+                    let count = layouts.iter().count();
+                    layouts.install();
+                    write_io_success(format!("loaded {count} layouts from {path}"))?;
+                }
+                None => {
+                    let layouts = Layouts::installed();
+                    if layouts.is_empty() {
+                        write_io_info("no layouts loaded".to_string())?;
+                    }
+                    for layout in layouts.iter() {
+                        println!("{}: {}", layout.name, layout.format);
+                    }
+                }
+            }
+            Ok(false)
+        }
+        Commands::Decode {
+            tablename,
+            value,
+            schema,
+        } => {
+            let decoder = match value.as_deref() {
+                None => None,
+                Some("none") => Some(None),
+                Some(name) => {
+                    let codec = Codec::parse(name).ok_or(format!(
+                        "unknown codec '{name}', expected {} or none",
+                        Codec::NAMES
+                    ))?;
+                    Some(Some(ValueDecoder { codec, schema }))
+                }
+            };
+            match (tablename, decoder) {
+                (Some(table), Some(decoder)) => {
+                    let message = match &decoder {
+                        Some(decoder) => {
+                            format!("values of table {table} are decoded as {decoder}")
+                        }
+                        None => format!("values of table {table} are shown as hex"),
+                    };
+                    status.dbm.set_value_decoder(&table, decoder)?;
+                    write_io_success(message)?;
+                    if let Some(path) = status.dbm.config.path() {
+                        write_io_info(format!("saved to {}", path.display()))?;
+                    }
+                }
+                (None, Some(decoder)) => {
+                    if let Some(decoder) = &decoder {
+                        decoder.layout()?;
+                    }
+                    let message = match &decoder {
+                        Some(decoder) => {
+                            format!("values of every table are decoded as {decoder} this session")
+                        }
+                        None => "values of tables without a decoder are shown as hex".to_string(),
+                    };
+                    status.dbm.session_decoder = decoder;
+                    write_io_success(message)?;
+                }
+                (tablename, None) => {
+                    let mut decoders = status.dbm.value_decoders();
+                    if let Some(table) = tablename {
+                        decoders.retain(|(name, _)| *name == table);
+                    }
+                    if let Some(decoder) = &status.dbm.session_decoder {
+                        println!("(session): {decoder}");
+                    } else if decoders.is_empty() {
+                        write_io_info("no value decoders set".to_string())?;
+                    }
+                    for (table, decoder) in decoders {
+                        println!("{table}: {decoder}");
+                    }
+                }
+            }
+            Ok(false)
+        }
+        Commands::Lens {
+            tablename,
+            key,
+            value,
+        } => {
+            let parse = |name: Option<String>| -> Result<Option<Option<Lens>>, String> {
+                match name.as_deref() {
+                    None => Ok(None),
+                    Some("none") => Ok(Some(None)),
+                    Some(name) => Lens::parse(name)
+                        .map(|lens| Some(Some(lens)))
+                        .ok_or(format!(
+                            "unknown lens '{name}', expected {} or none",
+                            Lens::NAMES
+                        )),
+                }
+            };
+            let (key, value) = (parse(key)?, parse(value)?);
+            if key.is_none() && value.is_none() {
+                let mut lenses: Vec<(String, Lenses)> = status
+                    .dbm
+                    .lenses
+                    .iter()
+                    .filter(|(name, _)| tablename.as_ref().is_none_or(|table| table == *name))
+                    .map(|(name, lenses)| (name.clone(), *lenses))
+                    .collect();
+                lenses.sort_by(|a, b| a.0.cmp(&b.0));
+                if lenses.is_empty() {
+                    write_io_info("no lenses set".to_string())?;
+                }
+                let show = |lens: Option<Lens>| lens.map_or("none", Lens::name);
+                for (table, lenses) in lenses {
+                    println!(
+                        "{table}: key {}, value {}",
+                        show(lenses.key),
+                        show(lenses.value)
+                    );
+                }
+                return Ok(false);
+            }
+            let table = tablename.unwrap_or_else(|| status.tablename.clone());
+            let current = status.dbm.lenses.get(&table).copied().unwrap_or_default();
+            let lenses = Lenses {
+                key: key.unwrap_or(current.key),
+                value: value.unwrap_or(current.value),
+            };
+            status.dbm.set_lenses(&table, lenses)?;
+            let show =
+                |lens: Option<Lens>| lens.map_or("plain".to_string(), |lens| lens.to_string());
+            write_io_success(format!(
+                "table {table} shows keys {} and values {}",
+                show(lenses.key),
+                show(lenses.value)
+            ))?;
             Ok(false)
         }
         Commands::New { databasename } => {
-            Database::create(&databasename).map_err(|e| e.to_string())?;
-            status.filepath = databasename.clone();
-            status
-                .dbm
-                .setdbpath(databasename)
-                .map_err(|e| e.to_string())?;
+            status.dbm.create_db(&databasename)?;
+            open_database(status, databasename)?;
             write_io_success("create database success".to_string())?;
             Ok(false)
         }
@@ -1,36 +1,95 @@
 use comfy_table::{Cell, Table};
 
-use crate::{KvInfo, TableInfo};
+use crate::diff::{RowChange, TableChange};
+use crate::{ChecksumInfo, DbInfo, DiffInfo, KvInfo, PreviewInfo, SalvageReport, TableInfo};
 
 pub trait PrintTable {
     fn print_data(&self);
 }
 
+/// Formats a byte count as `1234 (1.2 KiB)`.
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{bytes} ({size:.1} {})", UNITS[unit])
+    }
+}
+
 impl PrintTable for TableInfo {
     fn print_data(&self) {
+        let with_stats = self.tables.iter().any(|t| t.stats.is_some());
+        let mut header = vec![
+            Cell::new("id"),
+            Cell::new("name"),
+            Cell::new("key type"),
+            Cell::new("value type"),
+        ];
+        if with_stats {
+            header.extend([
+                Cell::new("rows"),
+                Cell::new("stored"),
+                Cell::new("metadata"),
+                Cell::new("fragmented"),
+                Cell::new("height"),
+            ]);
+        }
         let mut table = Table::new();
         table
             .load_preset(comfy_table::presets::UTF8_FULL)
             .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
             .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
-            .set_header(vec![
-                Cell::new("id"),
-                Cell::new("name"),
-                Cell::new("key type"),
-                Cell::new("value type"),
-            ]);
+            .set_header(header);
         self.tables.iter().enumerate().for_each(|(idx, t)| {
-            table.add_row(vec![
+            let mut row = vec![
                 Cell::new((idx + 1).to_string()),
                 Cell::new(&t.name),
                 Cell::new(&t.key_type),
                 Cell::new(&t.value_type),
-            ]);
+            ];
+            if let Some(stats) = &t.stats {
+                row.extend([
+                    Cell::new(stats.rows.to_string()),
+                    Cell::new(human_bytes(stats.stored_bytes)),
+                    Cell::new(human_bytes(stats.metadata_bytes)),
+                    Cell::new(human_bytes(stats.fragmented_bytes)),
+                    Cell::new(stats.tree_height.to_string()),
+                ]);
+            }
+            table.add_row(row);
         });
         println!("{table}");
     }
 }
 
+fn kv_table<R: IntoIterator<Item = Cell>>(
+    columns: &[String],
+    rows: impl Iterator<Item = R>,
+    first_id: usize,
+) -> Table {
+    let mut header = vec![Cell::new("id")];
+    header.extend(columns.iter().map(Cell::new));
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(header);
+    rows.enumerate().for_each(|(idx, cells)| {
+        let mut row = vec![Cell::new((first_id + idx).to_string())];
+        row.extend(cells);
+        table.add_row(row);
+    });
+    table
+}
+
 impl PrintTable for KvInfo {
     fn print_data(&self) {
         println!(
@@ -39,19 +98,231 @@ impl PrintTable for KvInfo {
             self.value_type,
             self.kvdatas.len()
         );
+        let columns = ["key".to_string(), "value".to_string()];
+        let rows = self
+            .kvdatas
+            .iter()
+            .map(|(k, v)| [Cell::new(k), Cell::new(v)]);
+        println!("{}", kv_table(&columns, rows, 1));
+    }
+}
+
+/// Prints rows as they are read, in pages of `KvPager::PAGE_ROWS`, so a
+/// table of any size is shown without holding it in memory. Nothing is
+/// printed until the first page or `finish`, so a read that fails right
+/// away leaves no header behind.
+pub struct KvPager {
+    header: Option<String>,
+    columns: Vec<String>,
+    page: Vec<Vec<String>>,
+    printed: usize,
+}
+
+impl KvPager {
+    pub const PAGE_ROWS: usize = 1000;
+
+    /// Pages rows of cells under the headers `columns`, ex: `key`, `value`
+    /// or `key.0`, `key.1`, `value`.
+    pub fn new(key_type: &str, value_type: &str, columns: Vec<String>) -> Self {
+        KvPager {
+            header: Some(format!(
+                "table type: key = {key_type}, value = {value_type}"
+            )),
+            columns,
+            page: Vec::with_capacity(Self::PAGE_ROWS),
+            printed: 0,
+        }
+    }
+    pub fn push(&mut self, row: Vec<String>) {
+        self.page.push(row);
+        if self.page.len() == Self::PAGE_ROWS {
+            self.flush();
+        }
+    }
+    /// Prints the last page and the row count.
+    pub fn finish(mut self) {
+        if !self.page.is_empty() || self.printed == 0 {
+            self.flush();
+        }
+        println!("rows = {}", self.printed);
+    }
+    fn flush(&mut self) {
+        if let Some(header) = self.header.take() {
+            println!("{header}");
+        }
+        let rows = self.page.iter().map(|row| row.iter().map(Cell::new));
+        println!("{}", kv_table(&self.columns, rows, self.printed + 1));
+        self.printed += self.page.len();
+        self.page.clear();
+    }
+}
+
+// Printed in `sha256sum` style so the output can be diffed or grepped.
+impl PrintTable for ChecksumInfo {
+    fn print_data(&self) {
+        for (name, digest) in &self.tables {
+            println!("{digest}  {name}");
+        }
+        println!("{}  *", self.overall);
+    }
+}
+
+impl PrintTable for DbInfo {
+    fn print_data(&self) {
         let mut table = Table::new();
         table
             .load_preset(comfy_table::presets::UTF8_FULL)
             .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
             .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
-            .set_header(vec![Cell::new("id"), Cell::new("key"), Cell::new("value")]);
-        self.kvdatas.iter().enumerate().for_each(|(idx, (k, v))| {
+            .set_header(vec![Cell::new("property"), Cell::new("value")]);
+        let last_transaction = self
+            .last_transaction
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let rows = [
+            ("path", self.path.clone()),
+            ("file size", human_bytes(self.file_size)),
+            ("tables", self.tables.to_string()),
+            ("multimap tables", self.multimap_tables.to_string()),
+            ("last committed transaction", last_transaction),
+            ("page size", human_bytes(self.page_size as u64)),
+            ("tree height", self.tree_height.to_string()),
+            ("allocated pages", self.allocated_pages.to_string()),
+            ("leaf pages", self.leaf_pages.to_string()),
+            ("branch pages", self.branch_pages.to_string()),
+            ("stored bytes", human_bytes(self.stored_bytes)),
+            ("metadata bytes", human_bytes(self.metadata_bytes)),
+            ("fragmented bytes", human_bytes(self.fragmented_bytes)),
+        ];
+        for (name, value) in rows {
+            table.add_row(vec![Cell::new(name), Cell::new(value)]);
+        }
+        println!("{table}");
+    }
+}
+
+impl PrintTable for SalvageReport {
+    fn print_data(&self) {
+        let mut table = Table::new();
+        table
+            .load_preset(comfy_table::presets::UTF8_FULL)
+            .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+            .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+            .set_header(vec![
+                Cell::new("name"),
+                Cell::new("key type"),
+                Cell::new("value type"),
+                Cell::new("rows copied"),
+                Cell::new("problem"),
+            ]);
+        for t in &self.tables {
             table.add_row(vec![
-                Cell::new((idx + 1).to_string()),
-                Cell::new(k),
-                Cell::new(v),
+                Cell::new(&t.name),
+                Cell::new(&t.key_type),
+                Cell::new(&t.value_type),
+                Cell::new(t.rows.to_string()),
+                Cell::new(t.problem.as_deref().unwrap_or("-")),
             ]);
-        });
+        }
         println!("{table}");
     }
 }
+
+impl PrintTable for DiffInfo {
+    fn print_data(&self) {
+        if self.tables.is_empty() {
+            println!("no differences");
+            return;
+        }
+        for t in &self.tables {
+            let change = match &t.change {
+                TableChange::Created => "created".to_string(),
+                TableChange::Dropped => "dropped".to_string(),
+                TableChange::Modified => "modified".to_string(),
+                TableChange::NotComparable(reason) => format!("not comparable: {reason}"),
+            };
+            println!(
+                "table {} ({change}), {} row change(s)",
+                t.name,
+                t.rows.len()
+            );
+            if t.rows.is_empty() {
+                continue;
+            }
+            let mut table = Table::new();
+            table
+                .load_preset(comfy_table::presets::UTF8_FULL)
+                .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+                .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+                .set_header(vec![
+                    Cell::new(""),
+                    Cell::new("key"),
+                    Cell::new("old value"),
+                    Cell::new("new value"),
+                ]);
+            for row in &t.rows {
+                let cells = match row {
+                    RowChange::Added { key, value } => ["+", key, "", value],
+                    RowChange::Removed { key, value } => ["-", key, value, ""],
+                    RowChange::Changed { key, old, new } => ["~", key, old, new],
+                };
+                table.add_row(cells.iter().map(Cell::new).collect::<Vec<_>>());
+            }
+            println!("{table}");
+        }
+    }
+}
+
+impl PrintTable for PreviewInfo {
+    fn print_data(&self) {
+        for entry in &self.entries {
+            println!(
+                "would {} table {}, {} row change(s)",
+                entry.action,
+                entry.table,
+                entry.changes.len()
+            );
+            if entry.changes.is_empty() {
+                continue;
+            }
+            let mut table = Table::new();
+            table
+                .load_preset(comfy_table::presets::UTF8_FULL)
+                .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+                .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+                .set_header(vec![
+                    Cell::new(""),
+                    Cell::new("key"),
+                    Cell::new("old value"),
+                    Cell::new("new value"),
+                ]);
+            for change in &entry.changes {
+                let mark = match (&change.before, &change.after) {
+                    (None, _) => "+",
+                    (_, None) => "-",
+                    _ => "~",
+                };
+                table.add_row(vec![
+                    Cell::new(mark),
+                    Cell::new(&change.key),
+                    Cell::new(change.before.as_deref().unwrap_or("")),
+                    Cell::new(change.after.as_deref().unwrap_or("")),
+                ]);
+            }
+            println!("{table}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn human_bytes_units() {
+        assert_eq!(human_bytes(0), "0 B");
+        assert_eq!(human_bytes(1023), "1023 B");
+        assert_eq!(human_bytes(1536), "1536 (1.5 KiB)");
+        assert_eq!(human_bytes(4 * 1024 * 1024 * 1024), "4294967296 (4.0 GiB)");
+    }
+}
@@ -1,12 +1,178 @@
-use crate::dynread::{self, TableTypeDesc};
-use redb::{Database, Error, ReadableDatabase, TableDefinition, TableError, TableHandle};
+use crate::audit::{AuditChange, AuditEntry, Journal};
+use crate::checksum::{self, TableDigest};
+use crate::config::{Config, ValueDecoder};
+use crate::diff::{self, TableDiff, TableRows};
+use crate::dynread::{self, ColumnQuery, CopyMode, RowSource, TableTypeDesc};
+use crate::lens::Lenses;
+use crate::typecodec::TableCodec;
+use crate::Error;
+use crate::{
+    fileheader, BackupReport, CompactReport, DbInfo, SalvageReport, SalvagedTable, TableStatsMeta,
+};
+use redb::{
+    Builder, Database, MultimapTableHandle, ReadOnlyDatabase, ReadTransaction, ReadableDatabase,
+    ReadableTable, ReadableTableMetadata, TableDefinition, TableError, TableHandle,
+    WriteTransaction,
+};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Receives the estimated repair progress in `[0.0, 1.0)` while redb
+/// repairs a database that was not shut down cleanly.
+pub type RepairProgress = fn(f64);
+
+/// A write transaction kept open across commands between
+/// `begin_transaction` and `commit_transaction`/`rollback_transaction`.
+pub struct PendingWrite {
+    txn: WriteTransaction,
+    /// Audit entries of the writes made so far, journaled on commit.
+    audited: RefCell<Vec<AuditEntry>>,
+}
+
+impl std::fmt::Debug for PendingWrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingWrite").finish_non_exhaustive()
+    }
+}
+
+/// The state a mutating command overwrote, kept as stored bytes so `undo`
+/// can put it back exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UndoRecord {
+    /// The rows a `put`/`del` touched, see `row_image`.
+    Rows {
+        table: String,
+        before: dynread::StoredImage,
+    },
+    /// A whole table before `edit`, `create` or `delete`, see
+    /// `table_image`; `None` if it did not exist.
+    Table {
+        table: String,
+        before: Option<dynread::StoredImage>,
+    },
+}
+
+/// A read transaction pinned by `pin_snapshot`, so consecutive commands
+/// all see the state of the file at the moment it was taken.
+pub struct PinnedSnapshot {
+    txn: ReadTransaction,
+    taken: Instant,
+}
+
+/// A database handle, opened normally or through redb's read-only API.
+pub enum OpenDb {
+    Writable(Database),
+    ReadOnly(ReadOnlyDatabase),
+}
+
+impl std::fmt::Debug for OpenDb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenDb::Writable(_) => f.write_str("OpenDb::Writable"),
+            OpenDb::ReadOnly(_) => f.write_str("OpenDb::ReadOnly"),
+        }
+    }
+}
+
+impl OpenDb {
+    fn begin_read(&self) -> Result<ReadTransaction, Error> {
+        Ok(match self {
+            OpenDb::Writable(db) => db.begin_read()?,
+            OpenDb::ReadOnly(db) => db.begin_read()?,
+        })
+    }
+    fn writable(&self) -> Result<&Database, Error> {
+        match self {
+            OpenDb::Writable(db) => Ok(db),
+            OpenDb::ReadOnly(_) => Err(read_only_error()),
+        }
+    }
+}
+
+/// A private copy of a database that another process holds locked, see
+/// `inspect_copy`. The file is removed when this is dropped.
+#[derive(Debug)]
+pub struct InspectedCopy {
+    pub source: String,
+    _file: tempfile::TempPath,
+}
+
+impl std::fmt::Debug for PinnedSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PinnedSnapshot")
+            .field("taken", &self.taken)
+            .finish_non_exhaustive()
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct CommonDbManager {
     pub tablename: String,
     pub dbpath: String,
+    // Fields drop in declaration order: the open transaction and snapshot
+    // must go before `db`, whose drop begins a write and would block on
+    // the pending one forever.
+    pub pending: Option<PendingWrite>,
+    pub snapshot: Option<PinnedSnapshot>,
+    /// The handle on `dbpath`, kept open between commands; see `open_db`,
+    /// `close_db` and `reopen_db`.
+    pub db: Option<OpenDb>,
+    pub on_repair: Option<RepairProgress>,
+    /// Journal that every committed write is recorded in.
+    pub audit: Option<Journal>,
+    /// Abort every write transaction instead of committing it.
+    pub dry_run: bool,
+    /// Changes computed by writes in dry-run mode, see `take_preview`.
+    pub preview: RefCell<Vec<AuditEntry>>,
+    /// Open through redb's read-only API and refuse every write.
+    pub read_only: bool,
+    /// Set while `dbpath` points at a temporary copy, which is read-only too.
+    pub inspected_copy: Option<InspectedCopy>,
+    /// Settings kept between sessions, ex: the value decoders of tables.
+    pub config: Config,
+    /// Decoder for the `&[u8]` values of tables without their own, kept
+    /// for this session only.
+    pub session_decoder: Option<ValueDecoder>,
+    /// Lenses of the key and value cells by table name, kept for this
+    /// session only.
+    pub lenses: HashMap<String, Lenses>,
+}
+
+/// Binds `$txn` to the pinned snapshot if there is one, or to a fresh read
+/// transaction otherwise.
+macro_rules! with_snapshot {
+    ($self:ident, |$txn:ident| $body:expr) => {
+        match &$self.snapshot {
+            Some(snapshot) => {
+                let $txn = &snapshot.txn;
+                $body
+            }
+            None => {
+                let read_txn = $self.handle()?.begin_read()?;
+                let $txn = &read_txn;
+                $body
+            }
+        }
+    };
+}
+
+/// Like `with_snapshot!`, but prefers the pending write transaction if one
+/// is open, so reads see its uncommitted changes.
+macro_rules! with_reader {
+    ($self:ident, |$txn:ident| $body:expr) => {
+        match &$self.pending {
+            Some(pending) => {
+                let $txn = &pending.txn;
+                $body
+            }
+            None => with_snapshot!($self, |$txn| $body),
+        }
+    };
 }
 // Deal with table
 pub trait DealTable {
@@ -24,12 +190,45 @@ pub trait DealData {
     fn update_by_key(&self, key: String, data: String) -> Result<(), Error>;
 }
 
+thread_local! {
+    static PANICS_QUIET: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Silences the panic hook on this thread until dropped, also when
+/// unwinding. The hook is wrapped once for the whole process and keeps
+/// reporting the panics of other threads.
+struct QuietPanics;
+
+impl QuietPanics {
+    fn new() -> Self {
+        static WRAP_HOOK: std::sync::Once = std::sync::Once::new();
+        WRAP_HOOK.call_once(|| {
+            let hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                if !PANICS_QUIET.with(|quiet| quiet.get()) {
+                    hook(info)
+                }
+            }));
+        });
+        PANICS_QUIET.with(|quiet| quiet.set(true));
+        QuietPanics
+    }
+}
+
+impl Drop for QuietPanics {
+    fn drop(&mut self) {
+        PANICS_QUIET.with(|quiet| quiet.set(false));
+    }
+}
+
+fn read_only_error() -> Error {
+    Error::refused("the database is opened read-only")
+}
+
 fn table_lookup_error(e: TableError, name: &str) -> Error {
     match e {
-        TableError::TableDoesNotExist(_) => {
-            Error::Corrupted(format!("Table '{name}' does not exist"))
-        }
-        TableError::TableIsMultimap(_) => Error::Corrupted(format!(
+        TableError::TableDoesNotExist(_) => Error::TableNotFound(name.to_string()),
+        TableError::TableIsMultimap(_) => Error::refused(format!(
             "Table '{name}' is a multimap table, which is not supported yet"
         )),
         e => e.into(),
@@ -38,7 +237,7 @@ fn table_lookup_error(e: TableError, name: &str) -> Error {
 
 fn str_op_error(e: TableError, name: &str) -> Error {
     match e {
-        TableError::TableTypeMismatch { key, value, .. } => Error::Corrupted(format!(
+        TableError::TableTypeMismatch { key, value, .. } => Error::refused(format!(
             "table '{name}' is of type Table<{}, {}>; only &str -> &str tables support this operation, use 'info table {name}' to browse it",
             key.name(),
             value.name()
@@ -48,175 +247,1060 @@ fn str_op_error(e: TableError, name: &str) -> Error {
 }
 
 impl CommonDbManager {
-    pub fn getdb(&self) -> Result<Database, Error> {
-        let db_file = Path::new(&self.dbpath);
-        if db_file.exists() {
-            let db = Database::open(&self.dbpath)?;
-            return Ok(db);
+    fn builder(&self) -> Builder {
+        let mut builder = Builder::new();
+        if let Some(report) = self.on_repair {
+            builder.set_repair_callback(move |session| report(session.progress()));
         }
-        Err(redb::Error::Corrupted("Database not found".to_string()))
+        builder
     }
-    pub fn settablename(&mut self, name: String) -> Result<(), Error> {
+    /// Fails while a pending transaction or a pinned snapshot is live:
+    /// redb blocks a second write transaction, and compaction or savepoint
+    /// restore need all read transactions to be gone.
+    fn ensure_released(&self) -> Result<(), Error> {
+        if self.pending.is_some() {
+            return Err(Error::refused(
+                "a transaction is open, commit or rollback it first",
+            ));
+        }
+        if self.snapshot.is_some() {
+            return Err(Error::refused(
+                "a snapshot is pinned, run 'snapshot off' first",
+            ));
+        }
+        Ok(())
+    }
+    fn handle(&self) -> Result<&OpenDb, Error> {
+        self.db
+            .as_ref()
+            .ok_or_else(|| Error::refused("no database is open, use 'set' or 'reopen'"))
+    }
+    /// The writable handle, for commands that start their own write
+    /// transaction.
+    pub fn getdb(&self) -> Result<&Database, Error> {
+        if self.is_read_only() {
+            return Err(read_only_error());
+        }
+        self.ensure_released()?;
+        self.handle()?.writable()
+    }
+    /// Like `getdb`, for the maintenance calls redb only allows on an
+    /// exclusive handle; opens `dbpath` first if it was closed.
+    fn getdb_mut(&mut self) -> Result<&mut Database, Error> {
+        if self.is_read_only() {
+            return Err(read_only_error());
+        }
+        self.ensure_released()?;
+        self.open_db()?;
+        match &mut self.db {
+            Some(OpenDb::Writable(db)) => Ok(db),
+            _ => Err(read_only_error()),
+        }
+    }
+    /// Opens `dbpath`, through the read-only API in read-only mode. A lock
+    /// held by another process is reported as `Error::Locked`.
+    pub fn open_db(&mut self) -> Result<(), Error> {
+        if self.db.is_some() {
+            return Ok(());
+        }
+        if !Path::new(&self.dbpath).exists() {
+            return Err(Error::DatabaseNotFound(self.dbpath.clone()));
+        }
+        let opened = if self.is_read_only() {
+            Builder::new()
+                .open_read_only(&self.dbpath)
+                .map(OpenDb::ReadOnly)
+        } else {
+            self.builder().open(&self.dbpath).map(OpenDb::Writable)
+        };
+        let db = opened.map_err(|e| match e {
+            redb::DatabaseError::DatabaseAlreadyOpen => Error::Locked(self.dbpath.clone()),
+            e => e.into(),
+        })?;
+        self.db = Some(db);
+        Ok(())
+    }
+    /// Closes the handle, releasing the file lock; returns `false` if no
+    /// database was open.
+    pub fn close_db(&mut self) -> Result<bool, Error> {
+        self.ensure_released()?;
+        Ok(self.db.take().is_some())
+    }
+    /// Closes and opens the database again, ex: after another process
+    /// released its lock or changed the file.
+    pub fn reopen_db(&mut self) -> Result<(), Error> {
+        self.close_db()?;
+        self.open_db()
+    }
+    pub fn is_read_only(&self) -> bool {
+        self.read_only || self.inspected_copy.is_some()
+    }
+    /// Copies `path`, which another process holds locked, to a temporary
+    /// file and inspects that copy read-only. The copy is taken without the
+    /// lock, so it may catch a commit in flight; it is repaired like a file
+    /// that was not shut down cleanly.
+    pub fn inspect_copy(&mut self, path: &str) -> Result<(), Error> {
+        self.close_db()?;
+        let file = tempfile::Builder::new()
+            .prefix("redbcli-")
+            .suffix(".redb")
+            .tempfile()?
+            .into_temp_path();
+        std::fs::copy(path, &file)?;
+        drop(self.builder().open(&file)?);
+        self.dbpath = file.to_string_lossy().to_string();
+        self.inspected_copy = Some(InspectedCopy {
+            source: path.to_string(),
+            _file: file,
+        });
+        self.open_db()
+    }
+    /// Whether writes need their changed rows computed, for the audit
+    /// journal or the dry-run preview.
+    fn tracking(&self) -> bool {
+        self.audit.is_some() || self.dry_run
+    }
+    /// Hands `entries` to the dry-run preview, or journals them once they
+    /// are committed: right away, or when the pending transaction they were
+    /// made in is committed.
+    fn record(&self, entries: Vec<AuditEntry>) -> Result<(), Error> {
+        if self.dry_run {
+            self.preview.borrow_mut().extend(entries);
+            return Ok(());
+        }
+        match &self.pending {
+            Some(pending) if self.audit.is_some() => {
+                pending.audited.borrow_mut().extend(entries);
+                Ok(())
+            }
+            _ => self.journal(&entries),
+        }
+    }
+    fn journal(&self, entries: &[AuditEntry]) -> Result<(), Error> {
+        match &self.audit {
+            Some(journal) => journal.append(&self.dbpath, entries).map_err(|e| {
+                Error::Io(std::io::Error::new(
+                    e.kind(),
+                    format!("the change was committed but writing the audit journal failed: {e}"),
+                ))
+            }),
+            None => Ok(()),
+        }
+    }
+    /// Like `write`, and records how `f` changed the rows of table `name`,
+    /// read inside the same transaction before and after `f`. Both reads
+    /// are kept in memory, so an audited write holds two full copies of the
+    /// table. If the rows cannot be read, the write still goes ahead and is
+    /// recorded as not captured; in dry-run mode it is refused, as there
+    /// would be nothing to preview.
+    fn write_table<R>(
+        &self,
+        name: &str,
+        action: &str,
+        f: impl FnOnce(&WriteTransaction) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        if !self.tracking() {
+            return self.write(f);
+        }
+        let (result, entry) = self.write(|txn| {
+            let before = table_rows(txn, name);
+            let result = f(txn)?;
+            let rows =
+                before.and_then(|before| Ok(diff::diff_rows(&before, &table_rows(txn, name)?)));
+            let entry = match rows {
+                Ok(rows) => AuditEntry::new(
+                    name,
+                    action,
+                    rows.into_iter().map(AuditChange::from).collect(),
+                ),
+                Err(e) if self.dry_run => {
+                    return Err(Error::refused(format!(
+                    "{action} of table '{name}' cannot be previewed ({e}), turn dry-run off first"
+                )))
+                }
+                Err(e) => AuditEntry::not_captured(name, action, e.to_string()),
+            };
+            Ok((result, entry))
+        })?;
+        self.record(vec![entry])?;
+        Ok(result)
+    }
+    /// Runs `f` in the pending transaction if one is open, without
+    /// committing; otherwise in a new write transaction that is committed
+    /// when `f` succeeds, or aborted in dry-run mode. Inside a pending
+    /// transaction a failed `f` may leave partial changes, which
+    /// `rollback_transaction` discards.
+    fn write<R>(&self, f: impl FnOnce(&WriteTransaction) -> Result<R, Error>) -> Result<R, Error> {
+        if let Some(pending) = &self.pending {
+            return f(&pending.txn);
+        }
         let db = self.getdb()?;
-        let read_txn = db.begin_read()?;
-        let handle: TableDefinition<&str, &str> = TableDefinition::new(name.as_str());
-        read_txn
-            .open_untyped_table(handle)
-            .map_err(|e| table_lookup_error(e, &name))?;
+        let write_txn = db.begin_write()?;
+        let result = f(&write_txn)?;
+        if self.dry_run {
+            write_txn.abort()?;
+        } else {
+            write_txn.commit()?;
+        }
+        Ok(result)
+    }
+    /// Turns dry-run mode on or off. Not allowed while a transaction is
+    /// open, whose writes would otherwise mix committed and previewed ones.
+    pub fn set_dry_run(&mut self, on: bool) -> Result<(), Error> {
+        if self.pending.is_some() {
+            return Err(Error::refused(
+                "a transaction is open, commit or rollback it first",
+            ));
+        }
+        self.dry_run = on;
+        Ok(())
+    }
+    /// Takes the changes previewed in dry-run mode since the last call.
+    pub fn take_preview(&self) -> Vec<AuditEntry> {
+        self.preview.take()
+    }
+    /// Fails in dry-run mode for commands whose effect cannot be previewed.
+    fn refuse_in_dry_run(&self, command: &str) -> Result<(), Error> {
+        if self.dry_run {
+            return Err(Error::refused(format!(
+                "{command} cannot be previewed, turn dry-run off first"
+            )));
+        }
+        Ok(())
+    }
+    pub fn in_transaction(&self) -> bool {
+        self.pending.is_some()
+    }
+    /// Opens a write transaction that subsequent mutations join until it is
+    /// committed or rolled back. Commands that need their own handle on the
+    /// file (compact, savepoints, ...) are refused meanwhile.
+    pub fn begin_transaction(&mut self) -> Result<(), Error> {
+        self.refuse_in_dry_run("begin")?;
+        if self.pending.is_some() {
+            return Err(Error::refused("a transaction is already open"));
+        }
+        let txn = self.getdb()?.begin_write()?;
+        self.pending = Some(PendingWrite {
+            txn,
+            audited: RefCell::default(),
+        });
+        Ok(())
+    }
+    pub fn commit_transaction(&mut self) -> Result<(), Error> {
+        let pending = self
+            .pending
+            .take()
+            .ok_or_else(|| Error::refused("no transaction is open"))?;
+        pending.txn.commit()?;
+        self.journal(&pending.audited.into_inner())
+    }
+    pub fn rollback_transaction(&mut self) -> Result<(), Error> {
+        let pending = self
+            .pending
+            .take()
+            .ok_or_else(|| Error::refused("no transaction is open"))?;
+        pending.txn.abort()?;
+        Ok(())
+    }
+    /// Pins a read transaction that all following reads use until
+    /// `release_snapshot`; pinning again moves it to the latest commit.
+    /// Writes are refused while it is pinned.
+    pub fn pin_snapshot(&mut self) -> Result<(), Error> {
+        self.snapshot = None;
+        let txn = self.handle()?.begin_read()?;
+        self.snapshot = Some(PinnedSnapshot {
+            txn,
+            taken: Instant::now(),
+        });
+        Ok(())
+    }
+    /// Drops the pinned snapshot; returns `false` if none was pinned.
+    pub fn release_snapshot(&mut self) -> bool {
+        self.snapshot.take().is_some()
+    }
+    /// How long ago the pinned snapshot was taken.
+    pub fn snapshot_age(&self) -> Option<Duration> {
+        self.snapshot.as_ref().map(|s| s.taken.elapsed())
+    }
+    /// Runs redb's full integrity check. Returns `true` if the file was
+    /// clean, `false` if problems were found and repaired; an error means the
+    /// file could not be repaired.
+    pub fn check_integrity(&mut self) -> Result<bool, Error> {
+        self.refuse_in_dry_run("check")?;
+        Ok(self.getdb_mut()?.check_integrity()?)
+    }
+    pub fn settablename(&mut self, name: String) -> Result<(), Error> {
+        self.table_type(&name)?;
         self.tablename = name;
         Ok(())
     }
+    /// Switches to the database at `path`. A lock held by another process
+    /// is reported as `Error::Locked`, see `inspect_copy`.
+    /// Creates an empty database file at `path` without switching to it,
+    /// see `setdbpath`. Refused in dry-run mode and while a transaction is
+    /// open or a snapshot is pinned, which switching would have to drop.
+    pub fn create_db(&self, path: &str) -> Result<(), Error> {
+        self.refuse_in_dry_run("new")?;
+        self.ensure_released()?;
+        Database::create(path)?;
+        Ok(())
+    }
     pub fn setdbpath(&mut self, path: String) -> Result<(), Error> {
+        self.close_db()?;
+        self.inspected_copy = None;
         self.dbpath = path;
-        self.getdb()?;
-        Ok(())
+        self.open_db()
     }
     pub fn table_type(&self, name: &str) -> Result<TableTypeDesc, Error> {
-        let db = self.getdb()?;
-        let read_txn = db.begin_read()?;
-        dynread::probe_table_type(&read_txn, name).map_err(|e| table_lookup_error(e, name))
+        let mut desc = with_reader!(self, |txn| dynread::probe_table_type(txn, name)
+            .map_err(|e| table_lookup_error(e, name)))?;
+        // a decoder whose schema is not loaded leaves the values as hex
+        if desc.value == dynread::type_name_of::<&[u8]>() {
+            desc.value_codec = self
+                .value_decoder(name)
+                .and_then(|decoder| decoder.layout().ok())
+                .map(Arc::new);
+        }
+        desc.lenses = self.lenses.get(name).copied().unwrap_or_default();
+        Ok(desc)
     }
-    pub fn list_table_types(&self) -> Result<Vec<(String, TableTypeDesc)>, Error> {
-        let names = DealTable::list_table(self)?;
-        let mut result = Vec::with_capacity(names.len());
-        for name in names {
-            let desc = self.table_type(&name)?;
-            result.push((name, desc));
+    /// The database the config keys settings by: the source of an
+    /// inspected copy, canonicalized so every spelling of a path agrees.
+    fn config_key(&self) -> String {
+        let path = match &self.inspected_copy {
+            Some(copy) => &copy.source,
+            None => &self.dbpath,
+        };
+        std::fs::canonicalize(path)
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| path.clone())
+    }
+    /// The decoder of the `&[u8]` values of table `name`, falling back to
+    /// the session's.
+    pub fn value_decoder(&self, name: &str) -> Option<&ValueDecoder> {
+        self.config
+            .decoder(&self.config_key(), name)
+            .or(self.session_decoder.as_ref())
+    }
+    /// The decoders set for tables of the current database.
+    pub fn value_decoders(&self) -> Vec<(String, ValueDecoder)> {
+        self.config
+            .decoders(&self.config_key())
+            .map(|(table, decoder)| (table.clone(), decoder.clone()))
+            .collect()
+    }
+    /// Sets, or with `None` clears, how the `&[u8]` values of table `name`
+    /// are decoded, and saves it in the config file.
+    pub fn set_value_decoder(
+        &mut self,
+        name: &str,
+        decoder: Option<ValueDecoder>,
+    ) -> Result<(), Error> {
+        let desc = with_reader!(self, |txn| dynread::probe_table_type(txn, name)
+            .map_err(|e| table_lookup_error(e, name)))?;
+        if let Some(decoder) = &decoder {
+            if desc.value != dynread::type_name_of::<&[u8]>() {
+                return Err(Error::refused(format!(
+                    "table '{name}' stores {} values, only &[u8] values can be decoded",
+                    desc.value
+                )));
+            }
+            decoder.layout().map_err(Error::refused)?;
         }
-        Ok(result)
+        let key = self.config_key();
+        self.config.set_decoder(&key, name, decoder);
+        self.config.save()
+    }
+    /// Sets how the key and value cells of table `name` are shown and
+    /// typed; each lens must fit the type of its side.
+    pub fn set_lenses(&mut self, name: &str, lenses: Lenses) -> Result<(), Error> {
+        let desc = self.table_type(name)?;
+        let codec = TableCodec::new(&desc).ok_or_else(|| {
+            Error::refused(format!(
+                "table '{name}' has types that are shown as raw bytes, see 'layouts'"
+            ))
+        })?;
+        let sides = [
+            (lenses.key, &codec.key, "keys"),
+            (lenses.value, &codec.value, "values"),
+        ];
+        for (lens, tree, side) in sides {
+            if let Some(lens) = lens.filter(|lens| !lens.fits(tree)) {
+                return Err(Error::refused(format!(
+                    "the {lens} lens cannot show the {tree} {side} of table '{name}'"
+                )));
+            }
+        }
+        if lenses == Lenses::default() {
+            self.lenses.remove(name);
+        } else {
+            self.lenses.insert(name.to_string(), lenses);
+        }
+        Ok(())
+    }
+    /// Probes every table from a single read transaction.
+    pub fn list_table_types(&self) -> Result<Vec<(String, TableTypeDesc)>, Error> {
+        with_reader!(self, |txn| {
+            let names = txn.table_names()?;
+            let mut result = Vec::with_capacity(names.len());
+            for name in names {
+                let desc = dynread::probe_table_type(txn, &name)
+                    .map_err(|e| table_lookup_error(e, &name))?;
+                result.push((name, desc));
+            }
+            Ok(result)
+        })
+    }
+    /// Returns row count and storage statistics of every table, read from a
+    /// single read transaction.
+    pub fn list_table_stats(&self) -> Result<Vec<(String, TableStatsMeta)>, Error> {
+        with_snapshot!(self, |read_txn| {
+            let mut result = Vec::new();
+            for name in read_txn.table_names()? {
+                let handle: TableDefinition<&str, &str> = TableDefinition::new(name.as_str());
+                let table = read_txn
+                    .open_untyped_table(handle)
+                    .map_err(|e| table_lookup_error(e, &name))?;
+                let stats = table.stats()?;
+                let meta = TableStatsMeta {
+                    rows: table.len()?,
+                    stored_bytes: stats.stored_bytes(),
+                    metadata_bytes: stats.metadata_bytes(),
+                    fragmented_bytes: stats.fragmented_bytes(),
+                    tree_height: stats.tree_height(),
+                };
+                result.push((name, meta));
+            }
+            Ok(result)
+        })
     }
     pub fn get_all_dyn(&self, name: &str) -> Result<(TableTypeDesc, Vec<(String, String)>), Error> {
         let desc = self.table_type(name)?;
-        let db = self.getdb()?;
-        let read_txn = db.begin_read()?;
-        let rows = dynread::read_table_dyn(&read_txn, name, &desc).map_err(Error::Corrupted)?;
+        let rows = with_reader!(self, |txn| dynread::read_table_dyn(txn, name, &desc))?;
         Ok((desc, rows))
     }
+    /// Streams the formatted rows of table `name` to `visit` in key order,
+    /// without collecting them; see `dynread::visit_table_dyn`.
+    pub fn visit_rows_dyn(
+        &self,
+        name: &str,
+        visit: &mut dyn FnMut(String, String) -> ControlFlow<()>,
+    ) -> Result<TableTypeDesc, Error> {
+        let desc = self.table_type(name)?;
+        with_reader!(self, |txn| dynread::visit_table_dyn(
+            txn, name, &desc, visit
+        ))?;
+        Ok(desc)
+    }
+    /// Streams the rows of table `name`, whose types `desc` were obtained
+    /// with `table_type`, to `visit` as cells laid out by `query`, ex: one
+    /// column per tuple element; see `dynread::visit_table_columns`.
+    pub fn visit_columns_dyn(
+        &self,
+        name: &str,
+        desc: &TableTypeDesc,
+        query: &ColumnQuery,
+        visit: &mut dyn FnMut(Vec<String>) -> ControlFlow<()>,
+    ) -> Result<(), Error> {
+        with_reader!(self, |txn| dynread::visit_table_columns(
+            txn, name, desc, query, visit
+        ))
+    }
     /// Replaces the whole content of table `name` with edited rows (cell
     /// strings as produced by `get_all_dyn`). All rows are validated before
     /// anything is written; on error the table is left unchanged.
     pub fn update_all_dyn(&self, name: &str, rows: Vec<(String, String)>) -> Result<(), Error> {
         let desc = self.table_type(name)?;
+        self.write_table(name, "edit", |txn| {
+            dynread::write_table_dyn(txn, name, &desc, rows)
+        })
+    }
+    /// Inserts or overwrites one row of table `name`, given as cell strings.
+    /// Returns the value it replaced, if any.
+    pub fn put_dyn(&self, name: &str, key: String, value: String) -> Result<Option<String>, Error> {
+        let desc = self.table_type(name)?;
+        let put = vec![(key.clone(), value.clone())];
+        let before = self.write(|txn| dynread::apply_rows_dyn(txn, name, &desc, put, vec![]))?;
+        let old = before.into_iter().next().and_then(|(_, old)| old);
+        let change = AuditChange {
+            key,
+            before: old.clone(),
+            after: Some(value),
+        };
+        self.record(vec![AuditEntry::new(name, "put", vec![change])])?;
+        Ok(old)
+    }
+    /// Removes one row of table `name`; returns the removed value, or `None`
+    /// if the key was absent.
+    pub fn remove_dyn(&self, name: &str, key: String) -> Result<Option<String>, Error> {
+        let desc = self.table_type(name)?;
+        let before =
+            self.write(|txn| dynread::apply_rows_dyn(txn, name, &desc, vec![], vec![key.clone()]))?;
+        let old = before.into_iter().next().and_then(|(_, old)| old);
+        if old.is_some() {
+            let change = AuditChange {
+                key,
+                before: old.clone(),
+                after: None,
+            };
+            self.record(vec![AuditEntry::new(name, "del", vec![change])])?;
+        }
+        Ok(old)
+    }
+    /// Captures table `name` as it is now, so a following `edit`, `create`
+    /// or `delete` can be reverted with `undo`.
+    pub fn table_image(&self, name: &str) -> Result<UndoRecord, Error> {
+        let before = if DealTable::list_table(self)?.iter().any(|t| t == name) {
+            let desc = self.table_type(name)?;
+            Some(with_reader!(self, |txn| dynread::table_image(
+                txn, name, &desc
+            ))?)
+        } else {
+            None
+        };
+        Ok(UndoRecord::Table {
+            table: name.to_string(),
+            before,
+        })
+    }
+    /// Captures the row of table `name` under `key` (cell text) as it is
+    /// now, so a following `put` or `del` of it can be reverted with `undo`.
+    pub fn row_image(&self, name: &str, key: &str) -> Result<UndoRecord, Error> {
+        let desc = self.table_type(name)?;
+        let keys = [key.to_string()];
+        let before = with_reader!(self, |txn| dynread::row_image(txn, name, &desc, &keys))?;
+        Ok(UndoRecord::Rows {
+            table: name.to_string(),
+            before,
+        })
+    }
+    /// Writes the before-image in `record` back, byte for byte.
+    pub fn undo(&self, record: UndoRecord) -> Result<(), Error> {
+        match record {
+            UndoRecord::Rows { table, before } => self.write_table(&table, "undo", |txn| {
+                dynread::restore_image(txn, &table, &before, false)
+            }),
+            UndoRecord::Table {
+                table,
+                before: None,
+            } => self.write_table(&table, "undo", |txn| delete_in(txn, &table)),
+            UndoRecord::Table {
+                table,
+                before: Some(image),
+            } => self.write_table(&table, "undo", |txn| {
+                dynread::restore_image(txn, &table, &image, true)
+            }),
+        }
+    }
+    /// Copies table `name` of the current database into `other`, which is
+    /// created if it does not exist yet. The table is stored as `dest_name`
+    /// with the same key/value types. Returns the number of copied rows.
+    pub fn copy_table_to(
+        &self,
+        other: &str,
+        name: &str,
+        dest_name: &str,
+        mode: CopyMode,
+    ) -> Result<u64, Error> {
+        self.refuse_in_dry_run("copy-to")?;
+        let dst = Database::create(other)?;
+        with_snapshot!(self, |read_txn| copy_between(
+            read_txn, &dst, name, dest_name, mode
+        ))
+    }
+    /// Copies table `name` of the existing database `other` into the current
+    /// database as `dest_name`. Returns the number of copied rows.
+    pub fn copy_table_from(
+        &self,
+        other: &str,
+        name: &str,
+        dest_name: &str,
+        mode: CopyMode,
+    ) -> Result<u64, Error> {
+        if !Path::new(other).exists() {
+            return Err(Error::DatabaseNotFound(other.to_string()));
+        }
+        let src = Database::open(other)?;
+        let read_txn = src.begin_read()?;
+        let desc =
+            dynread::probe_table_type(&read_txn, name).map_err(|e| table_lookup_error(e, name))?;
+        self.write_table(dest_name, "copy-from", |txn| {
+            dynread::copy_table_dyn(&read_txn, txn, name, dest_name, &desc, mode)
+        })
+    }
+    /// Collects file level information and redb storage statistics. The
+    /// statistics come from a write transaction that is aborted right away,
+    /// so nothing is written; with a pinned snapshot the table counts are
+    /// taken from it, the storage statistics from the latest commit. While
+    /// a transaction is open the statistics are read from it, as a second
+    /// write transaction would wait for it forever.
+    pub fn db_info(&self) -> Result<DbInfo, Error> {
+        let db = self.handle()?;
+        let fresh;
+        let read_txn = match &self.snapshot {
+            Some(snapshot) => &snapshot.txn,
+            None => {
+                fresh = db.begin_read()?;
+                &fresh
+            }
+        };
+        let file_size = std::fs::metadata(&self.dbpath)?.len();
+        let tables = read_txn.list_tables()?.count();
+        let multimap_tables = read_txn.list_multimap_tables()?.count();
+        let stats = match &self.pending {
+            Some(pending) => pending.txn.stats()?,
+            None => {
+                let write_txn = db
+                    .writable()
+                    .map_err(|_| {
+                        Error::refused(
+                            "storage statistics need a writable open, 'info db' is not available in read-only mode"
+                                .to_string(),
+                        )
+                    })?
+                    .begin_write()?;
+                let stats = write_txn.stats()?;
+                write_txn.abort()?;
+                stats
+            }
+        };
+        Ok(DbInfo {
+            path: self.dbpath.clone(),
+            file_size,
+            tables,
+            multimap_tables,
+            last_transaction: fileheader::last_transaction_id(&self.dbpath).ok(),
+            page_size: stats.page_size(),
+            tree_height: stats.tree_height(),
+            allocated_pages: stats.allocated_pages(),
+            leaf_pages: stats.leaf_pages(),
+            branch_pages: stats.branch_pages(),
+            stored_bytes: stats.stored_bytes(),
+            metadata_bytes: stats.metadata_bytes(),
+            fragmented_bytes: stats.fragmented_bytes(),
+        })
+    }
+    /// Compacts the database file and reports its size before and after.
+    /// redb cannot compact while persistent savepoints exist, because they
+    /// pin the pages they reference; those must be deleted first.
+    pub fn compact(&mut self) -> Result<CompactReport, Error> {
+        self.refuse_in_dry_run("compact")?;
+        let size_before = std::fs::metadata(&self.dbpath)?.len();
+        let db = self.getdb_mut()?;
+        let savepoints = {
+            let write_txn = db.begin_write()?;
+            let count = write_txn.list_persistent_savepoints()?.count();
+            write_txn.abort()?;
+            count
+        };
+        if savepoints > 0 {
+            return Err(Error::refused(format!(
+                "cannot compact: {savepoints} persistent savepoint(s) exist and pin the pages they reference, delete them first"
+            )));
+        }
+        let started = std::time::Instant::now();
+        let compacted = db.compact()?;
+        let elapsed = started.elapsed();
+        // the file only shrinks to its final length once the handle is closed
+        self.reopen_db()?;
+        let size_after = std::fs::metadata(&self.dbpath)?.len();
+        Ok(CompactReport {
+            size_before,
+            size_after,
+            elapsed,
+            compacted,
+        })
+    }
+    /// Writes a consistent copy of the database into the new file `dest`:
+    /// every table is recreated with its probed types from one read
+    /// transaction (the pinned snapshot, if any) and committed at once.
+    /// Tables of undecodable types are copied as stored bytes; multimap
+    /// tables are left out and listed in the report. A failed backup
+    /// removes the partial file.
+    pub fn backup(&self, dest: &str, compact: bool) -> Result<BackupReport, Error> {
+        if Path::new(dest).exists() {
+            return Err(Error::refused(format!(
+                "'{dest}' already exists, backup only writes into a new database"
+            )));
+        }
+        let started = Instant::now();
+        let result = with_snapshot!(self, |read_txn| backup_into(read_txn, dest, compact));
+        match result {
+            Ok((tables, rows, skipped)) => Ok(BackupReport {
+                tables,
+                rows,
+                skipped,
+                size: std::fs::metadata(dest)?.len(),
+                elapsed: started.elapsed(),
+            }),
+            Err(e) => {
+                let _ = std::fs::remove_file(dest);
+                Err(e)
+            }
+        }
+    }
+    /// Copies everything that is still readable from the damaged database
+    /// `damaged` into the new database `dest`. Tables that cannot be probed
+    /// or read, and damaged key ranges, are skipped and listed in the report.
+    /// Each table is committed on its own, so one bad table does not lose
+    /// the others.
+    pub fn salvage(&self, damaged: &str, dest: &str) -> Result<SalvageReport, Error> {
+        // Panics caught while reading damaged pages are reported per table;
+        // keep the hook from printing a backtrace for each of them.
+        let _quiet = QuietPanics::new();
+        self.salvage_inner(damaged, dest)
+    }
+    fn salvage_inner(&self, damaged: &str, dest: &str) -> Result<SalvageReport, Error> {
+        if !Path::new(damaged).exists() {
+            return Err(Error::DatabaseNotFound(damaged.to_string()));
+        }
+        if Path::new(dest).exists() {
+            return Err(Error::refused(format!(
+                "'{dest}' already exists, salvage only writes into a new database"
+            )));
+        }
+        // The damaged file is evidence and is never written: it is read
+        // through a read-only open, or else a temporary copy is repaired.
+        // Damaged pages can make redb panic instead of returning an error,
+        // so every open is guarded.
+        let read_only = panic::catch_unwind(|| Builder::new().open_read_only(damaged));
+        // The opened handles (and the copy) must outlive the read transaction.
+        let (mut _read_only_db, mut _repaired_db, mut _repaired_copy) = (None, None, None);
+        let read_txn = match read_only {
+            Ok(Ok(db)) => _read_only_db.insert(db).begin_read()?,
+            failed => {
+                let read_only_err = match failed {
+                    Ok(Err(e)) => e.to_string(),
+                    _ => "panic while opening".to_string(),
+                };
+                let copy = tempfile::Builder::new()
+                    .prefix("redbcli-salvage-")
+                    .suffix(".redb")
+                    .tempfile()?
+                    .into_temp_path();
+                std::fs::copy(damaged, &copy)?;
+                let repaired = panic::catch_unwind(AssertUnwindSafe(|| self.builder().open(&copy)));
+                let cannot_open = |repair_err: String| {
+                    redb::Error::Corrupted(format!(
+                        "cannot open '{damaged}': {read_only_err}; repairing a copy failed too: {repair_err}"
+                    ))
+                };
+                let db = match repaired {
+                    Ok(opened) => opened.map_err(|e| cannot_open(e.to_string()))?,
+                    Err(_) => return Err(cannot_open("panic while repairing".to_string()).into()),
+                };
+                _repaired_copy = Some(copy);
+                _repaired_db.insert(db).begin_read()?
+            }
+        };
+        let dst = Database::create(dest)?;
+        let mut report = SalvageReport::default();
+
+        for handle in read_txn.list_multimap_tables()? {
+            report.tables.push(SalvagedTable {
+                name: handle.name().to_string(),
+                problem: Some("multimap tables are not supported".to_string()),
+                ..Default::default()
+            });
+        }
+        let names: Vec<String> = read_txn
+            .list_tables()?
+            .map(|table| table.name().to_string())
+            .collect();
+        for name in names {
+            let mut entry = SalvagedTable {
+                name: name.clone(),
+                ..Default::default()
+            };
+            let desc = match dynread::probe_table_type(&read_txn, &name) {
+                Ok(desc) => desc,
+                Err(e) => {
+                    entry.problem = Some(format!("cannot read table definition: {e}"));
+                    report.tables.push(entry);
+                    continue;
+                }
+            };
+            entry.key_type = desc.key.clone();
+            entry.value_type = desc.value.clone();
+            let write_txn = dst.begin_write()?;
+            match dynread::salvage_table_dyn(&read_txn, &write_txn, &name, &desc) {
+                Ok(outcome) => {
+                    write_txn.commit()?;
+                    entry.rows = outcome.copied;
+                    entry.problem = outcome.skipped;
+                }
+                Err(e) => {
+                    write_txn.abort()?;
+                    entry.problem = Some(e.to_string());
+                }
+            }
+            report.tables.push(entry);
+        }
+        Ok(report)
+    }
+    /// Creates a persistent savepoint of the current state and returns its id.
+    pub fn create_savepoint(&self) -> Result<u64, Error> {
+        self.refuse_in_dry_run("savepoint create")?;
         let db = self.getdb()?;
         let write_txn = db.begin_write()?;
-        match dynread::write_table_dyn(&write_txn, name, &desc, rows) {
-            Ok(()) => {
-                write_txn.commit()?;
-                Ok(())
+        let id = write_txn.persistent_savepoint()?;
+        write_txn.commit()?;
+        Ok(id)
+    }
+    /// Lists the persistent savepoints. redb only lists them from a write
+    /// transaction: the pending one if open, otherwise one that is aborted
+    /// right away, so this needs a writable open.
+    pub fn list_savepoints(&self) -> Result<Vec<u64>, Error> {
+        if let Some(pending) = &self.pending {
+            return Ok(pending.txn.list_persistent_savepoints()?.collect());
+        }
+        let write_txn = self
+            .handle()?
+            .writable()
+            .map_err(|_| {
+                Error::refused(
+                    "redb lists savepoints from a write transaction, 'savepoint list' is not available in read-only mode",
+                )
+            })?
+            .begin_write()?;
+        let ids = write_txn.list_persistent_savepoints()?.collect();
+        write_txn.abort()?;
+        Ok(ids)
+    }
+    /// Deletes a persistent savepoint; returns `false` if it did not exist.
+    pub fn delete_savepoint(&self, id: u64) -> Result<bool, Error> {
+        self.refuse_in_dry_run("savepoint delete")?;
+        let db = self.getdb()?;
+        let write_txn = db.begin_write()?;
+        let deleted = write_txn.delete_persistent_savepoint(id)?;
+        write_txn.commit()?;
+        Ok(deleted)
+    }
+    /// Lists what restoring savepoint `id` would change, per table. The
+    /// savepoint is restored inside a write transaction that is aborted
+    /// after reading, so nothing is modified.
+    pub fn savepoint_diff(&self, id: u64) -> Result<Vec<TableDiff>, Error> {
+        let db = self.getdb()?;
+        let current = {
+            let read_txn = db.begin_read()?;
+            snapshot_tables(&read_txn)?
+        };
+        let mut write_txn = db.begin_write()?;
+        let savepoint = write_txn.get_persistent_savepoint(id)?;
+        write_txn.restore_savepoint(&savepoint)?;
+        let restored = snapshot_tables(&write_txn)?;
+        write_txn.abort()?;
+        Ok(diff::diff_tables(current, restored))
+    }
+    /// Rolls the whole database back to savepoint `id`.
+    pub fn restore_savepoint(&self, id: u64) -> Result<(), Error> {
+        let db = self.getdb()?;
+        let current = if self.tracking() {
+            snapshot_tables(&db.begin_read()?)?
+        } else {
+            Vec::new()
+        };
+        let mut write_txn = db.begin_write()?;
+        let savepoint = write_txn.get_persistent_savepoint(id)?;
+        write_txn.restore_savepoint(&savepoint)?;
+        let mut entries = Vec::new();
+        if self.tracking() {
+            for table in diff::diff_tables(current, snapshot_tables(&write_txn)?) {
+                let changes = table.rows.into_iter().map(AuditChange::from).collect();
+                entries.push(AuditEntry::new(&table.name, "restore", changes));
             }
-            Err(e) => Err(Error::Corrupted(e)),
         }
+        if self.dry_run {
+            write_txn.abort()?;
+        } else {
+            write_txn.commit()?;
+        }
+        self.record(entries)
+    }
+    /// Computes the content digest of each table in `names` from a single
+    /// read transaction, so the digests describe one consistent state.
+    pub fn checksum_tables(&self, names: &[String]) -> Result<Vec<(String, TableDigest)>, Error> {
+        with_snapshot!(self, |read_txn| {
+            let mut result = Vec::with_capacity(names.len());
+            for name in names {
+                let desc = dynread::probe_table_type(read_txn, name)
+                    .map_err(|e| table_lookup_error(e, name))?;
+                let digest = checksum::table_digest(read_txn, name, &desc)?;
+                result.push((name.clone(), digest));
+            }
+            Ok(result)
+        })
+    }
+}
+
+/// Rows of table `name` as seen by `txn`; empty if it is missing.
+fn table_rows(txn: &impl RowSource, name: &str) -> Result<Vec<(String, String)>, Error> {
+    match txn.probe_type(name) {
+        Err(TableError::TableDoesNotExist(_)) => Ok(Vec::new()),
+        probed => dynread::read_table_dyn(txn, name, &probed?),
+    }
+}
+
+/// Deletes table `name` whatever its types; `Error::TableNotFound` if
+/// there is no such table.
+fn delete_in(txn: &WriteTransaction, name: &str) -> Result<(), Error> {
+    let handle: TableDefinition<&str, &str> = TableDefinition::new(name);
+    if txn.delete_table(handle)? {
+        Ok(())
+    } else {
+        Err(Error::TableNotFound(name.to_string()))
+    }
+}
+
+/// Reads the formatted rows of every table visible to `txn`.
+fn snapshot_tables(txn: &impl RowSource) -> Result<Vec<(String, TableRows)>, Error> {
+    let mut tables = Vec::new();
+    for name in txn.table_names()? {
+        let rows = txn
+            .probe_type(&name)
+            .map_err(Error::from)
+            .and_then(|desc| dynread::read_table_dyn(txn, &name, &desc))
+            .map_err(|e| e.to_string());
+        tables.push((name, rows));
     }
+    Ok(tables)
 }
+
+/// Recreates every table visible to `read_txn` in the new database `dest`.
+fn backup_into(
+    read_txn: &ReadTransaction,
+    dest: &str,
+    compact: bool,
+) -> Result<(usize, u64, Vec<String>), Error> {
+    let skipped = read_txn
+        .list_multimap_tables()?
+        .map(|handle| handle.name().to_string())
+        .collect();
+    let mut dst = Database::create(dest)?;
+    let write_txn = dst.begin_write()?;
+    let (mut tables, mut rows) = (0, 0);
+    for name in read_txn.table_names()? {
+        let desc =
+            dynread::probe_table_type(read_txn, &name).map_err(|e| table_lookup_error(e, &name))?;
+        rows += if desc.is_decodable() {
+            dynread::copy_table_dyn(read_txn, &write_txn, &name, &name, &desc, CopyMode::Replace)?
+        } else {
+            dynread::copy_stored_table(read_txn, &write_txn, &name)?
+        };
+        tables += 1;
+    }
+    write_txn.commit()?;
+    if compact {
+        dst.compact()?;
+    }
+    Ok((tables, rows, skipped))
+}
+
+fn copy_between(
+    read_txn: &ReadTransaction,
+    dst: &Database,
+    name: &str,
+    dest_name: &str,
+    mode: CopyMode,
+) -> Result<u64, Error> {
+    let desc =
+        dynread::probe_table_type(read_txn, name).map_err(|e| table_lookup_error(e, name))?;
+    let write_txn = dst.begin_write()?;
+    let copied = dynread::copy_table_dyn(read_txn, &write_txn, name, dest_name, &desc, mode)?;
+    write_txn.commit()?;
+    Ok(copied)
+}
+
 impl DealTable for CommonDbManager {
     fn create_table(&self, key: String) -> Result<(), Error> {
-        let db = self.getdb()?;
         let tabledefinition: TableDefinition<&str, &str> = TableDefinition::new(key.as_str());
-        let write_txn = db.begin_write()?;
-        {
+        self.write(|write_txn| {
             write_txn.open_table(tabledefinition)?;
-        }
-        write_txn.commit()?;
-        Ok(())
+            Ok(())
+        })?;
+        self.record(vec![AuditEntry::new(&key, "create", Vec::new())])
     }
 
     fn delete_table(&self, key: String) -> Result<(), Error> {
-        let db = self.getdb()?;
-        let write_txn = db.begin_write()?;
-        let tabledefinition: TableDefinition<&str, &str> = TableDefinition::new(key.as_str());
-        let result = write_txn.delete_table(tabledefinition);
-        write_txn.commit()?;
-        match result {
-            Ok(_) => Ok(()),
-            Err(e) => Err(Error::Corrupted(e.to_string())),
-        }
+        self.write_table(&key, "delete", |txn| delete_in(txn, &key))
     }
 
     fn list_table(&self) -> Result<Vec<String>, Error> {
-        let db = self.getdb()?;
-        let read_txn = db.begin_read()?;
-        let result: Vec<String> = read_txn
-            .list_tables()?
-            .map(|table| table.name().to_string())
-            .collect();
-
-        Ok(result)
+        with_reader!(self, |txn| Ok(txn.table_names()?))
     }
 }
 impl DealData for CommonDbManager {
     fn get_by_key(&self, key: String) -> Result<String, Error> {
-        let db: Database = self.getdb()?;
         let tabledefinition: TableDefinition<&str, &str> =
             TableDefinition::new(self.tablename.as_str());
-        let read_txn = db.begin_read()?;
-        let table = read_txn
-            .open_table(tabledefinition)
-            .map_err(|e| str_op_error(e, &self.tablename))?;
-        let binding = table.get(&key.as_str())?;
-        if let Some(binding) = binding {
-            let name_str = binding.value();
-            Ok(name_str.to_string())
-        } else {
-            Err(Error::Corrupted("Key not found".to_string()))
-        }
+        with_reader!(self, |txn| {
+            let table = txn
+                .open_table(tabledefinition)
+                .map_err(|e| str_op_error(e, &self.tablename))?;
+            let binding = table.get(&key.as_str())?;
+            if let Some(binding) = binding {
+                let name_str = binding.value();
+                Ok(name_str.to_string())
+            } else {
+                Err(Error::KeyNotFound(key))
+            }
+        })
     }
 
     fn insert_by_key(&self, key: String, data: String) -> Result<(), Error> {
-        let db = self.getdb()?;
         let tabledefinition: TableDefinition<&str, &str> =
             TableDefinition::new(self.tablename.as_str());
-        let write_txn = db.begin_write()?;
-        {
+        let before = self.write(|write_txn| {
             let mut table = write_txn
                 .open_table(tabledefinition)
                 .map_err(|e| str_op_error(e, &self.tablename))?;
-            table.insert(&key.as_str(), &data.as_str())?;
-        }
-        write_txn.commit()?;
-        Ok(())
+            let old = table.insert(&key.as_str(), &data.as_str())?;
+            Ok(old.map(|old| old.value().to_string()))
+        })?;
+        let change = AuditChange {
+            key,
+            before,
+            after: Some(data),
+        };
+        self.record(vec![AuditEntry::new(&self.tablename, "put", vec![change])])
     }
 
     fn get_all(&self) -> Result<HashMap<String, String>, Error> {
-        let db = self.getdb()?;
         let tabledefinition: TableDefinition<&str, &str> =
             TableDefinition::new(self.tablename.as_str());
-        let read_txn = db.begin_read()?;
-        let table = read_txn
-            .open_table(tabledefinition)
-            .map_err(|e| str_op_error(e, &self.tablename))?;
-        let mut result = HashMap::new();
-        let mut iter = table.range::<&str>(..)?;
-        while let Some((k, v)) = iter.next().transpose()? {
-            result.insert(k.value().to_string(), v.value().to_string());
-        }
-        Ok(result)
+        with_reader!(self, |txn| {
+            let table = txn
+                .open_table(tabledefinition)
+                .map_err(|e| str_op_error(e, &self.tablename))?;
+            let mut result = HashMap::new();
+            let mut iter = table.range::<&str>(..)?;
+            while let Some((k, v)) = iter.next().transpose()? {
+                result.insert(k.value().to_string(), v.value().to_string());
+            }
+            Ok(result)
+        })
     }
     fn remove_by_key(&self, key: String) -> Result<(), Error> {
-        let db = self.getdb()?;
         let tabledefinition: TableDefinition<&str, &str> =
             TableDefinition::new(self.tablename.as_str());
-        let write_txn = db.begin_write()?;
-        {
+        let before = self.write(|write_txn| {
             let mut table = write_txn
                 .open_table(tabledefinition)
                 .map_err(|e| str_op_error(e, &self.tablename))?;
-            table.remove(&key.as_str())?;
+            let old = table.remove(&key.as_str())?;
+            Ok(old.map(|old| old.value().to_string()))
+        })?;
+        if before.is_none() {
+            return Ok(());
         }
-        write_txn.commit()?;
-        Ok(())
+        let change = AuditChange {
+            key,
+            before,
+            after: None,
+        };
+        self.record(vec![AuditEntry::new(&self.tablename, "del", vec![change])])
     }
     fn update_by_key(&self, key: String, data: String) -> Result<(), Error> {
-        let db = self.getdb()?;
-        let tabledefinition: TableDefinition<&str, &str> =
-            TableDefinition::new(self.tablename.as_str());
-        let write_txn = db.begin_write()?;
-        {
-            let mut table = write_txn
-                .open_table(tabledefinition)
-                .map_err(|e| str_op_error(e, &self.tablename))?;
-            table.insert(&key.as_str(), &data.as_str())?;
-        }
-        write_txn.commit()?;
-        Ok(())
+        self.insert_by_key(key, data)
     }
 }
@@ -0,0 +1,1178 @@
+use crate::dynread::{
+    lead, parse_byte_list, parse_char_text, parse_hex_cell, parse_quoted_str, parse_raw_cell,
+    split_top_level, ParseError, TableTypeDesc,
+};
+use crate::dynvalue::DynValue;
+use crate::layout::{self, Layout};
+use crate::lens::Lenses;
+use redb::{Key, TableDefinition, TypeName, Value};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// A key or value type built from redb's built-in types, parsed from the
+/// type name redb persists for a table, ex: `(u64,&str,[u8;32])` or
+/// `Option<u32>`. It decodes and encodes redb's byte layout directly, so
+/// any nesting of built-in types can be read and written without a typed
+/// `TableDefinition` per combination. Application defined types are
+/// understood when a layout for their name is installed, see `layout`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeTree {
+    Unit,
+    Bool,
+    Char,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    F32,
+    F64,
+    /// `&str`
+    Str,
+    String,
+    /// `&[u8]`
+    Bytes,
+    Option(Box<TypeTree>),
+    /// `[T;N]`; `[u8;N]` and `&[u8;N]` share this name and layout.
+    Array(Box<TypeTree>, usize),
+    Vec(Box<TypeTree>),
+    Tuple(Vec<TypeTree>),
+    /// An application defined type described by an installed layout.
+    Layout(Arc<Layout>),
+    /// A type that cannot be decoded, handled as its stored bytes; `width`
+    /// is the fixed width redb persisted for it. Never produced by `parse`,
+    /// see `TableCodec::with_raw_fallback`.
+    Raw {
+        name: String,
+        width: Option<usize>,
+    },
+}
+
+impl fmt::Display for TypeTree {
+    /// Renders the name exactly as redb persists it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TypeTree::Unit => "()",
+            TypeTree::Bool => "bool",
+            TypeTree::Char => "char",
+            TypeTree::U8 => "u8",
+            TypeTree::U16 => "u16",
+            TypeTree::U32 => "u32",
+            TypeTree::U64 => "u64",
+            TypeTree::U128 => "u128",
+            TypeTree::I8 => "i8",
+            TypeTree::I16 => "i16",
+            TypeTree::I32 => "i32",
+            TypeTree::I64 => "i64",
+            TypeTree::I128 => "i128",
+            TypeTree::F32 => "f32",
+            TypeTree::F64 => "f64",
+            TypeTree::Str => "&str",
+            TypeTree::String => "String",
+            TypeTree::Bytes => "&[u8]",
+            TypeTree::Layout(layout) => &layout.name,
+            TypeTree::Raw { name, .. } => name,
+            TypeTree::Option(inner) => return write!(f, "Option<{inner}>"),
+            TypeTree::Array(inner, n) => return write!(f, "[{inner};{n}]"),
+            TypeTree::Vec(inner) => return write!(f, "Vec<{inner}>"),
+            TypeTree::Tuple(elems) if elems.len() == 1 => return write!(f, "({},)", elems[0]),
+            TypeTree::Tuple(elems) => {
+                f.write_str("(")?;
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{elem}")?;
+                }
+                return f.write_str(")");
+            }
+        };
+        f.write_str(name)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Type names
+// ---------------------------------------------------------------------------
+
+struct NameParser<'a> {
+    text: &'a str,
+    at: usize,
+    /// Whether names of installed layouts are recognized.
+    layouts: bool,
+}
+
+impl NameParser<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.at..]
+    }
+    fn skip_ws(&mut self) {
+        self.at += lead(self.rest());
+    }
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        if self.rest().starts_with(token) {
+            self.at += token.len();
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, token: &str) -> Result<(), ParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(ParseError::new(self.at, format!("expected '{token}'")))
+        }
+    }
+    fn word(&mut self) -> &str {
+        self.skip_ws();
+        let len = self
+            .rest()
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(self.rest().len());
+        let start = self.at;
+        self.at += len;
+        &self.text[start..self.at]
+    }
+
+    fn parse_type(&mut self) -> Result<TypeTree, ParseError> {
+        self.skip_ws();
+        if let Some(layout) = self
+            .layouts
+            .then(|| layout::match_prefix(self.rest()))
+            .flatten()
+        {
+            self.at += layout.name.len();
+            return Ok(TypeTree::Layout(layout));
+        }
+        if self.eat("&[u8]") {
+            return Ok(TypeTree::Bytes);
+        }
+        if self.eat("&str") {
+            return Ok(TypeTree::Str);
+        }
+        if self.eat("(") {
+            return self.parse_tuple();
+        }
+        if self.eat("[") {
+            let inner = self.parse_type()?;
+            self.expect(";")?;
+            let at = self.at;
+            let len = self
+                .word()
+                .parse::<usize>()
+                .map_err(|_| ParseError::new(at, "expected an array length"))?;
+            self.expect("]")?;
+            return Ok(TypeTree::Array(Box::new(inner), len));
+        }
+        let at = self.at + lead(self.rest());
+        let tree = match self.word() {
+            "bool" => TypeTree::Bool,
+            "char" => TypeTree::Char,
+            "u8" => TypeTree::U8,
+            "u16" => TypeTree::U16,
+            "u32" => TypeTree::U32,
+            "u64" => TypeTree::U64,
+            "u128" => TypeTree::U128,
+            "i8" => TypeTree::I8,
+            "i16" => TypeTree::I16,
+            "i32" => TypeTree::I32,
+            "i64" => TypeTree::I64,
+            "i128" => TypeTree::I128,
+            "f32" => TypeTree::F32,
+            "f64" => TypeTree::F64,
+            "String" => TypeTree::String,
+            "Option" => TypeTree::Option(Box::new(self.parse_generic()?)),
+            "Vec" => TypeTree::Vec(Box::new(self.parse_generic()?)),
+            "" => return Err(ParseError::new(at, "expected a type")),
+            other => {
+                return Err(ParseError::new(
+                    at,
+                    format!("'{other}' is not a redb built-in type"),
+                ))
+            }
+        };
+        Ok(tree)
+    }
+    fn parse_generic(&mut self) -> Result<TypeTree, ParseError> {
+        self.expect("<")?;
+        let inner = self.parse_type()?;
+        self.expect(">")?;
+        Ok(inner)
+    }
+    /// Parses the rest of a tuple after its '('; `(T,)` is a 1-tuple and
+    /// `()` the unit type.
+    fn parse_tuple(&mut self) -> Result<TypeTree, ParseError> {
+        if self.eat(")") {
+            return Ok(TypeTree::Unit);
+        }
+        let mut elems = vec![self.parse_type()?];
+        while self.eat(",") {
+            if self.eat(")") {
+                return Ok(TypeTree::Tuple(elems));
+            }
+            elems.push(self.parse_type()?);
+        }
+        self.expect(")")?;
+        if elems.len() == 1 {
+            return Err(ParseError::new(self.at, "a 1-tuple is written '(T,)'"));
+        }
+        Ok(TypeTree::Tuple(elems))
+    }
+}
+
+impl TypeTree {
+    /// Parses a type name as persisted by redb. Names of application
+    /// defined types (anything not built from redb's built-in types) are
+    /// rejected unless an installed layout describes them.
+    pub fn parse(name: &str) -> Result<TypeTree, ParseError> {
+        TypeTree::parse_with(name, true)
+    }
+
+    /// Like `parse`, ignoring installed layouts.
+    pub(crate) fn parse_builtin(name: &str) -> Result<TypeTree, ParseError> {
+        TypeTree::parse_with(name, false)
+    }
+
+    fn parse_with(name: &str, layouts: bool) -> Result<TypeTree, ParseError> {
+        let mut parser = NameParser {
+            text: name,
+            at: 0,
+            layouts,
+        };
+        let tree = parser.parse_type()?;
+        parser.skip_ws();
+        if !parser.rest().is_empty() {
+            return Err(ParseError::new(parser.at, "unexpected text after the type"));
+        }
+        Ok(tree)
+    }
+
+    /// Byte width of every encoded value, `None` for variable width types.
+    pub fn fixed_width(&self) -> Option<usize> {
+        match self {
+            TypeTree::Unit => Some(0),
+            TypeTree::Bool | TypeTree::U8 | TypeTree::I8 => Some(1),
+            TypeTree::U16 | TypeTree::I16 => Some(2),
+            TypeTree::Char => Some(3),
+            TypeTree::U32 | TypeTree::I32 | TypeTree::F32 => Some(4),
+            TypeTree::U64 | TypeTree::I64 | TypeTree::F64 => Some(8),
+            TypeTree::U128 | TypeTree::I128 => Some(16),
+            TypeTree::Str | TypeTree::String | TypeTree::Bytes | TypeTree::Vec(_) => None,
+            TypeTree::Option(inner) => inner.fixed_width().map(|w| w + 1),
+            TypeTree::Array(inner, n) => inner.fixed_width().map(|w| w * n),
+            TypeTree::Tuple(elems) => elems.iter().map(TypeTree::fixed_width).sum(),
+            TypeTree::Layout(layout) => layout.fixed_width(),
+            TypeTree::Raw { width, .. } => *width,
+        }
+    }
+
+    pub fn is_raw(&self) -> bool {
+        matches!(self, TypeTree::Raw { .. })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Byte layout
+// ---------------------------------------------------------------------------
+
+// Same varint as redb uses for tuple and Vec lengths: one byte below 254,
+// otherwise a 254/255 marker followed by a u16/u32.
+fn write_varint(len: usize, out: &mut Vec<u8>) -> Result<(), String> {
+    if len < 254 {
+        out.push(len as u8);
+    } else if let Ok(len) = u16::try_from(len) {
+        out.push(254);
+        out.extend_from_slice(&len.to_le_bytes());
+    } else {
+        let len = u32::try_from(len).map_err(|_| format!("length {len} does not fit a u32"))?;
+        out.push(255);
+        out.extend_from_slice(&len.to_le_bytes());
+    }
+    Ok(())
+}
+
+fn read_varint(data: &[u8]) -> Result<(usize, usize), String> {
+    match data.first() {
+        None => Err("truncated length".to_string()),
+        Some(&len @ 0..=253) => Ok((len as usize, 1)),
+        Some(254) => Ok((u16::from_le_bytes(exact(take(data, 1, 2)?)?) as usize, 3)),
+        Some(_) => Ok((u32::from_le_bytes(exact(take(data, 1, 4)?)?) as usize, 5)),
+    }
+}
+
+fn take(data: &[u8], start: usize, len: usize) -> Result<&[u8], String> {
+    start
+        .checked_add(len)
+        .and_then(|end| data.get(start..end))
+        .ok_or_else(|| format!("truncated data, {} bytes", data.len()))
+}
+
+fn exact<const N: usize>(data: &[u8]) -> Result<[u8; N], String> {
+    data.try_into()
+        .map_err(|_| format!("expected {N} bytes, got {}", data.len()))
+}
+
+/// Cuts a tuple encoding into its elements: fixed width tuples are plain
+/// concatenations, otherwise the lengths of the variable width elements
+/// but the last are stored up front as varints.
+fn tuple_parts<'d>(elems: &[TypeTree], data: &'d [u8]) -> Result<Vec<&'d [u8]>, String> {
+    if elems.len() == 1 {
+        return Ok(vec![data]);
+    }
+    let mut lens = Vec::with_capacity(elems.len() - 1);
+    let mut offset = 0;
+    let fixed = elems.iter().all(|elem| elem.fixed_width().is_some());
+    for elem in &elems[..elems.len() - 1] {
+        match elem.fixed_width() {
+            Some(width) => lens.push(width),
+            None => {
+                let (len, used) = read_varint(&data[offset..])?;
+                lens.push(len);
+                offset += used;
+            }
+        }
+    }
+    let mut parts = Vec::with_capacity(elems.len());
+    for len in lens {
+        parts.push(take(data, offset, len)?);
+        offset += len;
+    }
+    let last = elems[elems.len() - 1].fixed_width();
+    let rest = &data[offset.min(data.len())..];
+    if offset > data.len() || (fixed && Some(rest.len()) != last) {
+        return Err(format!("truncated tuple, {} bytes", data.len()));
+    }
+    parts.push(rest);
+    Ok(parts)
+}
+
+/// Cuts an array encoding into its `n` elements; variable width elements
+/// are preceded by a table of u32 end offsets.
+fn array_parts<'d>(inner: &TypeTree, n: usize, data: &'d [u8]) -> Result<Vec<&'d [u8]>, String> {
+    let mut parts = Vec::with_capacity(n);
+    match inner.fixed_width() {
+        Some(width) => {
+            if data.len() != width * n {
+                return Err(format!("expected {} bytes, got {}", width * n, data.len()));
+            }
+            for i in 0..n {
+                parts.push(&data[width * i..width * (i + 1)]);
+            }
+        }
+        None => {
+            let mut start = 4 * n;
+            for i in 0..n {
+                let end = u32::from_le_bytes(exact(take(data, 4 * i, 4)?)?) as usize;
+                parts.push(
+                    data.get(start..end)
+                        .ok_or_else(|| format!("invalid array offset {end}"))?,
+                );
+                start = end;
+            }
+        }
+    }
+    Ok(parts)
+}
+
+/// Cuts a Vec encoding into its elements: a varint count, then every
+/// element, variable width ones prefixed with their varint length.
+fn vec_parts<'d>(inner: &TypeTree, data: &'d [u8]) -> Result<Vec<&'d [u8]>, String> {
+    let (count, mut offset) = read_varint(data)?;
+    let mut parts = Vec::new();
+    for _ in 0..count {
+        let len = match inner.fixed_width() {
+            Some(width) => width,
+            None => {
+                let (len, used) = read_varint(&data[offset..])?;
+                offset += used;
+                len
+            }
+        };
+        parts.push(take(data, offset, len)?);
+        offset += len;
+    }
+    if offset != data.len() {
+        return Err(format!("{} trailing bytes", data.len() - offset));
+    }
+    Ok(parts)
+}
+
+macro_rules! le_scalars {
+    ($m:ident) => {
+        $m!(U8, u8);
+        $m!(U16, u16);
+        $m!(U32, u32);
+        $m!(U64, u64);
+        $m!(U128, u128);
+        $m!(I8, i8);
+        $m!(I16, i16);
+        $m!(I32, i32);
+        $m!(I64, i64);
+        $m!(I128, i128);
+        $m!(F32, f32);
+        $m!(F64, f64);
+    };
+}
+
+impl TypeTree {
+    /// Decodes the bytes redb stores for a value of this type.
+    pub fn decode(&self, data: &[u8]) -> Result<DynValue, String> {
+        macro_rules! decode_le {
+            ($variant:ident, $t:ty) => {
+                if let TypeTree::$variant = self {
+                    return Ok(DynValue::$variant(<$t>::from_le_bytes(exact(data)?)));
+                }
+            };
+        }
+        le_scalars!(decode_le);
+
+        Ok(match self {
+            TypeTree::Unit => DynValue::Unit,
+            TypeTree::Bool => match exact::<1>(data)? {
+                [0] => DynValue::Bool(false),
+                [1] => DynValue::Bool(true),
+                [b] => return Err(format!("invalid bool byte {b}")),
+            },
+            TypeTree::Char => {
+                let [a, b, c] = exact::<3>(data)?;
+                let code = u32::from_le_bytes([a, b, c, 0]);
+                DynValue::Char(
+                    char::from_u32(code).ok_or_else(|| format!("invalid char {code:#x}"))?,
+                )
+            }
+            TypeTree::Str | TypeTree::String => DynValue::Str(
+                std::str::from_utf8(data)
+                    .map_err(|e| format!("invalid UTF-8: {e}"))?
+                    .to_string(),
+            ),
+            TypeTree::Bytes => DynValue::Bytes(data.to_vec()),
+            TypeTree::Raw { .. } => DynValue::Raw(data.to_vec()),
+            TypeTree::Option(inner) => match data.first() {
+                Some(0) => DynValue::Option(None),
+                Some(1) => DynValue::Option(Some(Box::new(inner.decode(&data[1..])?))),
+                Some(tag) => return Err(format!("invalid Option tag {tag}")),
+                None => return Err("empty Option".to_string()),
+            },
+            TypeTree::Array(inner, n) => DynValue::List(
+                array_parts(inner, *n, data)?
+                    .into_iter()
+                    .map(|part| inner.decode(part))
+                    .collect::<Result<_, _>>()?,
+            ),
+            TypeTree::Vec(inner) => DynValue::List(
+                vec_parts(inner, data)?
+                    .into_iter()
+                    .map(|part| inner.decode(part))
+                    .collect::<Result<_, _>>()?,
+            ),
+            TypeTree::Tuple(elems) => DynValue::Tuple(
+                elems
+                    .iter()
+                    .zip(tuple_parts(elems, data)?)
+                    .map(|(elem, part)| elem.decode(part))
+                    .collect::<Result<_, _>>()?,
+            ),
+            TypeTree::Layout(layout) => layout.decode(data)?,
+            _ => unreachable!("scalars are decoded above"),
+        })
+    }
+
+    /// Encodes `value` in redb's layout for this type.
+    pub fn encode(&self, value: &DynValue) -> Result<Vec<u8>, String> {
+        macro_rules! encode_le {
+            ($variant:ident, $t:ty) => {
+                if let (TypeTree::$variant, DynValue::$variant(v)) = (self, value) {
+                    return Ok(v.to_le_bytes().to_vec());
+                }
+            };
+        }
+        le_scalars!(encode_le);
+
+        let mismatch = || format!("expected a {self}, got a {}", value.kind());
+        Ok(match (self, value) {
+            (TypeTree::Unit, DynValue::Unit) => Vec::new(),
+            (TypeTree::Bool, DynValue::Bool(b)) => vec![u8::from(*b)],
+            (TypeTree::Char, DynValue::Char(c)) => u32::from(*c).to_le_bytes()[..3].to_vec(),
+            (TypeTree::Str | TypeTree::String, DynValue::Str(s)) => s.as_bytes().to_vec(),
+            (TypeTree::Bytes, DynValue::Bytes(b)) => b.clone(),
+            (TypeTree::Raw { width, .. }, DynValue::Raw(b) | DynValue::Bytes(b)) => match width {
+                Some(width) if b.len() != *width => {
+                    return Err(format!("expected {width} bytes, got {}", b.len()))
+                }
+                _ => b.clone(),
+            },
+            (TypeTree::Option(inner), DynValue::Option(v)) => match v {
+                Some(v) => {
+                    let mut out = vec![1];
+                    out.extend(inner.encode(v)?);
+                    out
+                }
+                None => vec![0; 1 + inner.fixed_width().unwrap_or(0)],
+            },
+            (TypeTree::Array(inner, n), DynValue::List(items)) => {
+                if items.len() != *n {
+                    return Err(format!("expected {n} array elements, got {}", items.len()));
+                }
+                match inner.fixed_width() {
+                    Some(_) => {
+                        let mut out = Vec::new();
+                        for item in items {
+                            out.extend(inner.encode(item)?);
+                        }
+                        out
+                    }
+                    None => {
+                        let mut out = vec![0; 4 * n];
+                        for (i, item) in items.iter().enumerate() {
+                            out.extend(inner.encode(item)?);
+                            let end = u32::try_from(out.len())
+                                .map_err(|_| "array larger than 4 GiB".to_string())?;
+                            out[4 * i..4 * (i + 1)].copy_from_slice(&end.to_le_bytes());
+                        }
+                        out
+                    }
+                }
+            }
+            (TypeTree::Vec(inner), DynValue::List(items)) => {
+                let mut out = Vec::new();
+                write_varint(items.len(), &mut out)?;
+                for item in items {
+                    let bytes = inner.encode(item)?;
+                    if inner.fixed_width().is_none() {
+                        write_varint(bytes.len(), &mut out)?;
+                    }
+                    out.extend(bytes);
+                }
+                out
+            }
+            (TypeTree::Tuple(elems), DynValue::Tuple(items)) => {
+                if items.len() != elems.len() {
+                    return Err(format!(
+                        "expected {} tuple elements, got {}",
+                        elems.len(),
+                        items.len()
+                    ));
+                }
+                let parts = elems
+                    .iter()
+                    .zip(items)
+                    .map(|(elem, item)| elem.encode(item))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut out = Vec::new();
+                if self.fixed_width().is_none() {
+                    for (elem, part) in elems.iter().zip(&parts).take(elems.len() - 1) {
+                        if elem.fixed_width().is_none() {
+                            write_varint(part.len(), &mut out)?;
+                        }
+                    }
+                }
+                for part in parts {
+                    out.extend(part);
+                }
+                out
+            }
+            (TypeTree::Layout(layout), value) => layout.encode(value)?,
+            _ => return Err(mismatch()),
+        })
+    }
+
+    /// Orders two encoded keys the way redb's `Key::compare` does for this
+    /// type. Bytes that do not decode are ordered bytewise.
+    pub fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        self.try_compare(a, b).unwrap_or_else(|_| a.cmp(b))
+    }
+
+    fn try_compare(&self, a: &[u8], b: &[u8]) -> Result<Ordering, String> {
+        match self {
+            TypeTree::Unit => Ok(Ordering::Equal),
+            TypeTree::Str | TypeTree::String | TypeTree::Bytes | TypeTree::Raw { .. } => {
+                Ok(a.cmp(b))
+            }
+            TypeTree::Option(inner) => match (a.first(), b.first()) {
+                (None, _) | (_, None) => Err("empty Option".to_string()),
+                (Some(0), Some(0)) => Ok(Ordering::Equal),
+                (Some(0), _) => Ok(Ordering::Less),
+                (_, Some(0)) => Ok(Ordering::Greater),
+                _ => inner.try_compare(&a[1..], &b[1..]),
+            },
+            TypeTree::Array(inner, n) => elementwise(
+                &vec![&**inner; *n],
+                array_parts(inner, *n, a)?,
+                array_parts(inner, *n, b)?,
+            ),
+            TypeTree::Vec(inner) => {
+                let (pa, pb) = (vec_parts(inner, a)?, vec_parts(inner, b)?);
+                elementwise(&vec![&**inner; pa.len().max(pb.len())], pa, pb)
+            }
+            TypeTree::Tuple(elems) => elementwise(
+                &elems.iter().collect::<Vec<_>>(),
+                tuple_parts(elems, a)?,
+                tuple_parts(elems, b)?,
+            ),
+            TypeTree::Layout(layout) => layout.compare(a, b),
+            scalar => Ok(scalar_cmp(&scalar.decode(a)?, &scalar.decode(b)?)),
+        }
+    }
+}
+
+/// Compares element by element, then by element count (Vec).
+fn elementwise(elems: &[&TypeTree], a: Vec<&[u8]>, b: Vec<&[u8]>) -> Result<Ordering, String> {
+    for ((elem, x), y) in elems.iter().zip(&a).zip(&b) {
+        let order = elem.try_compare(x, y)?;
+        if order.is_ne() {
+            return Ok(order);
+        }
+    }
+    Ok(a.len().cmp(&b.len()))
+}
+
+pub(crate) fn scalar_cmp(a: &DynValue, b: &DynValue) -> Ordering {
+    match (a, b) {
+        (DynValue::U8(a), DynValue::U8(b)) => a.cmp(b),
+        (DynValue::U16(a), DynValue::U16(b)) => a.cmp(b),
+        (DynValue::U32(a), DynValue::U32(b)) => a.cmp(b),
+        (DynValue::U64(a), DynValue::U64(b)) => a.cmp(b),
+        (DynValue::U128(a), DynValue::U128(b)) => a.cmp(b),
+        (DynValue::I8(a), DynValue::I8(b)) => a.cmp(b),
+        (DynValue::I16(a), DynValue::I16(b)) => a.cmp(b),
+        (DynValue::I32(a), DynValue::I32(b)) => a.cmp(b),
+        (DynValue::I64(a), DynValue::I64(b)) => a.cmp(b),
+        (DynValue::I128(a), DynValue::I128(b)) => a.cmp(b),
+        (DynValue::F32(a), DynValue::F32(b)) => a.total_cmp(b),
+        (DynValue::F64(a), DynValue::F64(b)) => a.total_cmp(b),
+        (DynValue::Bool(a), DynValue::Bool(b)) => a.cmp(b),
+        (DynValue::Char(a), DynValue::Char(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Cell text
+// ---------------------------------------------------------------------------
+
+impl TypeTree {
+    /// Parses a standalone cell, the inverse of `DynValue`'s `Display`:
+    /// strings are raw text and bytes are `0x` hex.
+    pub fn parse_cell(&self, text: &str) -> Result<DynValue, ParseError> {
+        match self {
+            TypeTree::Str | TypeTree::String => Ok(DynValue::Str(text.to_string())),
+            TypeTree::Bytes => parse_hex_cell(text).map(DynValue::Bytes),
+            TypeTree::Raw { .. } => parse_raw_cell(text).map(DynValue::Raw),
+            _ => self.parse_elem(text),
+        }
+    }
+
+    /// Parses an element nested in a tuple, array or Option, which is
+    /// formatted like `Debug`: quoted strings and `[1, 2]` byte lists.
+    pub fn parse_elem(&self, text: &str) -> Result<DynValue, ParseError> {
+        let t = text.trim();
+        let at = lead(text);
+        macro_rules! parse_number {
+            ($variant:ident, $t:ty) => {
+                if let TypeTree::$variant = self {
+                    return t
+                        .parse::<$t>()
+                        .map(DynValue::$variant)
+                        .map_err(|_| ParseError::new(at, format!("expected {self}, got '{t}'")));
+                }
+            };
+        }
+        le_scalars!(parse_number);
+
+        match self {
+            TypeTree::Unit if t == "()" => Ok(DynValue::Unit),
+            TypeTree::Unit => Err(ParseError::new(at, format!("expected '()', got '{t}'"))),
+            TypeTree::Bool => t
+                .parse()
+                .map(DynValue::Bool)
+                .map_err(|_| ParseError::new(at, format!("expected bool, got '{t}'"))),
+            TypeTree::Char => parse_char_text(text).map(DynValue::Char),
+            TypeTree::Str | TypeTree::String => parse_quoted_str(text).map(DynValue::Str),
+            TypeTree::Bytes => parse_byte_list(text).map(DynValue::Bytes),
+            TypeTree::Option(_) if t == "None" => Ok(DynValue::Option(None)),
+            TypeTree::Option(inner) => {
+                let body = t
+                    .strip_prefix("Some(")
+                    .and_then(|x| x.strip_suffix(')'))
+                    .ok_or_else(|| {
+                        ParseError::new(at, format!("expected None or Some(..), got '{t}'"))
+                    })?;
+                let value = inner.parse_elem(body).map_err(|e| e.shifted(at + 5))?;
+                Ok(DynValue::Option(Some(Box::new(value))))
+            }
+            TypeTree::Array(inner, _) | TypeTree::Vec(inner) => {
+                let body = t
+                    .strip_prefix('[')
+                    .and_then(|x| x.strip_suffix(']'))
+                    .ok_or_else(|| ParseError::new(at, format!("expected [a, b], got '{t}'")))?;
+                let items = if body.trim().is_empty() {
+                    Vec::new()
+                } else {
+                    parse_elems(inner, at + 1, body)?
+                };
+                if let TypeTree::Array(_, n) = self {
+                    if items.len() != *n {
+                        return Err(ParseError::new(
+                            at,
+                            format!("expected {n} array elements, got {}", items.len()),
+                        ));
+                    }
+                }
+                Ok(DynValue::List(items))
+            }
+            TypeTree::Tuple(elems) => {
+                let body = t
+                    .strip_prefix('(')
+                    .and_then(|x| x.strip_suffix(')'))
+                    .ok_or_else(|| {
+                        ParseError::new(at, format!("expected tuple like (a, b), got '{t}'"))
+                    })?;
+                // a 1-tuple is written "(x,)"
+                let body = match (elems.len(), body.trim_end().strip_suffix(',')) {
+                    (1, Some(single)) => single,
+                    _ => body,
+                };
+                let parts = split_top_level(body).map_err(|e| e.shifted(at + 1))?;
+                if parts.len() != elems.len() {
+                    return Err(ParseError::new(
+                        at,
+                        format!(
+                            "expected {} tuple elements, got {} in '{t}'",
+                            elems.len(),
+                            parts.len()
+                        ),
+                    ));
+                }
+                let items = elems
+                    .iter()
+                    .zip(parts)
+                    .map(|(elem, (offset, part))| {
+                        elem.parse_elem(&part)
+                            .map_err(|e| e.shifted(at + 1 + offset))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(DynValue::Tuple(items))
+            }
+            TypeTree::Layout(layout) => layout.parse_text(text),
+            TypeTree::Raw { .. } => parse_raw_cell(text).map(DynValue::Raw),
+            _ => unreachable!("numbers are parsed above"),
+        }
+    }
+}
+
+fn parse_elems(inner: &TypeTree, at: usize, body: &str) -> Result<Vec<DynValue>, ParseError> {
+    split_top_level(body)
+        .map_err(|e| e.shifted(at))?
+        .into_iter()
+        .map(|(offset, part)| inner.parse_elem(&part).map_err(|e| e.shifted(at + offset)))
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Opening tables through the codec
+// ---------------------------------------------------------------------------
+
+/// Stand-in element type used to synthesize redb type names: it reports the
+/// name and width stored in `NAMED`, and redb's own `Option`, `Vec`, array
+/// and tuple impls wrap that into a name with the right classification.
+#[derive(Debug)]
+struct Named<const SLOT: usize>;
+
+thread_local! {
+    static NAMED: RefCell<[(String, Option<usize>); 2]> = const {
+        RefCell::new([(String::new(), None), (String::new(), None)])
+    };
+    static ACTIVE: RefCell<[Option<Rc<Column>>; 2]> = const { RefCell::new([None, None]) };
+}
+
+impl<const SLOT: usize> Value for Named<SLOT> {
+    type SelfType<'a> = ();
+    type AsBytes<'a> = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        NAMED.with(|named| named.borrow()[SLOT].1)
+    }
+    fn from_bytes<'a>(_data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+    }
+    fn as_bytes<'a, 'b: 'a>(_value: &'a Self::SelfType<'b>) -> &'a [u8]
+    where
+        Self: 'b,
+    {
+        &[]
+    }
+    fn type_name() -> TypeName {
+        TypeName::new(&NAMED.with(|named| named.borrow()[SLOT].0.clone()))
+    }
+}
+
+fn with_named<T: Value>(slots: [(String, Option<usize>); 2]) -> TypeName {
+    NAMED.with(|named| *named.borrow_mut() = slots);
+    T::type_name()
+}
+
+macro_rules! array_type_name {
+    ($n:expr, $($len:literal)*) => {
+        match $n {
+            $($len => Some(<[Named<0>; $len] as Value>::type_name()),)*
+            _ => None,
+        }
+    };
+}
+
+impl TypeTree {
+    /// The `TypeName` redb persists for this type. The classification is
+    /// not settable from outside redb, so composite names are produced by
+    /// redb's own impls over `Named` stand-ins. `None` for array lengths
+    /// that have no instantiation here.
+    fn redb_type_name(&self) -> Option<TypeName> {
+        let blank = || (String::new(), None);
+        Some(match self {
+            TypeTree::Unit => <()>::type_name(),
+            TypeTree::Bool => bool::type_name(),
+            TypeTree::Char => char::type_name(),
+            TypeTree::U8 => u8::type_name(),
+            TypeTree::U16 => u16::type_name(),
+            TypeTree::U32 => u32::type_name(),
+            TypeTree::U64 => u64::type_name(),
+            TypeTree::U128 => u128::type_name(),
+            TypeTree::I8 => i8::type_name(),
+            TypeTree::I16 => i16::type_name(),
+            TypeTree::I32 => i32::type_name(),
+            TypeTree::I64 => i64::type_name(),
+            TypeTree::I128 => i128::type_name(),
+            TypeTree::F32 => f32::type_name(),
+            TypeTree::F64 => f64::type_name(),
+            TypeTree::Str => <&str>::type_name(),
+            TypeTree::String => String::type_name(),
+            TypeTree::Bytes => <&[u8]>::type_name(),
+            TypeTree::Layout(layout) => TypeName::new(&layout.name),
+            TypeTree::Raw { name, .. } => TypeName::new(name),
+            TypeTree::Option(inner) => {
+                with_named::<Option<Named<0>>>([(inner.to_string(), None), blank()])
+            }
+            TypeTree::Vec(inner) => {
+                with_named::<Vec<Named<0>>>([(inner.to_string(), None), blank()])
+            }
+            TypeTree::Array(inner, n) => {
+                NAMED.with(|named| *named.borrow_mut() = [(inner.to_string(), None), blank()]);
+                array_type_name!(*n,
+                    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28
+                    29 30 31 32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53
+                    54 55 56 57 58 59 60 61 62 63 64 96 128 256 512 1024 2048 4096)?
+            }
+            TypeTree::Tuple(elems) if elems.len() == 1 => {
+                with_named::<(Named<0>,)>([(elems[0].to_string(), None), blank()])
+            }
+            TypeTree::Tuple(elems) => {
+                // redb joins the element names with ',' and marks variable
+                // width tuples apart, so a pair reproduces any arity
+                let rest: Vec<String> = elems[1..].iter().map(|e| e.to_string()).collect();
+                with_named::<(Named<0>, Named<1>)>([
+                    (elems[0].to_string(), self.fixed_width()),
+                    (rest.join(","), Some(0)),
+                ])
+            }
+        })
+    }
+}
+
+/// One side of a table opened through the codec.
+#[derive(Debug)]
+struct Column {
+    tree: TypeTree,
+    name: TypeName,
+    /// Position of each stored key, see `TableCodec::with_key_order`.
+    order: Option<Arc<HashMap<Vec<u8>, usize>>>,
+}
+
+fn active<R>(slot: usize, f: impl FnOnce(&Column) -> R) -> R {
+    let column = ACTIVE.with(|active| active.borrow()[slot].clone());
+    f(&column.expect("a codec table was used outside of its TableScope"))
+}
+
+/// Key/value type of tables opened through the codec. Its redb type name,
+/// width and ordering are those of the `TypeTree` installed by the current
+/// `TableScope`; values are the stored bytes.
+#[derive(Debug)]
+pub struct Runtime<const SLOT: usize>;
+
+pub type RuntimeKey = Runtime<0>;
+pub type RuntimeValue = Runtime<1>;
+
+impl<const SLOT: usize> Value for Runtime<SLOT> {
+    type SelfType<'a> = &'a [u8];
+    type AsBytes<'a> = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        active(SLOT, |column| column.tree.fixed_width())
+    }
+    fn from_bytes<'a>(data: &'a [u8]) -> &'a [u8]
+    where
+        Self: 'a,
+    {
+        data
+    }
+    fn as_bytes<'a, 'b: 'a>(value: &'a &'b [u8]) -> &'a [u8]
+    where
+        Self: 'b,
+    {
+        value
+    }
+    fn type_name() -> TypeName {
+        active(SLOT, |column| column.name.clone())
+    }
+}
+
+impl<const SLOT: usize> Key for Runtime<SLOT> {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        active(SLOT, |column| {
+            let positions = column
+                .order
+                .as_ref()
+                .and_then(|order| Some((order.get(data1)?, order.get(data2)?)));
+            match positions {
+                Some((a, b)) => a.cmp(b),
+                None => column.tree.compare(data1, data2),
+            }
+        })
+    }
+}
+
+/// Definition of table `name` read and written as raw bytes through the
+/// codec; only usable while a `TableScope` is entered.
+pub fn definition(name: &str) -> TableDefinition<'_, RuntimeKey, RuntimeValue> {
+    TableDefinition::new(name)
+}
+
+/// The key and value codecs of a table.
+#[derive(Debug, Clone)]
+pub struct TableCodec {
+    pub key: TypeTree,
+    pub value: TypeTree,
+    key_name: TypeName,
+    value_name: TypeName,
+    key_order: Option<Arc<HashMap<Vec<u8>, usize>>>,
+    /// Lenses of the key and value cells; one that does not fit its
+    /// side's type, ex: after a decoder was set, is dropped.
+    pub lenses: Lenses,
+}
+
+impl TableCodec {
+    /// `None` if a type is neither built from redb's built-in types nor
+    /// described by an installed layout.
+    pub fn new(desc: &TableTypeDesc) -> Option<Self> {
+        let key = TypeTree::parse(&desc.key).ok()?;
+        let mut value = TypeTree::parse(&desc.value).ok()?;
+        let value_name = value.redb_type_name()?;
+        // serialized documents in `&[u8]` values are decoded by the table's
+        // value codec, under the persisted `&[u8]` name
+        if let (TypeTree::Bytes, Some(codec)) = (&value, &desc.value_codec) {
+            value = TypeTree::Layout(codec.clone());
+        }
+        let lenses = Lenses {
+            key: desc.lenses.key.filter(|lens| lens.fits(&key)),
+            value: desc.lenses.value.filter(|lens| lens.fits(&value)),
+        };
+        Some(TableCodec {
+            key_name: key.redb_type_name()?,
+            value_name,
+            key,
+            value,
+            key_order: None,
+            lenses,
+        })
+    }
+
+    /// Like `new`, but a side whose type cannot be decoded is handled as
+    /// raw bytes (`TypeTree::Raw`) under its exact persisted `names` and
+    /// the fixed `widths` redb persisted for it.
+    pub fn with_raw_fallback(names: [TypeName; 2], widths: [Option<usize>; 2]) -> Self {
+        let side = |name: &TypeName, width| {
+            TypeTree::parse(name.name())
+                .ok()
+                .filter(|tree| tree.redb_type_name().as_ref() == Some(name))
+                .unwrap_or_else(|| TypeTree::Raw {
+                    name: name.name().to_string(),
+                    width,
+                })
+        };
+        let [key_name, value_name] = names;
+        TableCodec {
+            key: side(&key_name, widths[0]),
+            value: side(&value_name, widths[1]),
+            key_name,
+            value_name,
+            key_order: None,
+            lenses: Lenses::default(),
+        }
+    }
+
+    /// Orders keys by their position in `keys`, the table's stored keys in
+    /// stored order, falling back to the key type's order for other keys.
+    /// The order of raw keys is only known to the application, and redb
+    /// searches by it to find a key to remove.
+    pub fn with_key_order(mut self, keys: impl IntoIterator<Item = Vec<u8>>) -> Self {
+        let order = keys.into_iter().enumerate().map(|(i, key)| (key, i));
+        self.key_order = Some(Arc::new(order.collect()));
+        self
+    }
+
+    /// Whether the key or value is handled as raw bytes.
+    pub fn is_raw(&self) -> bool {
+        self.key.is_raw() || self.value.is_raw()
+    }
+
+    /// Installs the types for `RuntimeKey`/`RuntimeValue` on this thread
+    /// until the returned scope is dropped; tables opened with `definition`
+    /// must not outlive it.
+    pub fn enter(&self) -> TableScope {
+        let column = |tree: &TypeTree, name: &TypeName, order| {
+            Some(Rc::new(Column {
+                tree: tree.clone(),
+                name: name.clone(),
+                order,
+            }))
+        };
+        let installed = [
+            column(&self.key, &self.key_name, self.key_order.clone()),
+            column(&self.value, &self.value_name, None),
+        ];
+        let previous =
+            ACTIVE.with(|active| std::mem::replace(&mut *active.borrow_mut(), installed));
+        TableScope { previous }
+    }
+}
+
+/// Keeps the types of a `TableCodec` installed, see `TableCodec::enter`.
+pub struct TableScope {
+    previous: [Option<Rc<Column>>; 2],
+}
+
+impl Drop for TableScope {
+    fn drop(&mut self) {
+        let previous = std::mem::take(&mut self.previous);
+        ACTIVE.with(|active| *active.borrow_mut() = previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynread::type_name_of;
+
+    fn tree(name: &str) -> TypeTree {
+        TypeTree::parse(name).unwrap()
+    }
+
+    fn roundtrip<T: Value + 'static>(value: T::SelfType<'_>) -> DynValue {
+        let name = type_name_of::<T>();
+        let tree = tree(&name);
+        assert_eq!(tree.to_string(), name);
+        assert_eq!(tree.fixed_width(), T::fixed_width(), "{name}");
+        assert_eq!(tree.redb_type_name(), Some(T::type_name()), "{name}");
+        let bytes = T::as_bytes(&value).as_ref().to_vec();
+        let decoded = tree.decode(&bytes).unwrap();
+        assert_eq!(tree.encode(&decoded).unwrap(), bytes, "{name}");
+        let text = decoded.to_string();
+        assert_eq!(tree.parse_cell(&text).unwrap(), decoded, "{name}: {text}");
+        decoded
+    }
+
+    #[test]
+    fn names_parse_and_render_like_redb() {
+        assert_eq!(
+            tree("(u64,&str,[u8;32])"),
+            TypeTree::Tuple(vec![
+                TypeTree::U64,
+                TypeTree::Str,
+                TypeTree::Array(Box::new(TypeTree::U8), 32)
+            ])
+        );
+        assert_eq!(
+            tree("Option<u32>"),
+            TypeTree::Option(Box::new(TypeTree::U32))
+        );
+        assert_eq!(tree("(u8,)").to_string(), "(u8,)");
+        assert_eq!(tree("()"), TypeTree::Unit);
+        assert!(TypeTree::parse("app::Record").is_err());
+        assert!(TypeTree::parse("(u8").is_err());
+        assert!(TypeTree::parse("u64 x").is_err());
+    }
+
+    #[test]
+    fn layouts_match_redb() {
+        assert_eq!(roundtrip::<u64>(7), DynValue::U64(7));
+        assert_eq!(roundtrip::<char>('é'), DynValue::Char('é'));
+        roundtrip::<f64>(-1.5);
+        roundtrip::<&[u8]>(&[0, 1, 255]);
+        roundtrip::<String>("text".to_string());
+        let mixed = roundtrip::<(u64, &str, [u8; 4])>((9, "a, b", [1, 2, 3, 4]));
+        assert_eq!(mixed.to_string(), "(9, \"a, b\", [1, 2, 3, 4])");
+        roundtrip::<(&str, u16, &[u8])>(("x", 3, &[7, 8]));
+        roundtrip::<(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, bool, char)>((
+            1, 2, 3, 4, 5, -1, -2, -3, -4, -5, true, 'z',
+        ));
+        assert_eq!(roundtrip::<Option<u32>>(None).to_string(), "None");
+        assert_eq!(
+            roundtrip::<Option<&str>>(Some("q")).to_string(),
+            "Some(\"q\")"
+        );
+        roundtrip::<Option<(u8, &str)>>(Some((1, "w")));
+        roundtrip::<[&str; 3]>(["a", "", "ccc"]);
+        roundtrip::<Vec<u32>>(vec![1, 2, 3]);
+        roundtrip::<Vec<&str>>(vec!["long".repeat(100).as_str(), "b"]);
+        roundtrip::<Vec<Option<u8>>>(vec![Some(1), None]);
+        roundtrip::<(u64,)>((5,));
+        roundtrip::<((u8, u8), Vec<(u64, &str)>)>(((1, 2), vec![(3, "c")]));
+    }
+
+    #[test]
+    fn compare_matches_redb() {
+        fn check<K: Key + 'static>(values: &[K::SelfType<'_>]) {
+            let tree = tree(&type_name_of::<K>());
+            for a in values {
+                for b in values {
+                    let (a, b) = (K::as_bytes(a), K::as_bytes(b));
+                    let (a, b) = (a.as_ref(), b.as_ref());
+                    assert_eq!(tree.compare(a, b), K::compare(a, b));
+                }
+            }
+        }
+        check::<i32>(&[-5, 0, 7]);
+        check::<(u8, &str)>(&[(1, "b"), (1, "ab"), (0, "z")]);
+        check::<Option<i64>>(&[None, Some(-1), Some(3)]);
+        check::<[&str; 2]>(&[["a", "b"], ["a", "a"], ["", "z"]]);
+        check::<(&str, &str, u8)>(&[("a", "b", 1), ("a", "b", 0), ("b", "", 0)]);
+    }
+
+    #[test]
+    fn cells_check_shape_and_type() {
+        let t = tree("(u64,Option<char>,[u8;2])");
+        assert_eq!(
+            t.parse_cell("(1, Some('x'), [3, 4])").unwrap().to_string(),
+            "(1, Some('x'), [3, 4])"
+        );
+        assert_eq!(
+            t.parse_cell("(1, None, [3, 4])").unwrap().to_string(),
+            "(1, None, [3, 4])"
+        );
+        assert_eq!(t.parse_cell("(1, x, [3, 4])").unwrap_err().position, 4);
+        assert!(t.parse_cell("(1, None, [3])").is_err());
+        let err = tree("u8").encode(&DynValue::U64(1)).unwrap_err();
+        assert_eq!(err, "expected a u8, got a u64");
+    }
+}
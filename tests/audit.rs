@@ -0,0 +1,132 @@
+mod common;
+
+use common::NotUtf8;
+use redb::{Database, TableDefinition};
+use redbcli::audit::Journal;
+use redbcli::redbcontrol::{CommonDbManager, DealData, DealTable};
+use std::path::{Path, PathBuf};
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager, PathBuf) {
+    let (dir, mut dbm) = common::make_db("audit.redb", |txn| {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        t.insert(1u64, "one").unwrap();
+        t.insert(2u64, "two").unwrap();
+    });
+    let journal_path = dir.path().join("audit.log");
+    let mut journal = Journal::open(&journal_path).unwrap();
+    journal.set_command("put 1 uno");
+    dbm.audit = Some(journal);
+    (dir, dbm, journal_path)
+}
+
+fn entries(path: &Path) -> Vec<serde_json::Value> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn put_and_del_record_before_and_after() {
+    let (_dir, dbm, journal) = make_db();
+    dbm.put_dyn("by_id", "1".into(), "uno".into()).unwrap();
+    dbm.remove_dyn("by_id", "2".into()).unwrap();
+    // deleting a missing key changes nothing and is not journaled
+    dbm.remove_dyn("by_id", "42".into()).unwrap();
+
+    let entries = entries(&journal);
+    assert_eq!(entries.len(), 2);
+    let put = &entries[0];
+    assert_eq!(put["action"], "put");
+    assert_eq!(put["table"], "by_id");
+    assert_eq!(put["command"], "put 1 uno");
+    assert!(put["db"].as_str().unwrap().ends_with("audit.redb"));
+    assert!(put["time"].as_str().unwrap().ends_with('Z'));
+    assert!(!put["user"].as_str().unwrap().is_empty());
+    assert_eq!(
+        put["changes"],
+        serde_json::json!([{ "key": "1", "before": "one", "after": "uno" }])
+    );
+    assert_eq!(
+        entries[1]["changes"],
+        serde_json::json!([{ "key": "2", "before": "two", "after": null }])
+    );
+}
+
+#[test]
+fn table_writes_record_row_diffs() {
+    let (_dir, mut dbm, journal) = make_db();
+    dbm.update_all_dyn(
+        "by_id",
+        vec![("1".into(), "one".into()), ("3".into(), "three".into())],
+    )
+    .unwrap();
+    dbm.create_table("names".into()).unwrap();
+    dbm.settablename("names".into()).unwrap();
+    dbm.insert_by_key("a".into(), "b".into()).unwrap();
+    dbm.delete_table("by_id".into()).unwrap();
+
+    let entries = entries(&journal);
+    let actions: Vec<&str> = entries
+        .iter()
+        .map(|e| e["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, vec!["edit", "create", "put", "delete"]);
+    assert_eq!(
+        entries[0]["changes"],
+        serde_json::json!([
+            { "key": "2", "before": "two", "after": null },
+            { "key": "3", "before": null, "after": "three" },
+        ])
+    );
+    assert_eq!(entries[3]["changes"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn transaction_is_journaled_on_commit_only() {
+    let (_dir, mut dbm, journal) = make_db();
+    dbm.begin_transaction().unwrap();
+    dbm.put_dyn("by_id", "5".into(), "five".into()).unwrap();
+    dbm.rollback_transaction().unwrap();
+    assert!(entries(&journal).is_empty());
+
+    dbm.begin_transaction().unwrap();
+    dbm.put_dyn("by_id", "5".into(), "five".into()).unwrap();
+    dbm.put_dyn("by_id", "6".into(), "six".into()).unwrap();
+    assert!(entries(&journal).is_empty());
+    dbm.commit_transaction().unwrap();
+    assert_eq!(entries(&journal).len(), 2);
+}
+
+#[test]
+fn unreadable_rows_are_journaled_as_not_captured() {
+    let (dir, dbm, journal) = make_db();
+    drop(dbm);
+    let path = dir.path().join("audit.redb");
+    let db = Database::open(&path).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let table: TableDefinition<u64, NotUtf8> = TableDefinition::new("broken");
+        txn.open_table(table)
+            .unwrap()
+            .insert(1, [0xff, 0xfe].as_slice())
+            .unwrap();
+    }
+    txn.commit().unwrap();
+    drop(db);
+    let mut dbm = CommonDbManager::default();
+    dbm.setdbpath(path.to_string_lossy().to_string()).unwrap();
+    dbm.audit = Some(Journal::open(&journal).unwrap());
+
+    dbm.delete_table("broken".into()).unwrap();
+    let entries = entries(&journal);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["action"], "delete");
+    assert_eq!(entries[0]["changes"], serde_json::json!([]));
+    let note = entries[0]["note"].as_str().unwrap();
+    assert!(note.starts_with("changes not captured: "), "{note}");
+    assert!(!dbm.list_table().unwrap().contains(&"broken".to_string()));
+}
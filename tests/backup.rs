@@ -0,0 +1,120 @@
+mod common;
+
+use common::Record;
+use redb::{Database, MultimapTableDefinition, ReadableDatabase, ReadableTable, TableDefinition};
+use redbcli::redbcontrol::{CommonDbManager, DealTable};
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+const FOUND_KEYS: TableDefinition<(u128, u128), u64> = TableDefinition::new("found_keys");
+const RECORDS: TableDefinition<u64, Record> = TableDefinition::new("records");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("backup.redb", |txn| {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        for i in 0..100u64 {
+            t.insert(i, "value").unwrap();
+        }
+        let mut t = txn.open_table(FOUND_KEYS).unwrap();
+        t.insert((1u128, 2u128), 100u64).unwrap();
+    })
+}
+
+fn open_copy(path: &std::path::Path) -> CommonDbManager {
+    let mut copy = CommonDbManager::default();
+    copy.setdbpath(path.to_string_lossy().to_string()).unwrap();
+    copy
+}
+
+#[test]
+fn backup_recreates_every_table_with_its_types() {
+    let (dir, dbm) = make_db();
+    let dest = dir.path().join("copy.redb");
+    let report = dbm.backup(dest.to_str().unwrap(), false).unwrap();
+    assert_eq!(report.tables, 2);
+    assert_eq!(report.rows, 101);
+    assert!(report.size > 0);
+
+    let copy = open_copy(&dest);
+    for table in ["by_id", "found_keys"] {
+        assert_eq!(
+            copy.get_all_dyn(table).unwrap(),
+            dbm.get_all_dyn(table).unwrap()
+        );
+    }
+    let names = vec!["by_id".to_string(), "found_keys".to_string()];
+    assert_eq!(
+        copy.checksum_tables(&names).unwrap(),
+        dbm.checksum_tables(&names).unwrap()
+    );
+}
+
+#[test]
+fn backup_can_compact_and_refuses_existing_dest() {
+    let (dir, dbm) = make_db();
+    let dest = dir.path().join("compacted.redb");
+    let report = dbm.backup(dest.to_str().unwrap(), true).unwrap();
+    assert_eq!(report.size, std::fs::metadata(&dest).unwrap().len());
+    assert_eq!(open_copy(&dest).get_all_dyn("by_id").unwrap().1.len(), 100);
+
+    let err = dbm.backup(dest.to_str().unwrap(), false).unwrap_err();
+    assert!(err.to_string().contains("already exists"));
+}
+
+#[test]
+fn backup_leaves_out_multimap_tables() {
+    let (dir, mut dbm) = make_db();
+    let path = dir.path().join("multimap.redb");
+    let db = Database::create(&path).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let table: MultimapTableDefinition<u64, u64> = MultimapTableDefinition::new("tags");
+        txn.open_multimap_table(table)
+            .unwrap()
+            .insert(1, 2)
+            .unwrap();
+        txn.open_table(BY_ID).unwrap().insert(1, "one").unwrap();
+    }
+    txn.commit().unwrap();
+    drop(db);
+    dbm.setdbpath(path.to_string_lossy().to_string()).unwrap();
+
+    let dest = dir.path().join("without_tags.redb");
+    let report = dbm.backup(dest.to_str().unwrap(), false).unwrap();
+    assert_eq!(report.skipped, ["tags"]);
+    assert_eq!((report.tables, report.rows), (1, 1));
+    assert_eq!(open_copy(&dest).list_table().unwrap(), ["by_id"]);
+}
+
+#[test]
+fn tables_of_app_types_are_copied_as_stored() {
+    let (dir, mut dbm) = make_db();
+    let path = dir.path().join("records.redb");
+    let db = Database::create(&path).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut t = txn.open_table(RECORDS).unwrap();
+        for i in [30u64, 10, 20] {
+            t.insert(i, i * 2).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+    drop(db);
+    dbm.setdbpath(path.to_string_lossy().to_string()).unwrap();
+
+    let dest = dir.path().join("records_copy.redb");
+    let report = dbm.backup(dest.to_str().unwrap(), false).unwrap();
+    assert_eq!((report.tables, report.rows), (1, 3));
+
+    let db = Database::open(&dest).unwrap();
+    let txn = db.begin_read().unwrap();
+    let records = txn.open_table(RECORDS).unwrap();
+    let rows: Vec<(u64, u64)> = records
+        .iter()
+        .unwrap()
+        .map(|row| {
+            let (k, v) = row.unwrap();
+            (k.value(), v.value())
+        })
+        .collect();
+    assert_eq!(rows, [(10, 20), (20, 40), (30, 60)]);
+}
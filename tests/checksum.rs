@@ -0,0 +1,111 @@
+use redb::{Database, TableDefinition};
+use redbcli::checksum;
+use redbcli::redbcontrol::{CommonDbManager, DealTable};
+
+const FOUND_KEYS: TableDefinition<(u128, u128), u64> = TableDefinition::new("found_keys");
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+const BY_ID_U32: TableDefinition<u32, &str> = TableDefinition::new("by_id");
+
+fn make_db(dir: &tempfile::TempDir, file: &str) -> CommonDbManager {
+    let path = dir.path().join(file);
+    let db = Database::create(&path).expect("create db failed");
+
+    let txn = db.begin_write().expect("begin write failed");
+    {
+        let mut t = txn.open_table(FOUND_KEYS).unwrap();
+        t.insert((1u128, 2u128), 100u64).unwrap();
+        t.insert((u128::MAX, 7u128), 42u64).unwrap();
+    }
+    {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        t.insert(1u64, "one").unwrap();
+        t.insert(2u64, "two").unwrap();
+    }
+    txn.commit().unwrap();
+    drop(db);
+
+    let mut dbm = CommonDbManager::default();
+    dbm.setdbpath(path.to_string_lossy().to_string())
+        .expect("set db path failed");
+    dbm
+}
+
+fn all_tables(dbm: &CommonDbManager) -> Vec<String> {
+    let mut names = dbm.list_table().unwrap();
+    names.sort();
+    names
+}
+
+#[test]
+fn identical_databases_hash_equal() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = make_db(&dir, "a.redb");
+    let b = make_db(&dir, "b.redb");
+    let da = a.checksum_tables(&all_tables(&a)).unwrap();
+    let db = b.checksum_tables(&all_tables(&b)).unwrap();
+    assert_eq!(da, db);
+    assert_eq!(checksum::overall_digest(&da), checksum::overall_digest(&db));
+}
+
+#[test]
+fn checksum_is_stable_across_runs() {
+    let dir = tempfile::tempdir().unwrap();
+    let dbm = make_db(&dir, "a.redb");
+    let names = vec!["found_keys".to_string()];
+    assert_eq!(
+        dbm.checksum_tables(&names).unwrap(),
+        dbm.checksum_tables(&names).unwrap()
+    );
+}
+
+#[test]
+fn changed_value_changes_only_that_table() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = make_db(&dir, "a.redb");
+    let b = make_db(&dir, "b.redb");
+    b.update_all_dyn(
+        "by_id",
+        vec![
+            ("1".to_string(), "one".to_string()),
+            ("2".to_string(), "TWO".to_string()),
+        ],
+    )
+    .unwrap();
+    let da = a.checksum_tables(&all_tables(&a)).unwrap();
+    let db = b.checksum_tables(&all_tables(&b)).unwrap();
+    assert_ne!(da[0], db[0]);
+    assert_eq!(da[1], db[1]);
+    assert_ne!(checksum::overall_digest(&da), checksum::overall_digest(&db));
+}
+
+#[test]
+fn checksum_covers_type_descriptor() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = make_db(&dir, "a.redb");
+    let path = dir.path().join("c.redb");
+    {
+        let db = Database::create(&path).unwrap();
+        let txn = db.begin_write().unwrap();
+        {
+            let mut t = txn.open_table(BY_ID_U32).unwrap();
+            t.insert(1u32, "one").unwrap();
+            t.insert(2u32, "two").unwrap();
+        }
+        txn.commit().unwrap();
+    }
+    let mut c = CommonDbManager::default();
+    c.setdbpath(path.to_string_lossy().to_string()).unwrap();
+    let names = vec!["by_id".to_string()];
+    assert_ne!(
+        a.checksum_tables(&names).unwrap(),
+        c.checksum_tables(&names).unwrap()
+    );
+}
+
+#[test]
+fn checksum_missing_table_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let dbm = make_db(&dir, "a.redb");
+    let err = dbm.checksum_tables(&["nope".to_string()]).unwrap_err();
+    assert!(err.to_string().contains("does not exist"), "got: {err}");
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use redb::TableDefinition;
+use redbcli::dynread::{self, ColumnQuery};
+use redbcli::lens::{Lens, Lenses};
+use redbcli::redbcontrol::CommonDbManager;
+use redbcli::Error;
+use std::ops::ControlFlow;
+
+const INDEX: TableDefinition<(u64, u64, u64), (u32, &str)> = TableDefinition::new("index");
+const PLAIN: TableDefinition<u64, u64> = TableDefinition::new("plain");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("columns.redb", |txn| {
+        let mut t = txn.open_table(INDEX).unwrap();
+        t.insert((1, 20, 300), (3, "c")).unwrap();
+        t.insert((1, 10, 100), (1, "a")).unwrap();
+        t.insert((2, 10, 200), (2, "b")).unwrap();
+        let mut t = txn.open_table(PLAIN).unwrap();
+        t.insert(7, 255).unwrap();
+    })
+}
+
+fn rows(dbm: &CommonDbManager, name: &str, query: &ColumnQuery) -> Result<Vec<Vec<String>>, Error> {
+    let desc = dbm.table_type(name)?;
+    let mut rows = Vec::new();
+    dbm.visit_columns_dyn(name, &desc, query, &mut |row| {
+        rows.push(row);
+        ControlFlow::Continue(())
+    })?;
+    Ok(rows)
+}
+
+#[test]
+fn tuple_elements_get_their_own_columns() {
+    let (_dir, dbm) = make_db();
+    let expand = ColumnQuery {
+        expand: true,
+        ..Default::default()
+    };
+    let desc = dbm.table_type("index").unwrap();
+    assert_eq!(
+        dynread::column_names(&desc, true),
+        ["key.0", "key.1", "key.2", "value.0", "value.1"]
+    );
+    assert_eq!(dynread::column_names(&desc, false), ["key", "value"]);
+    let index = rows(&dbm, "index", &expand).unwrap();
+    assert_eq!(index[0], ["1", "10", "100", "1", "a"]);
+    assert_eq!(index.len(), 3);
+
+    let whole = rows(&dbm, "index", &ColumnQuery::default()).unwrap();
+    assert_eq!(whole[0], ["(1, 10, 100)", "(1, \"a\")"]);
+
+    // other types keep a single column
+    let plain = dbm.table_type("plain").unwrap();
+    assert_eq!(dynread::column_names(&plain, true), ["key", "value"]);
+    assert_eq!(rows(&dbm, "plain", &expand).unwrap(), [["7", "255"]]);
+}
+
+#[test]
+fn rows_are_sorted_and_filtered_on_one_element() {
+    let (_dir, mut dbm) = make_db();
+    let query = ColumnQuery {
+        expand: true,
+        sort: Some("key.2".into()),
+        descending: true,
+        ..Default::default()
+    };
+    let third: Vec<String> = rows(&dbm, "index", &query)
+        .unwrap()
+        .into_iter()
+        .map(|row| row[2].clone())
+        .collect();
+    assert_eq!(third, ["300", "200", "100"]);
+
+    // equal cells keep their key order
+    let query = ColumnQuery {
+        sort: Some("key.1".into()),
+        ..Default::default()
+    };
+    let keys: Vec<String> = rows(&dbm, "index", &query)
+        .unwrap()
+        .into_iter()
+        .map(|row| row[0].clone())
+        .collect();
+    assert_eq!(keys, ["(1, 10, 100)", "(2, 10, 200)", "(1, 20, 300)"]);
+
+    let query = ColumnQuery {
+        expand: true,
+        filter: Some(("key.1".into(), "10".into())),
+        ..Default::default()
+    };
+    assert_eq!(rows(&dbm, "index", &query).unwrap().len(), 2);
+    let query = ColumnQuery {
+        filter: Some(("value.1".into(), "b".into())),
+        ..Default::default()
+    };
+    assert_eq!(
+        rows(&dbm, "index", &query).unwrap(),
+        [["(2, 10, 200)", "(2, \"b\")"]]
+    );
+
+    // a whole column is filtered in the form of its lens
+    dbm.set_lenses(
+        "plain",
+        Lenses {
+            key: None,
+            value: Some(Lens::Hex),
+        },
+    )
+    .unwrap();
+    let query = ColumnQuery {
+        filter: Some(("value".into(), "0xff".into())),
+        ..Default::default()
+    };
+    assert_eq!(rows(&dbm, "plain", &query).unwrap(), [["7", "0xff"]]);
+}
+
+#[test]
+fn unknown_columns_and_cells_are_rejected() {
+    let (_dir, dbm) = make_db();
+    let query = ColumnQuery {
+        sort: Some("key.3".into()),
+        ..Default::default()
+    };
+    let err = rows(&dbm, "index", &query).unwrap_err();
+    assert!(matches!(err, Error::Refused(_)), "{err}");
+    assert!(err.to_string().contains("key.0, key.1, key.2"), "{err}");
+
+    let query = ColumnQuery {
+        filter: Some(("key.0".into(), "x".into())),
+        ..Default::default()
+    };
+    let err = rows(&dbm, "index", &query).unwrap_err();
+    assert!(matches!(err, Error::Parse { .. }), "{err}");
+}
@@ -0,0 +1,96 @@
+// Not every test crate uses every fixture.
+#![allow(dead_code)]
+
+use redb::{Database, WriteTransaction};
+use redbcli::redbcontrol::CommonDbManager;
+
+/// Creates database `file` in a new temp dir, fills it with `seed` in one
+/// write transaction and points a manager at it.
+pub fn make_db(
+    file: &str,
+    seed: impl FnOnce(&WriteTransaction),
+) -> (tempfile::TempDir, CommonDbManager) {
+    let dir = tempfile::tempdir().expect("create temp dir failed");
+    let path = dir.path().join(file);
+    let db = Database::create(&path).expect("create db failed");
+
+    let txn = db.begin_write().expect("begin write failed");
+    seed(&txn);
+    txn.commit().unwrap();
+    drop(db);
+
+    let mut dbm = CommonDbManager::default();
+    dbm.setdbpath(path.to_string_lossy().to_string())
+        .expect("set db path failed");
+    (dir, dbm)
+}
+
+/// The rows of `table` as cell text.
+pub fn rows_of(dbm: &CommonDbManager, table: &str) -> Vec<(String, String)> {
+    dbm.get_all_dyn(table).unwrap().1
+}
+
+/// An application defined value type, which has no built-in codec.
+#[derive(Debug)]
+pub struct Record;
+
+impl redb::Value for Record {
+    type SelfType<'a> = u64;
+    type AsBytes<'a> = [u8; 8];
+
+    fn fixed_width() -> Option<usize> {
+        Some(8)
+    }
+    fn from_bytes<'a>(data: &'a [u8]) -> u64
+    where
+        Self: 'a,
+    {
+        u64::from_le_bytes(data.try_into().unwrap())
+    }
+    fn as_bytes<'a, 'b: 'a>(value: &'a u64) -> [u8; 8]
+    where
+        Self: 'b,
+    {
+        value.to_le_bytes()
+    }
+    fn type_name() -> redb::TypeName {
+        redb::TypeName::new("app::Record")
+    }
+}
+
+/// Stores any bytes under the type name of `&str`, so the table claims a
+/// built-in type its rows do not hold.
+#[derive(Debug)]
+pub struct NotUtf8;
+
+impl redb::Value for NotUtf8 {
+    type SelfType<'a> = &'a [u8];
+    type AsBytes<'a> = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+    fn from_bytes<'a>(data: &'a [u8]) -> &'a [u8]
+    where
+        Self: 'a,
+    {
+        data
+    }
+    fn as_bytes<'a, 'b: 'a>(value: &'a &'b [u8]) -> &'a [u8]
+    where
+        Self: 'b,
+    {
+        value
+    }
+    fn type_name() -> redb::TypeName {
+        redb::TypeName::new("&str")
+    }
+}
+
+// Ordered as numbers, which differs from the order of the little endian
+// bytes, so only the application knows how stored keys are ordered.
+impl redb::Key for Record {
+    fn compare(data1: &[u8], data2: &[u8]) -> std::cmp::Ordering {
+        <Self as redb::Value>::from_bytes(data1).cmp(&<Self as redb::Value>::from_bytes(data2))
+    }
+}
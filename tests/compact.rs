@@ -0,0 +1,75 @@
+use redb::{Database, ReadableDatabase, TableDefinition};
+use redbcli::redbcontrol::CommonDbManager;
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    let dir = tempfile::tempdir().expect("create temp dir failed");
+    let path = dir.path().join("compact.redb");
+    let db = Database::create(&path).expect("create db failed");
+
+    let value = "x".repeat(1000);
+    let txn = db.begin_write().expect("begin write failed");
+    {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        for i in 0..2000u64 {
+            t.insert(i, value.as_str()).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+    let txn = db.begin_write().expect("begin write failed");
+    {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        for i in 10..2000u64 {
+            t.remove(i).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+    drop(db);
+
+    let mut dbm = CommonDbManager::default();
+    dbm.setdbpath(path.to_string_lossy().to_string())
+        .expect("set db path failed");
+    (dir, dbm)
+}
+
+#[test]
+fn compact_shrinks_file_and_keeps_data() {
+    let (_dir, mut dbm) = make_db();
+    let report = dbm.compact().unwrap();
+    assert!(report.compacted);
+    assert!(
+        report.size_after < report.size_before,
+        "{} should be smaller than {}",
+        report.size_after,
+        report.size_before
+    );
+    assert_eq!(
+        report.size_after,
+        std::fs::metadata(&dbm.dbpath).unwrap().len()
+    );
+    let (_desc, rows) = dbm.get_all_dyn("by_id").unwrap();
+    assert_eq!(rows.len(), 10);
+}
+
+#[test]
+fn compact_refuses_with_persistent_savepoint() {
+    let (_dir, mut dbm) = make_db();
+    dbm.close_db().unwrap();
+    {
+        let db = Database::open(&dbm.dbpath).unwrap();
+        let txn = db.begin_write().unwrap();
+        txn.persistent_savepoint().unwrap();
+        txn.commit().unwrap();
+    }
+    dbm.open_db().unwrap();
+    let size_before = std::fs::metadata(&dbm.dbpath).unwrap().len();
+    let err = dbm.compact().unwrap_err();
+    assert!(err.to_string().contains("savepoint"), "got: {err}");
+    assert_eq!(std::fs::metadata(&dbm.dbpath).unwrap().len(), size_before);
+
+    dbm.close_db().unwrap();
+    let db = Database::open(&dbm.dbpath).unwrap();
+    let txn = db.begin_read().unwrap();
+    assert!(txn.open_table(BY_ID).is_ok());
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::Record;
+use redb::{Database, ReadableDatabase, ReadableTableMetadata, TableDefinition};
+use redbcli::dynread::CopyMode;
+use redbcli::redbcontrol::CommonDbManager;
+
+const FOUND_KEYS: TableDefinition<(u128, u128), u64> = TableDefinition::new("found_keys");
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+const RECORDS: TableDefinition<u64, Record> = TableDefinition::new("records");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("source.redb", |txn| {
+        let mut t = txn.open_table(FOUND_KEYS).unwrap();
+        t.insert((1u128, 2u128), 100u64).unwrap();
+        t.insert((u128::MAX, 7u128), 42u64).unwrap();
+        let mut t = txn.open_table(BY_ID).unwrap();
+        t.insert(1u64, "one").unwrap();
+        t.insert(2u64, "two").unwrap();
+        let mut t = txn.open_table(RECORDS).unwrap();
+        t.insert(5u64, 9u64).unwrap();
+    })
+}
+
+fn other_path(dir: &tempfile::TempDir) -> String {
+    dir.path().join("other.redb").to_string_lossy().to_string()
+}
+
+#[test]
+fn copy_to_creates_database_and_keeps_types() {
+    let (dir, dbm) = make_db();
+    let other = other_path(&dir);
+    let copied = dbm
+        .copy_table_to(&other, "found_keys", "found_keys", CopyMode::Replace)
+        .unwrap();
+    assert_eq!(copied, 2);
+
+    let db = Database::open(&other).unwrap();
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(FOUND_KEYS).unwrap();
+    assert_eq!(table.len().unwrap(), 2);
+    assert_eq!(table.get((1u128, 2u128)).unwrap().unwrap().value(), 100);
+    assert_eq!(table.get((u128::MAX, 7u128)).unwrap().unwrap().value(), 42);
+}
+
+#[test]
+fn copy_to_renames_destination_table() {
+    let (dir, dbm) = make_db();
+    let other = other_path(&dir);
+    dbm.copy_table_to(&other, "by_id", "ids_copy", CopyMode::Replace)
+        .unwrap();
+
+    let db = Database::open(&other).unwrap();
+    let txn = db.begin_read().unwrap();
+    let table = txn
+        .open_table(TableDefinition::<u64, &str>::new("ids_copy"))
+        .unwrap();
+    assert_eq!(table.get(2u64).unwrap().unwrap().value(), "two");
+}
+
+#[test]
+fn copy_replace_drops_existing_rows() {
+    let (dir, dbm) = make_db();
+    let other = other_path(&dir);
+    {
+        let db = Database::create(&other).unwrap();
+        let txn = db.begin_write().unwrap();
+        txn.open_table(BY_ID).unwrap().insert(9u64, "nine").unwrap();
+        txn.commit().unwrap();
+    }
+    dbm.copy_table_to(&other, "by_id", "by_id", CopyMode::Replace)
+        .unwrap();
+
+    let db = Database::open(&other).unwrap();
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(BY_ID).unwrap();
+    assert_eq!(table.len().unwrap(), 2);
+    assert!(table.get(9u64).unwrap().is_none());
+}
+
+#[test]
+fn copy_merge_keeps_existing_rows() {
+    let (dir, dbm) = make_db();
+    let other = other_path(&dir);
+    {
+        let db = Database::create(&other).unwrap();
+        let txn = db.begin_write().unwrap();
+        {
+            let mut t = txn.open_table(BY_ID).unwrap();
+            t.insert(1u64, "uno").unwrap();
+            t.insert(9u64, "nine").unwrap();
+        }
+        txn.commit().unwrap();
+    }
+    dbm.copy_table_to(&other, "by_id", "by_id", CopyMode::Merge)
+        .unwrap();
+
+    let db = Database::open(&other).unwrap();
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(BY_ID).unwrap();
+    assert_eq!(table.len().unwrap(), 3);
+    assert_eq!(table.get(1u64).unwrap().unwrap().value(), "one");
+    assert_eq!(table.get(9u64).unwrap().unwrap().value(), "nine");
+}
+
+#[test]
+fn copy_merge_rejects_type_mismatch() {
+    let (dir, dbm) = make_db();
+    let other = other_path(&dir);
+    {
+        let db = Database::create(&other).unwrap();
+        let txn = db.begin_write().unwrap();
+        txn.open_table(TableDefinition::<&str, &str>::new("by_id"))
+            .unwrap();
+        txn.commit().unwrap();
+    }
+    let err = dbm
+        .copy_table_to(&other, "by_id", "by_id", CopyMode::Merge)
+        .unwrap_err();
+    assert!(err.to_string().contains("by_id"), "got: {err}");
+}
+
+#[test]
+fn copy_from_pulls_table_into_current_database() {
+    let (dir, dbm) = make_db();
+    let other = other_path(&dir);
+    dbm.copy_table_to(&other, "found_keys", "exported", CopyMode::Replace)
+        .unwrap();
+    let copied = dbm
+        .copy_table_from(&other, "exported", "imported", CopyMode::Replace)
+        .unwrap();
+    assert_eq!(copied, 2);
+    let (desc, rows) = dbm.get_all_dyn("imported").unwrap();
+    assert_eq!(desc.key, "(u128,u128)");
+    assert_eq!(rows, dbm.get_all_dyn("found_keys").unwrap().1);
+}
+
+#[test]
+fn copy_from_missing_database_fails() {
+    let (dir, dbm) = make_db();
+    let err = dbm
+        .copy_table_from(&other_path(&dir), "by_id", "by_id", CopyMode::Replace)
+        .unwrap_err();
+    assert!(err.to_string().contains("not found"), "got: {err}");
+}
+
+#[test]
+fn copy_unsupported_type_reports_error() {
+    let (dir, dbm) = make_db();
+    let err = dbm
+        .copy_table_to(&other_path(&dir), "records", "records", CopyMode::Replace)
+        .unwrap_err();
+    assert!(err.to_string().contains("cannot be copied"), "got: {err}");
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use redb::{MultimapTableDefinition, TableDefinition};
+use redbcli::redbcontrol::{CommonDbManager, DealTable};
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+const MM_TABLE: MultimapTableDefinition<&str, u64> = MultimapTableDefinition::new("tags");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("info.redb", |txn| {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        for i in 0..100u64 {
+            t.insert(i, "some value").unwrap();
+        }
+        let mut t = txn.open_multimap_table(MM_TABLE).unwrap();
+        t.insert("k", 1u64).unwrap();
+    })
+}
+
+#[test]
+fn db_info_reports_file_and_tables() {
+    let (_dir, dbm) = make_db();
+    let info = dbm.db_info().unwrap();
+    assert_eq!(info.path, dbm.dbpath);
+    assert_eq!(
+        info.file_size,
+        std::fs::metadata(&dbm.dbpath).unwrap().len()
+    );
+    assert_eq!(info.tables, 1);
+    assert_eq!(info.multimap_tables, 1);
+}
+
+#[test]
+fn db_info_reports_storage_stats() {
+    let (_dir, dbm) = make_db();
+    let info = dbm.db_info().unwrap();
+    assert!(info.page_size > 0);
+    assert!(info.tree_height > 0);
+    assert!(info.stored_bytes > 0);
+    assert!(info.allocated_pages >= info.leaf_pages);
+}
+
+#[test]
+fn db_info_tracks_last_committed_transaction() {
+    let (_dir, dbm) = make_db();
+    let before = dbm.db_info().unwrap().last_transaction.unwrap();
+    dbm.create_table("another".to_string()).unwrap();
+    let after = dbm.db_info().unwrap().last_transaction.unwrap();
+    assert!(after > before, "{after} should be newer than {before}");
+}
+
+#[test]
+fn db_info_reads_statistics_from_the_open_transaction() {
+    let (_dir, mut dbm) = make_db();
+    dbm.begin_transaction().unwrap();
+    dbm.create_table("pending".to_string()).unwrap();
+    let info = dbm.db_info().unwrap();
+    assert!(info.stored_bytes > 0);
+    // the table counts come from the last commit
+    assert_eq!(info.tables, 1);
+    dbm.commit_transaction().unwrap();
+    assert_eq!(dbm.db_info().unwrap().tables, 2);
+}
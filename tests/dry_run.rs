@@ -0,0 +1,103 @@
+mod common;
+
+use common::{rows_of, NotUtf8};
+use redb::{Database, TableDefinition};
+use redbcli::redbcontrol::{CommonDbManager, DealTable, UndoRecord};
+use redbcli::Error;
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    let (dir, mut dbm) = common::make_db("dry_run.redb", |txn| {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        t.insert(1u64, "one").unwrap();
+        t.insert(2u64, "two").unwrap();
+    });
+    dbm.set_dry_run(true).unwrap();
+    (dir, dbm)
+}
+
+#[test]
+fn writes_are_previewed_not_committed() {
+    let (_dir, dbm) = make_db();
+    let before = rows_of(&dbm, "by_id");
+
+    dbm.put_dyn("by_id", "1".into(), "uno".into()).unwrap();
+    dbm.update_all_dyn("by_id", vec![("3".into(), "three".into())])
+        .unwrap();
+    dbm.delete_table("by_id".into()).unwrap();
+    dbm.create_table("fresh".into()).unwrap();
+    assert_eq!(rows_of(&dbm, "by_id"), before);
+    assert_eq!(dbm.list_table().unwrap(), vec!["by_id".to_string()]);
+
+    let preview = dbm.take_preview();
+    let actions: Vec<&str> = preview.iter().map(|e| e.action.as_str()).collect();
+    assert_eq!(actions, vec!["put", "edit", "delete", "create"]);
+    assert_eq!(preview[0].changes[0].before.as_deref(), Some("one"));
+    assert_eq!(preview[0].changes[0].after.as_deref(), Some("uno"));
+    // edit replaces both rows with one new row
+    assert_eq!(preview[1].changes.len(), 3);
+    assert_eq!(preview[2].changes.len(), 2);
+    assert!(dbm.take_preview().is_empty());
+}
+
+#[test]
+fn validation_still_runs_in_dry_run() {
+    let (_dir, dbm) = make_db();
+    assert!(dbm.put_dyn("by_id", "x".into(), "bad".into()).is_err());
+    assert!(dbm
+        .update_all_dyn(
+            "by_id",
+            vec![("1".into(), "one".into()), ("y".into(), "bad".into())]
+        )
+        .is_err());
+    assert!(dbm.take_preview().is_empty());
+}
+
+#[test]
+fn undo_and_unpreviewable_commands() {
+    let (_dir, mut dbm) = make_db();
+    dbm.undo(UndoRecord::Table {
+        table: "by_id".into(),
+        before: None,
+    })
+    .unwrap();
+    assert_eq!(rows_of(&dbm, "by_id").len(), 2);
+    assert_eq!(dbm.take_preview()[0].action, "undo");
+
+    assert!(dbm.compact().is_err());
+    assert!(dbm.create_savepoint().is_err());
+    assert!(dbm.begin_transaction().is_err());
+
+    dbm.set_dry_run(false).unwrap();
+    dbm.begin_transaction().unwrap();
+    assert!(dbm.set_dry_run(true).is_err());
+    dbm.rollback_transaction().unwrap();
+}
+
+#[test]
+fn writes_whose_rows_cannot_be_read_are_refused() {
+    let (dir, dbm) = make_db();
+    drop(dbm);
+    let path = dir.path().join("dry_run.redb");
+    let db = Database::open(&path).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let table: TableDefinition<u64, NotUtf8> = TableDefinition::new("broken");
+        txn.open_table(table)
+            .unwrap()
+            .insert(1, [0xff].as_slice())
+            .unwrap();
+    }
+    txn.commit().unwrap();
+    drop(db);
+    let mut dbm = CommonDbManager::default();
+    dbm.setdbpath(path.to_string_lossy().to_string()).unwrap();
+    dbm.set_dry_run(true).unwrap();
+
+    let err = dbm.delete_table("broken".into()).unwrap_err();
+    assert!(matches!(err, Error::Refused(_)), "{err}");
+    assert!(err.to_string().contains("cannot be previewed"), "{err}");
+    assert!(dbm.take_preview().is_empty());
+    assert!(dbm.list_table().unwrap().contains(&"broken".to_string()));
+}
@@ -0,0 +1,129 @@
+use redb::{Database, ReadableDatabase, TableDefinition};
+use redbcli::{DynTable, DynValue, Error};
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+const PAIRS: TableDefinition<(u32, u32), f64> = TableDefinition::new("pairs");
+const BLOBS: TableDefinition<&str, &[u8]> = TableDefinition::new("blobs");
+
+fn make_db() -> (tempfile::TempDir, Database) {
+    let dir = tempfile::tempdir().expect("create temp dir failed");
+    let db = Database::create(dir.path().join("dyn.redb")).expect("create db failed");
+    let txn = db.begin_write().expect("begin write failed");
+    {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        for (id, name) in [(1u64, "one"), (2, "two"), (3, "three"), (4, "four")] {
+            t.insert(id, name).unwrap();
+        }
+        txn.open_table(PAIRS)
+            .unwrap()
+            .insert((1u32, 2u32), 0.5)
+            .unwrap();
+        txn.open_table(BLOBS)
+            .unwrap()
+            .insert("k", &[0xde, 0xad][..])
+            .unwrap();
+    }
+    txn.commit().unwrap();
+    (dir, db)
+}
+
+#[test]
+fn reads_typed_values_by_table_name() {
+    let (_dir, db) = make_db();
+    let txn = db.begin_read().unwrap();
+
+    let by_id = DynTable::open(&txn, "by_id").unwrap();
+    assert_eq!(by_id.types().key, "u64");
+    assert_eq!(
+        by_id.get(&DynValue::U64(2)).unwrap(),
+        Some(DynValue::Str("two".into()))
+    );
+    assert_eq!(by_id.get(&DynValue::U64(9)).unwrap(), None);
+    let keys: Vec<DynValue> = by_id
+        .range(&DynValue::U64(2)..&DynValue::U64(4))
+        .unwrap()
+        .into_iter()
+        .map(|(k, _)| k)
+        .collect();
+    assert_eq!(keys, vec![DynValue::U64(2), DynValue::U64(3)]);
+    assert_eq!(by_id.range(..).unwrap().len(), 4);
+
+    let pairs = DynTable::open(&txn, "pairs").unwrap();
+    let key = DynValue::Tuple(vec![DynValue::U32(1), DynValue::U32(2)]);
+    assert_eq!(pairs.get(&key).unwrap(), Some(DynValue::F64(0.5)));
+
+    let blobs = DynTable::open(&txn, "blobs").unwrap();
+    assert_eq!(
+        blobs.get(&DynValue::Str("k".into())).unwrap(),
+        Some(DynValue::Bytes(vec![0xde, 0xad]))
+    );
+}
+
+#[test]
+fn inserts_and_removes_in_a_write_transaction() {
+    let (_dir, db) = make_db();
+    let txn = db.begin_write().unwrap();
+    {
+        let by_id = DynTable::open(&txn, "by_id").unwrap();
+        let old = by_id
+            .insert(&DynValue::U64(1), &DynValue::Str("uno".into()))
+            .unwrap();
+        assert_eq!(old, Some(DynValue::Str("one".into())));
+        assert_eq!(
+            by_id.remove(&DynValue::U64(4)).unwrap(),
+            Some(DynValue::Str("four".into()))
+        );
+        assert_eq!(by_id.remove(&DynValue::U64(4)).unwrap(), None);
+        // reads see the uncommitted writes of the same transaction
+        assert_eq!(by_id.range(..).unwrap().len(), 3);
+    }
+    txn.commit().unwrap();
+
+    let read = db.begin_read().unwrap();
+    let table = read.open_table(BY_ID).unwrap();
+    assert_eq!(table.get(1u64).unwrap().unwrap().value(), "uno");
+    assert!(table.get(4u64).unwrap().is_none());
+}
+
+#[test]
+fn mismatched_values_and_missing_tables_are_rejected() {
+    let (_dir, db) = make_db();
+    let txn = db.begin_write().unwrap();
+    let by_id = DynTable::open(&txn, "by_id").unwrap();
+    let err = by_id
+        .insert(&DynValue::Str("1".into()), &DynValue::Str("x".into()))
+        .unwrap_err();
+    assert!(matches!(err, Error::Parse { .. }), "{err}");
+    assert!(matches!(
+        DynTable::open(&txn, "nope"),
+        Err(Error::TableNotFound(_))
+    ));
+}
+
+#[test]
+fn display_and_parse_round_trip() {
+    let (_dir, db) = make_db();
+    let txn = db.begin_read().unwrap();
+    for name in ["by_id", "pairs", "blobs"] {
+        let table = DynTable::open(&txn, name).unwrap();
+        for (key, value) in table.range(..).unwrap() {
+            assert_eq!(table.parse_key(&key.to_string()).unwrap(), key);
+            assert_eq!(table.parse_value(&value.to_string()).unwrap(), value);
+        }
+    }
+    let triple = DynValue::parse(r#"("a, b", "c", "")"#, "(&str,&str,&str)").unwrap();
+    assert_eq!(
+        triple,
+        DynValue::Tuple(vec![
+            DynValue::Str("a, b".into()),
+            DynValue::Str("c".into()),
+            DynValue::Str(String::new()),
+        ])
+    );
+    assert_eq!(
+        DynValue::parse(&triple.to_string(), "(&str,&str,&str)").unwrap(),
+        triple
+    );
+    let err = DynValue::parse("(1, x)", "(u64,u64)").unwrap_err();
+    assert!(matches!(err, Error::Parse { position: 4, .. }), "{err}");
+}
@@ -1,3 +1,6 @@
+mod common;
+
+use common::Record;
 use redb::{Database, ReadableDatabase, TableDefinition};
 use redbcli::redbcontrol::CommonDbManager;
 
@@ -7,7 +10,7 @@ const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
 const BLOBS: TableDefinition<u32, &[u8]> = TableDefinition::new("blobs");
 const STR_PAIRS: TableDefinition<(&str, &str), &str> = TableDefinition::new("str_pairs");
 const FLOATS: TableDefinition<u64, f64> = TableDefinition::new("floats");
-const OPT_VALS: TableDefinition<u64, Option<u64>> = TableDefinition::new("opt_vals");
+const RECORDS: TableDefinition<u64, Record> = TableDefinition::new("records");
 
 fn make_db() -> (tempfile::TempDir, CommonDbManager) {
     let dir = tempfile::tempdir().expect("create temp dir failed");
@@ -44,8 +47,8 @@ fn make_db() -> (tempfile::TempDir, CommonDbManager) {
         t.insert(1u64, 3.5f64).unwrap();
     }
     {
-        let mut t = txn.open_table(OPT_VALS).unwrap();
-        t.insert(5u64, Some(9u64)).unwrap();
+        let mut t = txn.open_table(RECORDS).unwrap();
+        t.insert(5u64, 9u64).unwrap();
     }
     txn.commit().unwrap();
     drop(db);
@@ -219,18 +222,19 @@ fn edit_out_of_range_value_keeps_table_unchanged() {
 
 #[test]
 fn edit_unsupported_type_reports_error() {
-    let (dir, dbm) = make_db();
+    let (dir, mut dbm) = make_db();
     let err = dbm
-        .update_all_dyn("opt_vals", vec![("5".to_string(), "9".to_string())])
+        .update_all_dyn("records", vec![("5".to_string(), "9".to_string())])
         .unwrap_err();
     let msg = err.to_string();
     assert!(msg.contains("cannot be edited"), "got: {msg}");
-    assert!(msg.contains("Option<u64>"), "got: {msg}");
+    assert!(msg.contains("app::Record"), "got: {msg}");
     // verify data is untouched via a direct typed read
+    dbm.close_db().unwrap();
     let db = Database::open(dir.path().join("edit.rdb")).unwrap();
     let txn = db.begin_read().unwrap();
-    let table = txn.open_table(OPT_VALS).unwrap();
-    assert_eq!(table.get(5u64).unwrap().unwrap().value(), Some(9u64));
+    let table = txn.open_table(RECORDS).unwrap();
+    assert_eq!(table.get(5u64).unwrap().unwrap().value(), 9u64);
 }
 
 #[test]
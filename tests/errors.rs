@@ -0,0 +1,81 @@
+mod common;
+
+use common::Record;
+use redb::TableDefinition;
+use redbcli::redbcontrol::{CommonDbManager, DealData};
+use redbcli::Error;
+
+const PAIRS: TableDefinition<(u64, u64), &str> = TableDefinition::new("pairs");
+const NAMES: TableDefinition<&str, &str> = TableDefinition::new("names");
+const RECORDS: TableDefinition<u64, Record> = TableDefinition::new("records");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("errors.redb", |txn| {
+        txn.open_table(PAIRS)
+            .unwrap()
+            .insert((1u64, 2u64), "a")
+            .unwrap();
+        txn.open_table(NAMES).unwrap().insert("k", "v").unwrap();
+        txn.open_table(RECORDS).unwrap().insert(1u64, 2u64).unwrap();
+    })
+}
+
+#[test]
+fn missing_database_and_table_are_distinct() {
+    let mut dbm = CommonDbManager::default();
+    let err = dbm
+        .setdbpath("/nonexistent/errors.redb".into())
+        .unwrap_err();
+    assert!(matches!(err, Error::DatabaseNotFound(_)), "{err}");
+
+    let (_dir, mut dbm) = make_db();
+    let err = dbm.settablename("nope".into()).unwrap_err();
+    assert!(
+        matches!(err, Error::TableNotFound(ref name) if name == "nope"),
+        "{err}"
+    );
+    assert_ne!(
+        err.exit_code(),
+        Error::DatabaseNotFound(String::new()).exit_code()
+    );
+}
+
+#[test]
+fn missing_key_is_not_corruption() {
+    let (_dir, mut dbm) = make_db();
+    dbm.settablename("names".into()).unwrap();
+    let err = dbm.get_by_key("absent".into()).unwrap_err();
+    assert!(
+        matches!(err, Error::KeyNotFound(ref key) if key == "absent"),
+        "{err}"
+    );
+}
+
+#[test]
+fn parse_errors_carry_input_and_position() {
+    let (_dir, dbm) = make_db();
+    let err = dbm
+        .put_dyn("pairs", "(1, x)".into(), "b".into())
+        .unwrap_err();
+    match err {
+        Error::Parse {
+            input, position, ..
+        } => {
+            assert_eq!(input, "(1, x)");
+            assert_eq!(position, 4);
+        }
+        other => panic!("expected a parse error, got {other}"),
+    }
+}
+
+#[test]
+fn unsupported_types_are_reported_as_such() {
+    let (_dir, dbm) = make_db();
+    let err = dbm
+        .put_dyn("records", "2".to_string(), "0x0300000000000000".to_string())
+        .unwrap_err();
+    assert!(
+        matches!(err, Error::UnsupportedType { ref value, .. } if value == "app::Record"),
+        "{err}"
+    );
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use redb::TableDefinition;
+use redbcli::redbcontrol::CommonDbManager;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+// god byte of the redb header: clearing the two-phase-commit flag makes redb
+// distrust the saved allocator state, exactly like after a crash
+const GOD_BYTE_OFFSET: u64 = 9;
+const RECOVERY_REQUIRED: u8 = 2;
+const TWO_PHASE_COMMIT: u8 = 4;
+
+static REPAIR_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+fn count_repair(_progress: f64) {
+    REPAIR_CALLS.fetch_add(1, Ordering::SeqCst);
+}
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("check.redb", |txn| {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        t.insert(1u64, "one").unwrap();
+        t.insert(2u64, "two").unwrap();
+    })
+}
+
+fn mark_unclean(path: &str) {
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .unwrap();
+    let mut god_byte = [0u8; 1];
+    file.seek(SeekFrom::Start(GOD_BYTE_OFFSET)).unwrap();
+    file.read_exact(&mut god_byte).unwrap();
+    god_byte[0] = (god_byte[0] | RECOVERY_REQUIRED) & !TWO_PHASE_COMMIT;
+    file.seek(SeekFrom::Start(GOD_BYTE_OFFSET)).unwrap();
+    file.write_all(&god_byte).unwrap();
+}
+
+#[test]
+fn check_reports_clean_database() {
+    let (_dir, mut dbm) = make_db();
+    assert!(dbm.check_integrity().unwrap());
+}
+
+#[test]
+fn check_missing_database_fails() {
+    let mut dbm = CommonDbManager {
+        dbpath: "/nonexistent/check.redb".to_string(),
+        ..Default::default()
+    };
+    let err = dbm.check_integrity().unwrap_err();
+    assert!(err.to_string().contains("not found"), "got: {err}");
+}
+
+#[test]
+fn repair_progress_is_reported_after_unclean_shutdown() {
+    let (_dir, mut dbm) = make_db();
+    dbm.close_db().unwrap();
+    mark_unclean(&dbm.dbpath);
+    dbm.on_repair = Some(count_repair);
+    let before = REPAIR_CALLS.load(Ordering::SeqCst);
+    dbm.open_db().unwrap();
+    let (_desc, rows) = dbm.get_all_dyn("by_id").unwrap();
+    assert_eq!(rows.len(), 2);
+    assert!(REPAIR_CALLS.load(Ordering::SeqCst) > before);
+    assert!(dbm.check_integrity().unwrap());
+}
@@ -0,0 +1,306 @@
+mod common;
+
+use redb::{Database, Key, ReadableDatabase, ReadableTable, TableDefinition, TypeName, Value};
+use redbcli::layout::Layouts;
+use redbcli::redbcontrol::{CommonDbManager, DealTable};
+use redbcli::{DynTable, DynValue, Error};
+use std::cmp::Ordering;
+
+const USERS: TableDefinition<Stamp, User> = TableDefinition::new("users");
+const MAYBE: TableDefinition<&str, Option<User>> = TableDefinition::new("maybe");
+const EVENTS: TableDefinition<u64, Event> = TableDefinition::new("events");
+
+const LAYOUTS: &str = r#"{
+    "app::Stamp": {"endian": "big", "order": "bytes", "fields": [
+        {"name": "day", "type": "u32"},
+        {"name": "seq", "type": "u64"}
+    ]},
+    "app::User": {"fields": [
+        {"name": "id", "type": "u32"},
+        {"name": "name", "type": "str", "len": "u16"}
+    ]},
+    "app::Event": {"codec": "json"}
+}"#;
+
+/// Key stored as big endian (day, seq), ordered bytewise.
+#[derive(Debug)]
+struct Stamp;
+
+impl Value for Stamp {
+    type SelfType<'a> = (u32, u64);
+    type AsBytes<'a> = Vec<u8>;
+
+    fn fixed_width() -> Option<usize> {
+        Some(12)
+    }
+    fn from_bytes<'a>(data: &'a [u8]) -> (u32, u64)
+    where
+        Self: 'a,
+    {
+        (
+            u32::from_be_bytes(data[..4].try_into().unwrap()),
+            u64::from_be_bytes(data[4..].try_into().unwrap()),
+        )
+    }
+    fn as_bytes<'a, 'b: 'a>(value: &'a (u32, u64)) -> Vec<u8>
+    where
+        Self: 'b,
+    {
+        [&value.0.to_be_bytes()[..], &value.1.to_be_bytes()].concat()
+    }
+    fn type_name() -> TypeName {
+        TypeName::new("app::Stamp")
+    }
+}
+
+impl Key for Stamp {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        data1.cmp(data2)
+    }
+}
+
+/// Value stored as a little endian id and a u16 length prefixed name.
+#[derive(Debug)]
+struct User;
+
+impl Value for User {
+    type SelfType<'a> = (u32, String);
+    type AsBytes<'a> = Vec<u8>;
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+    fn from_bytes<'a>(data: &'a [u8]) -> (u32, String)
+    where
+        Self: 'a,
+    {
+        let id = u32::from_le_bytes(data[..4].try_into().unwrap());
+        let len = u16::from_le_bytes(data[4..6].try_into().unwrap()) as usize;
+        (id, String::from_utf8(data[6..6 + len].to_vec()).unwrap())
+    }
+    fn as_bytes<'a, 'b: 'a>(value: &'a (u32, String)) -> Vec<u8>
+    where
+        Self: 'b,
+    {
+        let mut out = value.0.to_le_bytes().to_vec();
+        out.extend((value.1.len() as u16).to_le_bytes());
+        out.extend(value.1.as_bytes());
+        out
+    }
+    fn type_name() -> TypeName {
+        TypeName::new("app::User")
+    }
+}
+
+/// Value stored as a JSON document.
+#[derive(Debug)]
+struct Event;
+
+impl Value for Event {
+    type SelfType<'a> = serde_json::Value;
+    type AsBytes<'a> = Vec<u8>;
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+    fn from_bytes<'a>(data: &'a [u8]) -> serde_json::Value
+    where
+        Self: 'a,
+    {
+        serde_json::from_slice(data).unwrap()
+    }
+    fn as_bytes<'a, 'b: 'a>(value: &'a serde_json::Value) -> Vec<u8>
+    where
+        Self: 'b,
+    {
+        serde_json::to_vec(value).unwrap()
+    }
+    fn type_name() -> TypeName {
+        TypeName::new("app::Event")
+    }
+}
+
+/// The stored bytes of an `Event`, to write documents it cannot parse.
+#[derive(Debug)]
+struct EventBytes;
+
+impl Value for EventBytes {
+    type SelfType<'a> = &'a [u8];
+    type AsBytes<'a> = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+    fn from_bytes<'a>(data: &'a [u8]) -> &'a [u8]
+    where
+        Self: 'a,
+    {
+        data
+    }
+    fn as_bytes<'a, 'b: 'a>(value: &'a &'b [u8]) -> &'a [u8]
+    where
+        Self: 'b,
+    {
+        value
+    }
+    fn type_name() -> TypeName {
+        Event::type_name()
+    }
+}
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    Layouts::parse(LAYOUTS).unwrap().install();
+    common::make_db("layouts.redb", |txn| {
+        let mut t = txn.open_table(USERS).unwrap();
+        t.insert((2, 1), (7, "bob".to_string())).unwrap();
+        t.insert((1, 9), (3, "ann, jr".to_string())).unwrap();
+        let mut t = txn.open_table(MAYBE).unwrap();
+        t.insert("some", Some((1, "x".to_string()))).unwrap();
+        t.insert("none", None).unwrap();
+        let mut t = txn.open_table(EVENTS).unwrap();
+        t.insert(1, serde_json::json!({"kind": "start", "tags": [1, 2]}))
+            .unwrap();
+    })
+}
+
+#[test]
+fn reads_application_types_through_layouts() {
+    let (_dir, dbm) = make_db();
+    let (desc, rows) = dbm.get_all_dyn("users").unwrap();
+    assert_eq!(desc.key, "app::Stamp");
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "{day: 1, seq: 9}".to_string(),
+                "{id: 3, name: \"ann, jr\"}".to_string()
+            ),
+            (
+                "{day: 2, seq: 1}".to_string(),
+                "{id: 7, name: \"bob\"}".to_string()
+            ),
+        ]
+    );
+
+    let (desc, rows) = dbm.get_all_dyn("maybe").unwrap();
+    assert_eq!(desc.value, "Option<app::User>");
+    assert_eq!(rows[0].1, "None");
+    assert_eq!(rows[1].1, "Some({id: 1, name: \"x\"})");
+
+    let (_, rows) = dbm.get_all_dyn("events").unwrap();
+    assert_eq!(
+        rows[0].1,
+        "{\n  \"kind\": \"start\",\n  \"tags\": [\n    1,\n    2\n  ]\n}"
+    );
+}
+
+#[test]
+fn edits_are_readable_by_the_application() {
+    let (dir, mut dbm) = make_db();
+    let old = dbm
+        .put_dyn(
+            "users",
+            "{day: 2, seq: 1}".to_string(),
+            "{name: \"robert\", id: 8}".to_string(),
+        )
+        .unwrap();
+    assert_eq!(old.as_deref(), Some("{id: 7, name: \"bob\"}"));
+    dbm.put_dyn(
+        "users",
+        "{day: 1, seq: 10}".to_string(),
+        "{id: 4, name: \"\"}".to_string(),
+    )
+    .unwrap();
+    dbm.put_dyn("events", "2".to_string(), r#"{"kind": "stop"}"#.to_string())
+        .unwrap();
+    let err = dbm
+        .put_dyn(
+            "users",
+            "{day: 1}".to_string(),
+            "{id: 1, name: \"\"}".to_string(),
+        )
+        .unwrap_err();
+    assert!(matches!(err, Error::Parse { .. }), "{err}");
+
+    dbm.close_db().unwrap();
+    let db = Database::open(dir.path().join("layouts.redb")).unwrap();
+    let txn = db.begin_read().unwrap();
+    let users = txn.open_table(USERS).unwrap();
+    assert_eq!(
+        users.get((2, 1)).unwrap().unwrap().value(),
+        (8, "robert".to_string())
+    );
+    let keys: Vec<_> = users
+        .iter()
+        .unwrap()
+        .map(|row| row.unwrap().0.value())
+        .collect();
+    assert_eq!(keys, vec![(1, 9), (1, 10), (2, 1)]);
+    let events = txn.open_table(EVENTS).unwrap();
+    assert_eq!(
+        events.get(2).unwrap().unwrap().value(),
+        serde_json::json!({"kind": "stop"})
+    );
+
+    let users = DynTable::open(&txn, "users").unwrap();
+    let from = users.parse_key("{day: 1, seq: 10}").unwrap();
+    let rows = users.range(&from..).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(
+        rows[0].1,
+        DynValue::Struct(vec![
+            ("id".to_string(), DynValue::U32(4)),
+            ("name".to_string(), DynValue::Str(String::new())),
+        ])
+    );
+}
+
+#[test]
+fn invalid_layouts_file_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("layouts.json");
+    std::fs::write(
+        &path,
+        r#"{"app::X": {"fields": [{"name": "a", "type": "u7"}]}}"#,
+    )
+    .unwrap();
+    let err = Layouts::load(&path.to_string_lossy()).unwrap_err();
+    assert!(matches!(err, Error::InvalidLayout { .. }), "{err}");
+    assert_eq!(err.exit_code(), 11);
+    assert!(err.to_string().contains("unknown type 'u7'"), "{err}");
+}
+
+#[test]
+fn undo_of_delete_keeps_undecodable_rows() {
+    let (dir, mut dbm) = make_db();
+    let path = dir.path().join("layouts.redb");
+    let raw_events: TableDefinition<u64, EventBytes> = TableDefinition::new("events");
+    dbm.close_db().unwrap();
+    {
+        let db = Database::open(&path).unwrap();
+        let txn = db.begin_write().unwrap();
+        txn.open_table(raw_events)
+            .unwrap()
+            .insert(2, b"{not json".as_slice())
+            .unwrap();
+        txn.commit().unwrap();
+    }
+    dbm.reopen_db().unwrap();
+    // the bad document is shown as hex
+    let (_, rows) = dbm.get_all_dyn("events").unwrap();
+    assert!(rows[1].1.starts_with("0x"), "{}", rows[1].1);
+
+    let image = dbm.table_image("events").unwrap();
+    dbm.delete_table("events".into()).unwrap();
+    dbm.undo(image).unwrap();
+
+    dbm.close_db().unwrap();
+    let db = Database::open(&path).unwrap();
+    let txn = db.begin_read().unwrap();
+    let events = txn.open_table(raw_events).unwrap();
+    assert_eq!(
+        events.get(1).unwrap().unwrap().value(),
+        br#"{"kind":"start","tags":[1,2]}"#
+    );
+    assert_eq!(events.get(2).unwrap().unwrap().value(), b"{not json");
+}
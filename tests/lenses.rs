@@ -0,0 +1,126 @@
+mod common;
+
+use redb::{Database, ReadableDatabase, ReadableTableMetadata, TableDefinition};
+use redbcli::lens::{Lens, Lenses};
+use redbcli::redbcontrol::CommonDbManager;
+use redbcli::Error;
+
+const EVENTS: TableDefinition<u64, &[u8]> = TableDefinition::new("events");
+const NAMES: TableDefinition<&str, u32> = TableDefinition::new("names");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("lenses.redb", |txn| {
+        let mut t = txn.open_table(EVENTS).unwrap();
+        t.insert(1_792_281_600_000, b"started".as_slice()).unwrap();
+        t.insert(1_792_281_601_500, [0xff, 0x00].as_slice())
+            .unwrap();
+        let mut t = txn.open_table(NAMES).unwrap();
+        t.insert("tab\there", 0x7f00_0001).unwrap();
+    })
+}
+
+fn lenses(key: Option<Lens>, value: Option<Lens>) -> Lenses {
+    Lenses { key, value }
+}
+
+#[test]
+fn cells_are_shown_through_lenses() {
+    let (_dir, mut dbm) = make_db();
+    dbm.set_lenses("events", lenses(Some(Lens::EpochMillis), Some(Lens::Utf8)))
+        .unwrap();
+    let (_, rows) = dbm.get_all_dyn("events").unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "2026-10-18T00:00:00.000Z".to_string(),
+                "\"started\"".to_string()
+            ),
+            // bytes that are not UTF-8 keep their plain form
+            ("2026-10-18T00:00:01.500Z".to_string(), "0xff00".to_string()),
+        ]
+    );
+
+    dbm.set_lenses("names", lenses(Some(Lens::Escape), Some(Lens::Ip)))
+        .unwrap();
+    let (_, rows) = dbm.get_all_dyn("names").unwrap();
+    assert_eq!(rows[0], ("\"tab\\there\"".into(), "127.0.0.1".into()));
+
+    // clearing both lenses shows the plain form again
+    dbm.set_lenses("names", Lenses::default()).unwrap();
+    assert!(!dbm.lenses.contains_key("names"));
+    let (_, rows) = dbm.get_all_dyn("names").unwrap();
+    assert_eq!(rows[0], ("tab\there".into(), "2130706433".into()));
+}
+
+#[test]
+fn input_is_accepted_in_the_lens_form() {
+    let (dir, mut dbm) = make_db();
+    dbm.set_lenses("events", lenses(Some(Lens::EpochSecs), Some(Lens::Hex)))
+        .unwrap_err();
+    dbm.set_lenses("events", lenses(Some(Lens::EpochMillis), Some(Lens::Utf8)))
+        .unwrap();
+
+    let old = dbm
+        .put_dyn(
+            "events",
+            "2026-10-19T00:00:00Z".to_string(),
+            "stopped".to_string(),
+        )
+        .unwrap();
+    assert!(old.is_none());
+    let old = dbm
+        .put_dyn(
+            "events",
+            "1792281600000".to_string(),
+            "\"a\\nb\"".to_string(),
+        )
+        .unwrap();
+    assert_eq!(old.as_deref(), Some("\"started\""));
+    let err = dbm
+        .put_dyn(
+            "events",
+            "2026-13-01T00:00:00Z".to_string(),
+            "x".to_string(),
+        )
+        .unwrap_err();
+    assert!(matches!(err, Error::Parse { .. }), "{err}");
+    let removed = dbm
+        .remove_dyn("events", "2026-10-18T00:00:01.5Z".to_string())
+        .unwrap();
+    assert_eq!(removed.as_deref(), Some("0xff00"));
+
+    dbm.close_db().unwrap();
+    let db = Database::open(dir.path().join("lenses.redb")).unwrap();
+    let txn = db.begin_read().unwrap();
+    let events = txn.open_table(EVENTS).unwrap();
+    assert_eq!(events.len().unwrap(), 2);
+    assert_eq!(
+        events.get(1_792_368_000_000).unwrap().unwrap().value(),
+        b"stopped"
+    );
+    assert_eq!(
+        events.get(1_792_281_600_000).unwrap().unwrap().value(),
+        b"a\nb"
+    );
+}
+
+#[test]
+fn lenses_must_fit_the_column_type() {
+    let (_dir, mut dbm) = make_db();
+    let err = dbm
+        .set_lenses("names", lenses(Some(Lens::Hex), None))
+        .unwrap_err();
+    assert!(matches!(err, Error::Refused(_)), "{err}");
+    assert!(err.to_string().contains("&str keys"), "{err}");
+    let err = dbm
+        .set_lenses("events", lenses(None, Some(Lens::ByteSize)))
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("bytesize lens cannot show the &[u8] values"),
+        "{err}"
+    );
+    assert!(dbm.lenses.is_empty());
+    assert!(dbm.set_lenses("missing", Lenses::default()).is_err());
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use redb::{Database, TableDefinition};
+use redbcli::redbcontrol::{CommonDbManager, DealTable};
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("lifecycle.redb", |txn| {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        t.insert(1u64, "one").unwrap();
+    })
+}
+
+#[test]
+fn handle_stays_open_until_closed() {
+    let (_dir, mut dbm) = make_db();
+    // the manager holds the lock between commands
+    assert!(matches!(
+        Database::open(&dbm.dbpath),
+        Err(redb::DatabaseError::DatabaseAlreadyOpen)
+    ));
+
+    assert!(dbm.close_db().unwrap());
+    assert!(!dbm.close_db().unwrap());
+    let err = dbm.list_table().unwrap_err();
+    assert!(
+        err.to_string().contains("no database is open"),
+        "got: {err}"
+    );
+    {
+        let db = Database::open(&dbm.dbpath).unwrap();
+        let txn = db.begin_write().unwrap();
+        txn.open_table(BY_ID).unwrap().insert(2u64, "two").unwrap();
+        txn.commit().unwrap();
+    }
+
+    dbm.reopen_db().unwrap();
+    let (_desc, rows) = dbm.get_all_dyn("by_id").unwrap();
+    assert_eq!(rows.len(), 2);
+}
+
+#[test]
+fn close_and_set_are_refused_while_a_transaction_is_open() {
+    let (dir, mut dbm) = make_db();
+    let other = dir.path().join("other.redb");
+    Database::create(&other).unwrap();
+
+    dbm.begin_transaction().unwrap();
+    assert!(dbm.close_db().is_err());
+    assert!(dbm.setdbpath(other.to_string_lossy().to_string()).is_err());
+    // nor is a new file created that could not be switched to
+    let fresh = dir.path().join("fresh.redb");
+    assert!(dbm.create_db(fresh.to_str().unwrap()).is_err());
+    assert!(!fresh.exists());
+    dbm.rollback_transaction().unwrap();
+
+    // switching releases the previous file
+    let first = dbm.dbpath.clone();
+    dbm.setdbpath(other.to_string_lossy().to_string()).unwrap();
+    assert!(Database::open(&first).is_ok());
+    assert!(dbm.list_table().unwrap().is_empty());
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use redb::{
+    Database, Key, ReadableDatabase, ReadableTable, ReadableTableMetadata, TableDefinition,
+    TypeName, Value,
+};
+use redbcli::redbcontrol::CommonDbManager;
+use redbcli::Error;
+use std::cmp::Ordering;
+
+const BY_RANK: TableDefinition<Rank, Blob> = TableDefinition::new("by_rank");
+const BY_BLOB: TableDefinition<Blob, Rank> = TableDefinition::new("by_blob");
+const MAYBE: TableDefinition<u8, Option<Rank>> = TableDefinition::new("maybe");
+
+/// Fixed width key stored as a little endian u32 and ordered descending,
+/// so its order is not the bytewise one.
+#[derive(Debug)]
+struct Rank;
+
+impl Value for Rank {
+    type SelfType<'a> = u32;
+    type AsBytes<'a> = [u8; 4];
+
+    fn fixed_width() -> Option<usize> {
+        Some(4)
+    }
+    fn from_bytes<'a>(data: &'a [u8]) -> u32
+    where
+        Self: 'a,
+    {
+        u32::from_le_bytes(data.try_into().unwrap())
+    }
+    fn as_bytes<'a, 'b: 'a>(value: &'a u32) -> [u8; 4]
+    where
+        Self: 'b,
+    {
+        value.to_le_bytes()
+    }
+    fn type_name() -> TypeName {
+        TypeName::new("app::Rank")
+    }
+}
+
+impl Key for Rank {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        Self::from_bytes(data2).cmp(&Self::from_bytes(data1))
+    }
+}
+
+/// Variable width type stored as its bytes.
+#[derive(Debug)]
+struct Blob;
+
+impl Value for Blob {
+    type SelfType<'a> = &'a [u8];
+    type AsBytes<'a> = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+    fn from_bytes<'a>(data: &'a [u8]) -> &'a [u8]
+    where
+        Self: 'a,
+    {
+        data
+    }
+    fn as_bytes<'a, 'b: 'a>(value: &'a &'b [u8]) -> &'a [u8]
+    where
+        Self: 'b,
+    {
+        value
+    }
+    fn type_name() -> TypeName {
+        TypeName::new("app::Blob")
+    }
+}
+
+impl Key for Blob {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        data1.cmp(data2)
+    }
+}
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("raw.redb", |txn| {
+        let mut t = txn.open_table(BY_RANK).unwrap();
+        for rank in [1u32, 2, 3, 300] {
+            t.insert(rank, format!("r{rank}").as_bytes()).unwrap();
+        }
+        let mut t = txn.open_table(BY_BLOB).unwrap();
+        t.insert(&b"\x01id"[..], 7).unwrap();
+        let mut t = txn.open_table(MAYBE).unwrap();
+        t.insert(1, Some(5)).unwrap();
+        t.insert(2, None).unwrap();
+    })
+}
+
+#[test]
+fn unknown_types_are_shown_as_hex_and_ascii() {
+    let (_dir, dbm) = make_db();
+    let (desc, rows) = dbm.get_all_dyn("by_rank").unwrap();
+    assert!(!desc.is_decodable());
+    // rows come in the application's (descending) order
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "0x2c010000 |,...|".to_string(),
+                "0x72333030 |r300|".to_string()
+            ),
+            ("0x03000000".to_string(), "0x7233 |r3|".to_string()),
+            ("0x02000000".to_string(), "0x7232 |r2|".to_string()),
+            ("0x01000000".to_string(), "0x7231 |r1|".to_string()),
+        ]
+    );
+
+    // variable width key, fixed width value
+    let (_, rows) = dbm.get_all_dyn("by_blob").unwrap();
+    assert_eq!(
+        rows,
+        vec![("0x016964 |.id|".to_string(), "0x07000000".to_string())]
+    );
+
+    // built-in key, composite name around an unknown type
+    let (desc, rows) = dbm.get_all_dyn("maybe").unwrap();
+    assert_eq!(desc.value, "Option<app::Rank>");
+    assert_eq!(
+        rows,
+        vec![
+            ("1".to_string(), "0x0105000000".to_string()),
+            ("2".to_string(), "0x0000000000".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn rows_are_deleted_by_raw_key() {
+    let (dir, mut dbm) = make_db();
+    // the displayed cell, ascii column included, is accepted as a key
+    let old = dbm
+        .remove_dyn("by_rank", "0x2c010000 |,...|".to_string())
+        .unwrap();
+    assert_eq!(old.as_deref(), Some("0x72333030 |r300|"));
+    assert!(dbm
+        .remove_dyn("by_rank", "0x02000000".to_string())
+        .unwrap()
+        .is_some());
+    assert!(dbm
+        .remove_dyn("by_rank", "0x09000000".to_string())
+        .unwrap()
+        .is_none());
+    assert!(dbm
+        .remove_dyn("by_blob", "0x016964".to_string())
+        .unwrap()
+        .is_some());
+
+    let err = dbm
+        .put_dyn("by_rank", "0x04000000".to_string(), "0x00".to_string())
+        .unwrap_err();
+    assert!(matches!(err, Error::UnsupportedType { .. }), "{err}");
+    let err = dbm.remove_dyn("by_rank", "0x0100".to_string()).unwrap_err();
+    assert!(matches!(err, Error::Parse { .. }), "{err}");
+    assert!(err.to_string().contains("expected 4 bytes"), "{err}");
+
+    dbm.close_db().unwrap();
+    let db = Database::open(dir.path().join("raw.redb")).unwrap();
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(BY_RANK).unwrap();
+    let rows: Vec<_> = table
+        .iter()
+        .unwrap()
+        .map(|row| {
+            let (key, value) = row.unwrap();
+            (key.value(), value.value().to_vec())
+        })
+        .collect();
+    assert_eq!(rows, vec![(3, b"r3".to_vec()), (1, b"r1".to_vec())]);
+    assert!(table.get(1).unwrap().is_some());
+    assert!(txn.open_table(BY_BLOB).unwrap().is_empty().unwrap());
+}
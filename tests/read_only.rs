@@ -0,0 +1,73 @@
+use redb::{Database, TableDefinition};
+use redbcli::redbcontrol::{CommonDbManager, DealTable};
+use std::path::PathBuf;
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+
+fn make_file() -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().expect("create temp dir failed");
+    let path = dir.path().join("read_only.redb");
+    let db = Database::create(&path).expect("create db failed");
+
+    let txn = db.begin_write().expect("begin write failed");
+    {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        t.insert(1u64, "one").unwrap();
+    }
+    txn.commit().unwrap();
+    (dir, path)
+}
+
+#[test]
+fn read_only_mode_reads_but_refuses_writes() {
+    let (_dir, path) = make_file();
+    let mut dbm = CommonDbManager {
+        read_only: true,
+        ..Default::default()
+    };
+    dbm.setdbpath(path.to_string_lossy().to_string()).unwrap();
+
+    assert_eq!(dbm.get_all_dyn("by_id").unwrap().1.len(), 1);
+    assert_eq!(dbm.list_table_stats().unwrap()[0].1.rows, 1);
+    dbm.pin_snapshot().unwrap();
+    assert_eq!(dbm.list_table().unwrap(), vec!["by_id".to_string()]);
+    dbm.release_snapshot();
+
+    let err = dbm
+        .put_dyn("by_id", "2".into(), "two".into())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("read-only"), "{err}");
+    assert!(dbm.create_table("fresh".into()).is_err());
+    assert!(dbm.compact().is_err());
+    assert!(dbm.begin_transaction().is_err());
+    assert!(dbm.db_info().unwrap_err().to_string().contains("read-only"));
+}
+
+#[test]
+fn locked_file_can_be_inspected_through_a_copy() {
+    let (dir, path) = make_file();
+    let service = Database::open(&path).unwrap();
+
+    let mut dbm = CommonDbManager::default();
+    let err = dbm
+        .setdbpath(path.to_string_lossy().to_string())
+        .unwrap_err();
+    assert!(matches!(err, redbcli::Error::Locked(_)), "{err}");
+
+    dbm.inspect_copy(path.to_str().unwrap()).unwrap();
+    assert!(dbm.is_read_only());
+    let copy = PathBuf::from(&dbm.dbpath);
+    assert_ne!(copy, path);
+    assert_eq!(dbm.get_all_dyn("by_id").unwrap().1.len(), 1);
+    assert!(dbm.remove_dyn("by_id", "1".into()).is_err());
+
+    // switching away removes the copy and leaves read-only mode
+    drop(service);
+    let other = dir.path().join("other.redb");
+    Database::create(&other).unwrap();
+    dbm.setdbpath(other.to_string_lossy().to_string()).unwrap();
+    assert!(!copy.exists());
+    assert!(!dbm.is_read_only());
+    dbm.create_table("fresh".into()).unwrap();
+}
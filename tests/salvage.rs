@@ -0,0 +1,215 @@
+mod common;
+
+use common::Record;
+use redb::{Database, MultimapTableDefinition, ReadableDatabase, ReadableTable, TableDefinition};
+use redbcli::redbcontrol::CommonDbManager;
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+const FOUND_KEYS: TableDefinition<(u128, u128), u64> = TableDefinition::new("found_keys");
+const RECORDS: TableDefinition<u64, Record> = TableDefinition::new("records");
+const MM_TABLE: MultimapTableDefinition<&str, u64> = MultimapTableDefinition::new("tags");
+const ROWS: u64 = 3000;
+
+fn value_of(i: u64) -> String {
+    format!("row-{i:08}-{}", "v".repeat(100))
+}
+
+fn make_db(dir: &tempfile::TempDir) -> String {
+    let path = dir.path().join("damaged.redb");
+    let db = Database::create(&path).expect("create db failed");
+
+    let txn = db.begin_write().expect("begin write failed");
+    {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        for i in 0..ROWS {
+            t.insert(i, value_of(i).as_str()).unwrap();
+        }
+    }
+    {
+        let mut t = txn.open_table(FOUND_KEYS).unwrap();
+        t.insert((1u128, 2u128), 100u64).unwrap();
+    }
+    {
+        let mut t = txn.open_table(RECORDS).unwrap();
+        t.insert(5u64, 9u64).unwrap();
+    }
+    {
+        let mut t = txn.open_multimap_table(MM_TABLE).unwrap();
+        t.insert("k", 1u64).unwrap();
+    }
+    txn.commit().unwrap();
+    drop(db);
+    path.to_string_lossy().to_string()
+}
+
+/// Overwrites the page holding `needle` with garbage.
+fn smash_page_containing(path: &str, needle: &str) {
+    let mut data = std::fs::read(path).unwrap();
+    let at = data
+        .windows(needle.len())
+        .position(|w| w == needle.as_bytes())
+        .expect("needle not found");
+    let page = at / 4096 * 4096;
+    for (i, byte) in data[page..page + 4096].iter_mut().enumerate() {
+        *byte = (i * 7 + 13) as u8;
+    }
+    std::fs::write(path, data).unwrap();
+}
+
+fn rescued_path(dir: &tempfile::TempDir) -> String {
+    dir.path()
+        .join("rescued.redb")
+        .to_string_lossy()
+        .to_string()
+}
+
+#[test]
+fn salvage_healthy_database_copies_everything() {
+    let dir = tempfile::tempdir().unwrap();
+    let damaged = make_db(&dir);
+    let rescued = rescued_path(&dir);
+    let report = CommonDbManager::default()
+        .salvage(&damaged, &rescued)
+        .unwrap();
+
+    let by_name = |name: &str| report.tables.iter().find(|t| t.name == name).unwrap();
+    assert_eq!(by_name("by_id").rows, ROWS);
+    assert_eq!(by_name("by_id").problem, None);
+    assert_eq!(by_name("found_keys").rows, 1);
+    // app-typed tables are copied as stored bytes
+    assert_eq!(by_name("records").rows, 1);
+    assert_eq!(by_name("records").problem, None);
+    assert!(by_name("tags")
+        .problem
+        .as_ref()
+        .unwrap()
+        .contains("multimap"));
+
+    let db = Database::open(&rescued).unwrap();
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(FOUND_KEYS).unwrap();
+    assert_eq!(table.get((1u128, 2u128)).unwrap().unwrap().value(), 100);
+    let table = txn.open_table(RECORDS).unwrap();
+    assert_eq!(table.get(5u64).unwrap().unwrap().value(), 9);
+}
+
+#[test]
+fn salvage_skips_damaged_key_range_of_app_typed_table() {
+    const BLOBS: TableDefinition<Record, &[u8]> = TableDefinition::new("blobs");
+    let blob_of = |i: u64| format!("blob-{i:08}-{}", "b".repeat(100));
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("damaged.redb");
+    let db = Database::create(&path).unwrap();
+    let txn = db.begin_write().unwrap();
+    {
+        let mut t = txn.open_table(BLOBS).unwrap();
+        for i in 0..ROWS {
+            t.insert(i, blob_of(i).as_bytes()).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+    drop(db);
+    let damaged = path.to_string_lossy().to_string();
+    smash_page_containing(&damaged, &blob_of(ROWS / 2));
+
+    let rescued = rescued_path(&dir);
+    let report = CommonDbManager::default()
+        .salvage(&damaged, &rescued)
+        .unwrap();
+    let table = &report.tables[0];
+    assert!(table.rows > 0 && table.rows < ROWS, "copied {}", table.rows);
+    assert!(
+        table.problem.as_ref().unwrap().contains("unreadable"),
+        "got: {:?}",
+        table.problem
+    );
+
+    // both ends were copied in the application's key order
+    let db = Database::open(&rescued).unwrap();
+    let txn = db.begin_read().unwrap();
+    let rescued_table = txn.open_table(BLOBS).unwrap();
+    for i in [0, ROWS - 1] {
+        let value = rescued_table.get(i).unwrap().unwrap();
+        assert_eq!(value.value(), blob_of(i).as_bytes());
+    }
+    let keys: Vec<u64> = rescued_table
+        .iter()
+        .unwrap()
+        .map(|row| row.unwrap().0.value())
+        .collect();
+    assert_eq!(keys.len() as u64, table.rows);
+    assert!(keys.windows(2).all(|w| w[0] < w[1]), "keys out of order");
+}
+
+#[test]
+fn salvage_skips_damaged_key_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let damaged = make_db(&dir);
+    smash_page_containing(&damaged, &value_of(ROWS / 2));
+    let evidence = std::fs::read(&damaged).unwrap();
+    let rescued = rescued_path(&dir);
+    let report = CommonDbManager::default()
+        .salvage(&damaged, &rescued)
+        .unwrap();
+    assert!(
+        std::fs::read(&damaged).unwrap() == evidence,
+        "damaged file was modified"
+    );
+
+    let table = report.tables.iter().find(|t| t.name == "by_id").unwrap();
+    assert!(table.rows > 0 && table.rows < ROWS, "copied {}", table.rows);
+    assert!(
+        table.problem.as_ref().unwrap().contains("unreadable"),
+        "got: {:?}",
+        table.problem
+    );
+
+    // the rows that were copied are intact
+    let db = Database::open(&rescued).unwrap();
+    let txn = db.begin_read().unwrap();
+    let rescued_table = txn.open_table(BY_ID).unwrap();
+    assert_eq!(
+        rescued_table.get(0u64).unwrap().unwrap().value(),
+        value_of(0)
+    );
+    assert_eq!(
+        rescued_table.get(ROWS - 1).unwrap().unwrap().value(),
+        value_of(ROWS - 1)
+    );
+}
+
+#[test]
+fn salvage_refuses_existing_destination() {
+    let dir = tempfile::tempdir().unwrap();
+    let damaged = make_db(&dir);
+    let err = CommonDbManager::default()
+        .salvage(&damaged, &damaged)
+        .unwrap_err();
+    assert!(err.to_string().contains("already exists"), "got: {err}");
+}
+
+#[test]
+fn salvage_repairs_a_copy_and_leaves_the_source_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("unclean.redb");
+    let db = Database::create(&path).unwrap();
+    let txn = db.begin_write().unwrap();
+    txn.open_table(BY_ID).unwrap().insert(1, "one").unwrap();
+    txn.commit().unwrap();
+    // copied while open, the file is not marked as cleanly shut down and
+    // needs a repair before redb reads it
+    let unclean = dir.path().join("copy.redb");
+    std::fs::copy(&path, &unclean).unwrap();
+    drop(db);
+    let evidence = std::fs::read(&unclean).unwrap();
+
+    let rescued = rescued_path(&dir);
+    let report = CommonDbManager::default()
+        .salvage(unclean.to_str().unwrap(), &rescued)
+        .unwrap();
+    assert_eq!(report.tables[0].rows, 1);
+    assert!(
+        std::fs::read(&unclean).unwrap() == evidence,
+        "damaged file was modified"
+    );
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::rows_of;
+use redb::TableDefinition;
+use redbcli::diff::{RowChange, TableChange};
+use redbcli::redbcontrol::{CommonDbManager, DealTable};
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+const FOUND_KEYS: TableDefinition<(u128, u128), u64> = TableDefinition::new("found_keys");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("savepoint.redb", |txn| {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        t.insert(1u64, "one").unwrap();
+        t.insert(2u64, "two").unwrap();
+        let mut t = txn.open_table(FOUND_KEYS).unwrap();
+        t.insert((1u128, 2u128), 100u64).unwrap();
+    })
+}
+
+#[test]
+fn create_list_delete_savepoints() {
+    let (_dir, dbm) = make_db();
+    assert!(dbm.list_savepoints().unwrap().is_empty());
+    let first = dbm.create_savepoint().unwrap();
+    let second = dbm.create_savepoint().unwrap();
+    assert_eq!(dbm.list_savepoints().unwrap(), vec![first, second]);
+    assert!(dbm.delete_savepoint(first).unwrap());
+    assert!(!dbm.delete_savepoint(first).unwrap());
+    assert_eq!(dbm.list_savepoints().unwrap(), vec![second]);
+}
+
+#[test]
+fn diff_shows_changes_since_savepoint_without_writing() {
+    let (_dir, dbm) = make_db();
+    let id = dbm.create_savepoint().unwrap();
+    dbm.update_all_dyn(
+        "by_id",
+        vec![
+            ("1".to_string(), "ONE".to_string()),
+            ("3".to_string(), "three".to_string()),
+        ],
+    )
+    .unwrap();
+    dbm.create_table("scratch".to_string()).unwrap();
+
+    let diff = dbm.savepoint_diff(id).unwrap();
+    assert_eq!(diff.len(), 2);
+    assert_eq!(diff[0].name, "by_id");
+    assert_eq!(diff[0].change, TableChange::Modified);
+    assert_eq!(
+        diff[0].rows,
+        vec![
+            RowChange::Changed {
+                key: "1".to_string(),
+                old: "ONE".to_string(),
+                new: "one".to_string()
+            },
+            RowChange::Removed {
+                key: "3".to_string(),
+                value: "three".to_string()
+            },
+            RowChange::Added {
+                key: "2".to_string(),
+                value: "two".to_string()
+            },
+        ]
+    );
+    assert_eq!(diff[1].name, "scratch");
+    assert_eq!(diff[1].change, TableChange::Dropped);
+
+    // diffing does not restore anything
+    assert_eq!(rows_of(&dbm, "by_id")[0].1, "ONE");
+    assert!(dbm.list_table().unwrap().contains(&"scratch".to_string()));
+}
+
+#[test]
+fn restore_rolls_back_whole_database() {
+    let (_dir, dbm) = make_db();
+    let before_ids = rows_of(&dbm, "by_id");
+    let before_keys = rows_of(&dbm, "found_keys");
+    let id = dbm.create_savepoint().unwrap();
+
+    dbm.update_all_dyn("by_id", vec![]).unwrap();
+    dbm.update_all_dyn("found_keys", vec![("(9, 9)".to_string(), "1".to_string())])
+        .unwrap();
+    dbm.delete_table("by_id".to_string()).unwrap();
+
+    dbm.restore_savepoint(id).unwrap();
+    assert_eq!(rows_of(&dbm, "by_id"), before_ids);
+    assert_eq!(rows_of(&dbm, "found_keys"), before_keys);
+    assert!(dbm.savepoint_diff(id).unwrap().is_empty());
+}
+
+#[test]
+fn restore_unknown_savepoint_fails() {
+    let (_dir, dbm) = make_db();
+    assert!(dbm.restore_savepoint(42).is_err());
+    assert!(dbm.savepoint_diff(42).is_err());
+}
+
+#[test]
+fn list_savepoints_in_every_mode() {
+    let (_dir, mut dbm) = make_db();
+    let id = dbm.create_savepoint().unwrap();
+
+    dbm.pin_snapshot().unwrap();
+    assert_eq!(dbm.list_savepoints().unwrap(), vec![id]);
+    dbm.release_snapshot();
+    dbm.begin_transaction().unwrap();
+    assert_eq!(dbm.list_savepoints().unwrap(), vec![id]);
+    dbm.rollback_transaction().unwrap();
+
+    // redb's read-only API cannot list them; say so instead of a generic refusal
+    let mut read_only = CommonDbManager {
+        read_only: true,
+        ..Default::default()
+    };
+    let path = dbm.dbpath.clone();
+    drop(dbm);
+    read_only.setdbpath(path).unwrap();
+    let err = read_only.list_savepoints().unwrap_err();
+    assert!(matches!(err, redbcli::Error::Refused(_)), "{err}");
+    assert!(err
+        .to_string()
+        .contains("'savepoint list' is not available in read-only mode"));
+}
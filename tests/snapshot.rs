@@ -0,0 +1,52 @@
+mod common;
+
+use redb::TableDefinition;
+use redbcli::redbcontrol::{CommonDbManager, DealTable};
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("snapshot.redb", |txn| {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        t.insert(1u64, "one").unwrap();
+    })
+}
+
+#[test]
+fn reads_use_the_pinned_snapshot() {
+    let (_dir, mut dbm) = make_db();
+    dbm.pin_snapshot().unwrap();
+    assert!(dbm.snapshot_age().is_some());
+
+    assert_eq!(dbm.list_table().unwrap(), vec!["by_id".to_string()]);
+    assert_eq!(dbm.get_all_dyn("by_id").unwrap().1.len(), 1);
+    assert_eq!(dbm.list_table_stats().unwrap()[0].1.rows, 1);
+    assert_eq!(
+        dbm.checksum_tables(&["by_id".to_string()]).unwrap().len(),
+        1
+    );
+    assert_eq!(dbm.db_info().unwrap().tables, 1);
+
+    // pinning again moves the snapshot instead of failing
+    dbm.pin_snapshot().unwrap();
+    assert_eq!(dbm.get_all_dyn("by_id").unwrap().1.len(), 1);
+}
+
+#[test]
+fn writes_are_refused_while_pinned() {
+    let (_dir, mut dbm) = make_db();
+    dbm.pin_snapshot().unwrap();
+    let err = dbm
+        .put_dyn("by_id", "2".into(), "two".into())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("snapshot off"), "{err}");
+    assert!(dbm.create_table("fresh".into()).is_err());
+    assert!(dbm.begin_transaction().is_err());
+
+    assert!(dbm.release_snapshot());
+    assert!(!dbm.release_snapshot());
+    assert!(dbm.snapshot_age().is_none());
+    dbm.put_dyn("by_id", "2".into(), "two".into()).unwrap();
+    assert_eq!(dbm.get_all_dyn("by_id").unwrap().1.len(), 2);
+}
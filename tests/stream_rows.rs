@@ -0,0 +1,51 @@
+mod common;
+
+use redb::TableDefinition;
+use redbcli::redbcontrol::CommonDbManager;
+use std::ops::ControlFlow;
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("stream.redb", |txn| {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        for id in 0..5000u64 {
+            t.insert(id, "row").unwrap();
+        }
+    })
+}
+
+#[test]
+fn visits_every_row_in_key_order() {
+    let (_dir, dbm) = make_db();
+    let mut next = 0u64;
+    let desc = dbm
+        .visit_rows_dyn("by_id", &mut |key, value| {
+            assert_eq!(key, next.to_string());
+            assert_eq!(value, "row");
+            next += 1;
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+    assert_eq!(next, 5000);
+    assert_eq!(desc.key, "u64");
+}
+
+#[test]
+fn visit_stops_when_asked() {
+    let (_dir, dbm) = make_db();
+    let mut seen = Vec::new();
+    dbm.visit_rows_dyn("by_id", &mut |key, _| {
+        seen.push(key);
+        if seen.len() == 3 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    })
+    .unwrap();
+    assert_eq!(seen, vec!["0", "1", "2"]);
+    assert!(dbm
+        .visit_rows_dyn("nope", &mut |_, _| ControlFlow::Continue(()))
+        .is_err());
+}
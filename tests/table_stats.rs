@@ -0,0 +1,89 @@
+mod common;
+
+use redb::TableDefinition;
+use redbcli::flags::TableColumn;
+use redbcli::redbcontrol::CommonDbManager;
+use redbcli::{TableInfo, TableMeta};
+
+const BIG: TableDefinition<u64, &str> = TableDefinition::new("user_big");
+const SMALL: TableDefinition<u64, u64> = TableDefinition::new("user_small");
+const EMPTY: TableDefinition<&str, &str> = TableDefinition::new("config");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("stats.redb", |txn| {
+        let mut t = txn.open_table(BIG).unwrap();
+        let value = "x".repeat(200);
+        for i in 0..500u64 {
+            t.insert(i, value.as_str()).unwrap();
+        }
+        let mut t = txn.open_table(SMALL).unwrap();
+        t.insert(1u64, 1u64).unwrap();
+        t.insert(2u64, 2u64).unwrap();
+        txn.open_table(EMPTY).unwrap();
+    })
+}
+
+fn table_info(dbm: &CommonDbManager) -> TableInfo {
+    let mut stats: std::collections::HashMap<_, _> =
+        dbm.list_table_stats().unwrap().into_iter().collect();
+    let tables = dbm
+        .list_table_types()
+        .unwrap()
+        .into_iter()
+        .map(|(name, desc)| TableMeta {
+            stats: stats.remove(&name),
+            name,
+            key_type: desc.key,
+            value_type: desc.value,
+        })
+        .collect();
+    TableInfo { tables }
+}
+
+fn names(info: &TableInfo) -> Vec<&str> {
+    info.tables.iter().map(|t| t.name.as_str()).collect()
+}
+
+#[test]
+fn stats_report_row_counts_and_sizes() {
+    let (_dir, dbm) = make_db();
+    let stats: std::collections::HashMap<_, _> =
+        dbm.list_table_stats().unwrap().into_iter().collect();
+    assert_eq!(stats["user_big"].rows, 500);
+    assert_eq!(stats["user_small"].rows, 2);
+    assert_eq!(stats["config"].rows, 0);
+    assert!(stats["user_big"].stored_bytes >= 500 * 200);
+    assert!(stats["user_big"].stored_bytes > stats["user_small"].stored_bytes);
+    assert!(stats["user_big"].tree_height > 1);
+}
+
+#[test]
+fn match_filters_by_glob() {
+    let (_dir, dbm) = make_db();
+    let mut info = table_info(&dbm);
+    info.retain_matching("user_*").unwrap();
+    let mut found = names(&info);
+    found.sort();
+    assert_eq!(found, vec!["user_big", "user_small"]);
+    assert!(info.retain_matching("[").is_err());
+}
+
+#[test]
+fn sort_by_stats_column() {
+    let (_dir, dbm) = make_db();
+    let mut info = table_info(&dbm);
+    info.sort_by_column(TableColumn::Stored, true);
+    assert_eq!(names(&info), vec!["user_big", "user_small", "config"]);
+    info.sort_by_column(TableColumn::Rows, false);
+    assert_eq!(names(&info), vec!["config", "user_small", "user_big"]);
+}
+
+#[test]
+fn sort_by_type_column() {
+    let (_dir, dbm) = make_db();
+    let mut info = table_info(&dbm);
+    info.sort_by_column(TableColumn::ValueType, false);
+    assert_eq!(names(&info), vec!["config", "user_big", "user_small"]);
+    info.sort_by_column(TableColumn::Name, true);
+    assert_eq!(names(&info), vec!["user_small", "user_big", "config"]);
+}
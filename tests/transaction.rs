@@ -0,0 +1,100 @@
+mod common;
+
+use common::rows_of;
+use redb::TableDefinition;
+use redbcli::redbcontrol::{CommonDbManager, DealTable};
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("transaction.redb", |txn| {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        t.insert(1u64, "one").unwrap();
+        t.insert(2u64, "two").unwrap();
+    })
+}
+
+#[test]
+fn put_and_del_commit_immediately_without_transaction() {
+    let (_dir, dbm) = make_db();
+    dbm.put_dyn("by_id", "3".into(), "three".into()).unwrap();
+    assert_eq!(
+        dbm.remove_dyn("by_id", "1".into()).unwrap(),
+        Some("one".to_string())
+    );
+    assert!(dbm.remove_dyn("by_id", "42".into()).unwrap().is_none());
+    assert_eq!(
+        rows_of(&dbm, "by_id"),
+        vec![
+            ("2".to_string(), "two".to_string()),
+            ("3".to_string(), "three".to_string()),
+        ]
+    );
+    assert!(dbm.put_dyn("by_id", "x".into(), "bad".into()).is_err());
+}
+
+#[test]
+fn commit_applies_all_pending_writes() {
+    let (_dir, mut dbm) = make_db();
+    dbm.begin_transaction().unwrap();
+    assert!(dbm.in_transaction());
+    dbm.put_dyn("by_id", "3".into(), "three".into()).unwrap();
+    dbm.remove_dyn("by_id", "1".into()).unwrap();
+    dbm.create_table("fresh".into()).unwrap();
+
+    // reads inside the transaction see its own changes
+    assert_eq!(rows_of(&dbm, "by_id").len(), 2);
+    assert!(dbm.list_table().unwrap().contains(&"fresh".to_string()));
+    // commands that open the file themselves are refused
+    assert!(dbm.create_savepoint().is_err());
+    assert!(dbm.begin_transaction().is_err());
+
+    dbm.commit_transaction().unwrap();
+    assert!(!dbm.in_transaction());
+    assert_eq!(
+        rows_of(&dbm, "by_id"),
+        vec![
+            ("2".to_string(), "two".to_string()),
+            ("3".to_string(), "three".to_string()),
+        ]
+    );
+    assert!(dbm.list_table().unwrap().contains(&"fresh".to_string()));
+}
+
+#[test]
+fn rollback_discards_pending_writes() {
+    let (_dir, mut dbm) = make_db();
+    let before = rows_of(&dbm, "by_id");
+    dbm.begin_transaction().unwrap();
+    dbm.put_dyn("by_id", "3".into(), "three".into()).unwrap();
+    dbm.delete_table("by_id".into()).unwrap();
+    dbm.rollback_transaction().unwrap();
+
+    assert_eq!(rows_of(&dbm, "by_id"), before);
+    assert!(dbm.commit_transaction().is_err());
+    assert!(dbm.rollback_transaction().is_err());
+}
+
+#[test]
+fn dropping_the_manager_with_an_open_transaction_releases_the_file() {
+    let (dir, mut dbm) = make_db();
+    let path = dbm.dbpath.clone();
+    dbm.begin_transaction().unwrap();
+    dbm.put_dyn("by_id", "3".into(), "three".into()).unwrap();
+
+    // dropped on another thread, so a hang fails the test
+    let (done, dropped) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        drop(dbm);
+        done.send(()).unwrap();
+    });
+    dropped
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .expect("dropping the manager hung");
+
+    // the lock is released and the pending write discarded
+    let mut again = CommonDbManager::default();
+    again.setdbpath(path).unwrap();
+    assert_eq!(rows_of(&again, "by_id").len(), 2);
+    drop(dir);
+}
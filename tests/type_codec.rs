@@ -0,0 +1,114 @@
+mod common;
+
+use redb::{Database, ReadableDatabase, TableDefinition};
+use redbcli::redbcontrol::CommonDbManager;
+use redbcli::{DynTable, DynValue};
+
+const MIXED: TableDefinition<(u64, &str, [u8; 4]), Option<u32>> = TableDefinition::new("mixed");
+const LISTS: TableDefinition<Option<&str>, Vec<(u16, &str)>> = TableDefinition::new("lists");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("codec.redb", |txn| {
+        let mut t = txn.open_table(MIXED).unwrap();
+        t.insert((2, "b", [0, 0, 0, 1]), Some(7)).unwrap();
+        t.insert((1, "a, z", [9, 9, 9, 9]), None).unwrap();
+        let mut t = txn.open_table(LISTS).unwrap();
+        t.insert(Some("k"), vec![(1, "x"), (2, "")]).unwrap();
+        t.insert(None, vec![]).unwrap();
+    })
+}
+
+#[test]
+fn reads_nested_builtin_types() {
+    let (_dir, dbm) = make_db();
+    let (desc, rows) = dbm.get_all_dyn("mixed").unwrap();
+    assert_eq!(desc.key, "(u64,&str,[u8;4])");
+    assert_eq!(desc.value, "Option<u32>");
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "(1, \"a, z\", [9, 9, 9, 9])".to_string(),
+                "None".to_string()
+            ),
+            (
+                "(2, \"b\", [0, 0, 0, 1])".to_string(),
+                "Some(7)".to_string()
+            ),
+        ]
+    );
+
+    let (_, rows) = dbm.get_all_dyn("lists").unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            ("None".to_string(), "[]".to_string()),
+            (
+                "Some(\"k\")".to_string(),
+                "[(1, \"x\"), (2, \"\")]".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn edits_are_readable_by_the_typed_api() {
+    let (dir, mut dbm) = make_db();
+    dbm.put_dyn(
+        "mixed",
+        "(3, \"c\", [1, 2, 3, 4])".to_string(),
+        "Some(4)".to_string(),
+    )
+    .unwrap();
+    let old = dbm
+        .put_dyn(
+            "mixed",
+            "(2, \"b\", [0, 0, 0, 1])".to_string(),
+            "None".to_string(),
+        )
+        .unwrap();
+    assert_eq!(old.as_deref(), Some("Some(7)"));
+    dbm.update_all_dyn(
+        "lists",
+        vec![("Some(\"new\")".to_string(), "[(5, \"five\")]".to_string())],
+    )
+    .unwrap();
+    assert!(dbm
+        .put_dyn("mixed", "(4, \"d\", [1])".to_string(), "None".to_string())
+        .is_err());
+
+    dbm.close_db().unwrap();
+    let db = Database::open(dir.path().join("codec.redb")).unwrap();
+    let txn = db.begin_read().unwrap();
+    let mixed = txn.open_table(MIXED).unwrap();
+    assert_eq!(
+        mixed.get((3, "c", [1, 2, 3, 4])).unwrap().unwrap().value(),
+        Some(4)
+    );
+    assert_eq!(
+        mixed.get((2, "b", [0, 0, 0, 1])).unwrap().unwrap().value(),
+        None
+    );
+    let lists = txn.open_table(LISTS).unwrap();
+    assert!(lists.get(None).unwrap().is_none());
+    assert_eq!(
+        lists.get(Some("new")).unwrap().unwrap().value(),
+        vec![(5, "five")]
+    );
+}
+
+#[test]
+fn dyn_table_ranges_over_tuple_keys() {
+    let (dir, mut dbm) = make_db();
+    dbm.close_db().unwrap();
+    let db = Database::open(dir.path().join("codec.redb")).unwrap();
+    let txn = db.begin_read().unwrap();
+    let mixed = DynTable::open(&txn, "mixed").unwrap();
+    let from = mixed.parse_key("(2, \"\", [0, 0, 0, 0])").unwrap();
+    let rows = mixed.range(&from..).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(
+        rows[0].1,
+        DynValue::Option(Some(Box::new(DynValue::U32(7))))
+    );
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use common::rows_of;
+use redb::TableDefinition;
+use redbcli::dynread::TableTypeDesc;
+use redbcli::redbcontrol::{CommonDbManager, DealTable, UndoRecord};
+
+const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    common::make_db("undo.redb", |txn| {
+        let mut t = txn.open_table(BY_ID).unwrap();
+        t.insert(1u64, "one").unwrap();
+        t.insert(2u64, "two").unwrap();
+    })
+}
+
+#[test]
+fn undo_put_and_del() {
+    let (_dir, dbm) = make_db();
+    let before = rows_of(&dbm, "by_id");
+
+    let image = dbm.row_image("by_id", "1").unwrap();
+    dbm.put_dyn("by_id", "1".into(), "uno".into()).unwrap();
+    dbm.undo(image).unwrap();
+    assert_eq!(rows_of(&dbm, "by_id"), before);
+
+    let image = dbm.row_image("by_id", "3").unwrap();
+    dbm.put_dyn("by_id", "3".into(), "three".into()).unwrap();
+    dbm.undo(image).unwrap();
+    assert_eq!(rows_of(&dbm, "by_id"), before);
+
+    let image = dbm.row_image("by_id", "2").unwrap();
+    dbm.remove_dyn("by_id", "2".into()).unwrap();
+    dbm.undo(image).unwrap();
+    assert_eq!(rows_of(&dbm, "by_id"), before);
+}
+
+#[test]
+fn undo_delete_restores_types_and_rows() {
+    let (_dir, dbm) = make_db();
+    let before = rows_of(&dbm, "by_id");
+    let image = dbm.table_image("by_id").unwrap();
+    dbm.delete_table("by_id".into()).unwrap();
+    // recreated with other types in between
+    dbm.create_table("by_id".into()).unwrap();
+
+    dbm.undo(image).unwrap();
+    let (desc, rows) = dbm.get_all_dyn("by_id").unwrap();
+    assert_eq!(
+        desc,
+        TableTypeDesc {
+            key: "u64".into(),
+            value: "&str".into(),
+            ..Default::default()
+        }
+    );
+    assert_eq!(rows, before);
+}
+
+#[test]
+fn undo_create_drops_the_table() {
+    let (_dir, dbm) = make_db();
+    let image = dbm.table_image("fresh").unwrap();
+    assert_eq!(
+        image,
+        UndoRecord::Table {
+            table: "fresh".into(),
+            before: None
+        }
+    );
+    dbm.create_table("fresh".into()).unwrap();
+    dbm.undo(image).unwrap();
+    assert_eq!(dbm.list_table().unwrap(), vec!["by_id".to_string()]);
+}
+
+#[test]
+fn undo_edit_restores_whole_table() {
+    let (_dir, dbm) = make_db();
+    let before = rows_of(&dbm, "by_id");
+    let image = dbm.table_image("by_id").unwrap();
+    dbm.update_all_dyn("by_id", vec![("7".into(), "seven".into())])
+        .unwrap();
+    dbm.undo(image).unwrap();
+    assert_eq!(rows_of(&dbm, "by_id"), before);
+}
+
+#[test]
+fn undo_rows_refuses_a_table_recreated_with_other_types() {
+    let (_dir, dbm) = make_db();
+    let image = dbm.row_image("by_id", "1").unwrap();
+    dbm.delete_table("by_id".into()).unwrap();
+    dbm.create_table("by_id".into()).unwrap();
+    let err = dbm.undo(image).unwrap_err();
+    assert!(matches!(err, redbcli::Error::Refused(_)), "{err}");
+}
@@ -1,4 +1,7 @@
-use redb::{Database, MultimapTableDefinition, TableDefinition};
+mod common;
+
+use common::Record;
+use redb::{Database, MultimapTableDefinition, ReadableDatabase, ReadableTable, TableDefinition};
 use redbcli::redbcontrol::{CommonDbManager, DealData, DealTable};
 
 const STR_TABLE: TableDefinition<&str, &str> = TableDefinition::new("strings");
@@ -6,7 +9,7 @@ const FOUND_KEYS: TableDefinition<(u128, u128), u64> = TableDefinition::new("fou
 const BY_ID: TableDefinition<u64, &str> = TableDefinition::new("by_id");
 const BLOBS: TableDefinition<u32, &[u8]> = TableDefinition::new("blobs");
 const MM_TABLE: MultimapTableDefinition<&str, u64> = MultimapTableDefinition::new("tags");
-const OPTION_VALS: TableDefinition<u64, Option<u64>> = TableDefinition::new("opt_vals");
+const RECORDS: TableDefinition<u64, Record> = TableDefinition::new("records");
 
 fn make_db() -> (tempfile::TempDir, CommonDbManager) {
     let dir = tempfile::tempdir().expect("create temp dir failed");
@@ -38,8 +41,8 @@ fn make_db() -> (tempfile::TempDir, CommonDbManager) {
         t.insert("k", 1u64).unwrap();
     }
     {
-        let mut t = txn.open_table(OPTION_VALS).unwrap();
-        t.insert(5u64, Some(9u64)).unwrap();
+        let mut t = txn.open_table(RECORDS).unwrap();
+        t.insert(5u64, 9u64).unwrap();
     }
     txn.commit().unwrap();
     drop(db);
@@ -57,7 +60,7 @@ fn list_table_finds_all_normal_tables() {
     tables.sort();
     assert_eq!(
         tables,
-        vec!["blobs", "by_id", "found_keys", "opt_vals", "strings"]
+        vec!["blobs", "by_id", "found_keys", "records", "strings"]
     );
 }
 
@@ -151,12 +154,15 @@ fn info_table_still_reads_str_tables() {
 }
 
 #[test]
-fn info_table_reports_unsupported_types() {
+fn info_table_shows_unknown_types_as_raw_bytes() {
     let (_dir, dbm) = make_db();
-    let err = dbm.get_all_dyn("opt_vals").unwrap_err();
-    let msg = err.to_string();
-    assert!(msg.contains("unsupported"), "unexpected error: {msg}");
-    assert!(msg.contains("Option<u64>"), "unexpected error: {msg}");
+    let (desc, rows) = dbm.get_all_dyn("records").unwrap();
+    assert_eq!(desc.value, "app::Record");
+    assert!(!desc.is_decodable());
+    assert_eq!(
+        rows,
+        vec![("5".to_string(), "0x0900000000000000".to_string())]
+    );
 }
 
 #[test]
@@ -226,6 +232,8 @@ fn create_and_delete_table_roundtrip() {
     assert!(dbm.list_table().unwrap().contains(&"temp".to_string()));
     dbm.delete_table("temp".to_string()).unwrap();
     assert!(!dbm.list_table().unwrap().contains(&"temp".to_string()));
+    let err = dbm.delete_table("temp".to_string()).unwrap_err();
+    assert!(matches!(err, redbcli::Error::TableNotFound(_)), "{err}");
 }
 
 #[test]
@@ -236,3 +244,58 @@ fn regression_issue1_tuple_u128_table_is_browsable() {
     assert_eq!(desc.value, "u64");
     assert_eq!(rows.len(), 2);
 }
+
+#[test]
+fn raw_tables_are_restored_byte_for_byte() {
+    let (dir, dbm) = make_db();
+    let path = dir.path().join("test.redb");
+    let by_record: TableDefinition<Record, u64> = TableDefinition::new("by_record");
+    {
+        let mut dbm = dbm;
+        dbm.close_db().unwrap();
+        let db = Database::open(&path).unwrap();
+        let txn = db.begin_write().unwrap();
+        {
+            let mut t = txn.open_table(by_record).unwrap();
+            // 256 sorts after 1, though its little endian bytes sort first
+            t.insert(256, 2).unwrap();
+            t.insert(1, 1).unwrap();
+        }
+        txn.commit().unwrap();
+    }
+    let mut dbm = CommonDbManager::default();
+    dbm.setdbpath(path.to_string_lossy().to_string()).unwrap();
+
+    // raw keys: a single row could not be put back in order
+    let err = dbm
+        .row_image("by_record", "0x0100000000000000")
+        .unwrap_err();
+    assert!(matches!(err, redbcli::Error::Refused(_)), "{err}");
+    assert!(dbm.row_image("records", "5").is_ok());
+
+    for table in ["records", "by_record"] {
+        let image = dbm.table_image(table).unwrap();
+        dbm.delete_table(table.into()).unwrap();
+        dbm.undo(image).unwrap();
+    }
+    dbm.close_db().unwrap();
+    let db = Database::open(&path).unwrap();
+    let txn = db.begin_read().unwrap();
+    assert_eq!(
+        txn.open_table(RECORDS)
+            .unwrap()
+            .get(5)
+            .unwrap()
+            .unwrap()
+            .value(),
+        9
+    );
+    let t = txn.open_table(by_record).unwrap();
+    assert_eq!(t.get(256).unwrap().unwrap().value(), 2);
+    let keys: Vec<u64> = t
+        .iter()
+        .unwrap()
+        .map(|row| row.unwrap().0.value())
+        .collect();
+    assert_eq!(keys, [1, 256]);
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use redb::{Database, ReadableDatabase, TableDefinition};
+use redbcli::config::{Config, ValueDecoder};
+use redbcli::layout::{Codec, Layouts};
+use redbcli::redbcontrol::CommonDbManager;
+use redbcli::Error;
+
+const DOCS: TableDefinition<&str, &[u8]> = TableDefinition::new("docs");
+const POINTS: TableDefinition<u32, &[u8]> = TableDefinition::new("points");
+const NAMES: TableDefinition<u32, &str> = TableDefinition::new("names");
+
+const LAYOUTS: &str = r#"{
+    "app::Point": {"fields": [
+        {"name": "x", "type": "i32"},
+        {"name": "label", "type": "str"}
+    ]}
+}"#;
+
+/// msgpack of {"id": 7, "tags": ["a"]}
+const DOC: &[u8] = &[
+    0x82, 0xa2, b'i', b'd', 0x07, 0xa4, b't', b'a', b'g', b's', 0x91, 0xa1, b'a',
+];
+
+/// bincode of Point { x: -1, label: "p" }
+const POINT: &[u8] = &[0xff, 0xff, 0xff, 0xff, 1, 0, 0, 0, 0, 0, 0, 0, b'p'];
+
+fn make_db() -> (tempfile::TempDir, CommonDbManager) {
+    Layouts::parse(LAYOUTS).unwrap().install();
+    let (dir, mut dbm) = common::make_db("codecs.redb", |txn| {
+        let mut t = txn.open_table(DOCS).unwrap();
+        t.insert("one", DOC).unwrap();
+        let mut t = txn.open_table(POINTS).unwrap();
+        t.insert(1, POINT).unwrap();
+        let mut t = txn.open_table(NAMES).unwrap();
+        t.insert(1, "x").unwrap();
+    });
+    dbm.config = Config::load(dir.path().join("config.json")).unwrap();
+    (dir, dbm)
+}
+
+fn decoder(codec: Codec, schema: Option<&str>) -> Option<ValueDecoder> {
+    Some(ValueDecoder {
+        codec,
+        schema: schema.map(str::to_string),
+    })
+}
+
+#[test]
+fn values_are_shown_as_pretty_json() {
+    let (_dir, mut dbm) = make_db();
+    let (_, rows) = dbm.get_all_dyn("docs").unwrap();
+    assert_eq!(rows[0].1, "0x82a2696407a47461677391a161");
+
+    dbm.set_value_decoder("docs", decoder(Codec::MsgPack, None))
+        .unwrap();
+    let (desc, rows) = dbm.get_all_dyn("docs").unwrap();
+    assert_eq!(desc.value, "&[u8]");
+    assert_eq!(
+        rows[0].1,
+        "{\n  \"id\": 7,\n  \"tags\": [\n    \"a\"\n  ]\n}"
+    );
+
+    dbm.set_value_decoder("points", decoder(Codec::Bincode, Some("app::Point")))
+        .unwrap();
+    let (_, rows) = dbm.get_all_dyn("points").unwrap();
+    assert_eq!(rows[0].1, "{\n  \"label\": \"p\",\n  \"x\": -1\n}");
+
+    // a session decoder covers tables without their own
+    dbm.set_value_decoder("docs", None).unwrap();
+    dbm.session_decoder = decoder(Codec::MsgPack, None);
+    let (_, rows) = dbm.get_all_dyn("docs").unwrap();
+    assert!(rows[0].1.contains("\"tags\""));
+}
+
+#[test]
+fn saved_values_are_reencoded() {
+    let (dir, mut dbm) = make_db();
+    dbm.set_value_decoder("docs", decoder(Codec::MsgPack, None))
+        .unwrap();
+    dbm.set_value_decoder("points", decoder(Codec::Bincode, Some("app::Point")))
+        .unwrap();
+    let old = dbm
+        .put_dyn("docs", "two".to_string(), r#"{"id": 300}"#.to_string())
+        .unwrap();
+    assert!(old.is_none());
+    let err = dbm
+        .put_dyn("points", "2".to_string(), r#"{"x": 1}"#.to_string())
+        .unwrap_err();
+    assert!(err.to_string().contains("missing field 'label'"), "{err}");
+
+    // edit round trip: values are embedded as JSON documents
+    let (_, rows) = dbm.get_all_dyn("points").unwrap();
+    let json = redbcli::rows_to_json_embedded(&rows).unwrap();
+    assert!(json.contains("\"x\": -1"), "{json}");
+    let edited = json.replace("-1", "2");
+    let rows = redbcli::json_to_rows_embedded(&edited).unwrap();
+    dbm.update_all_dyn("points", rows).unwrap();
+
+    dbm.close_db().unwrap();
+    let db = Database::open(dir.path().join("codecs.redb")).unwrap();
+    let txn = db.begin_read().unwrap();
+    let docs = txn.open_table(DOCS).unwrap();
+    assert_eq!(
+        docs.get("two").unwrap().unwrap().value(),
+        [0x81, 0xa2, b'i', b'd', 0xcd, 0x01, 0x2c]
+    );
+    assert_eq!(docs.get("one").unwrap().unwrap().value(), DOC);
+    let points = txn.open_table(POINTS).unwrap();
+    assert_eq!(
+        points.get(1).unwrap().unwrap().value(),
+        [2, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, b'p']
+    );
+}
+
+#[test]
+fn decoders_persist_in_the_config_file() {
+    let (dir, mut dbm) = make_db();
+    dbm.set_value_decoder("docs", decoder(Codec::MsgPack, None))
+        .unwrap();
+
+    let err = dbm
+        .set_value_decoder("names", decoder(Codec::Cbor, None))
+        .unwrap_err();
+    assert!(matches!(err, Error::Refused(_)), "{err}");
+    let err = dbm
+        .set_value_decoder("points", decoder(Codec::Postcard, None))
+        .unwrap_err();
+    assert!(err.to_string().contains("not self-describing"), "{err}");
+
+    // a new session on the same file, named differently, finds the decoder
+    dbm.close_db().unwrap();
+    let mut again = CommonDbManager {
+        config: Config::load(dir.path().join("config.json")).unwrap(),
+        ..Default::default()
+    };
+    again
+        .setdbpath(format!("{}/./codecs.redb", dir.path().display()))
+        .unwrap();
+    let decoders = again.value_decoders();
+    assert_eq!(
+        decoders,
+        vec![("docs".to_string(), decoder(Codec::MsgPack, None).unwrap())]
+    );
+    let (desc, _) = again.get_all_dyn("docs").unwrap();
+    assert!(desc.value_codec.is_some());
+}
+
+/// msgpack of {"b": 1, "a": 2.5f32}, keys not sorted and a 32 bit float
+const UNSORTED: &[u8] = &[
+    0x82, 0xa1, b'b', 0x01, 0xa1, b'a', 0xca, 0x40, 0x20, 0x00, 0x00,
+];
+
+#[test]
+fn edit_keeps_the_bytes_of_untouched_rows() {
+    let (dir, mut dbm) = make_db();
+    dbm.set_value_decoder("docs", decoder(Codec::MsgPack, None))
+        .unwrap();
+    dbm.put_dyn("docs", "two".to_string(), "{}".to_string())
+        .unwrap();
+    dbm.close_db().unwrap();
+    {
+        let db = Database::open(dir.path().join("codecs.redb")).unwrap();
+        let txn = db.begin_write().unwrap();
+        {
+            let mut docs = txn.open_table(DOCS).unwrap();
+            docs.insert("one", UNSORTED).unwrap();
+            // 0xc1 is never used by msgpack
+            docs.insert("bad", [0xc1].as_slice()).unwrap();
+        }
+        txn.commit().unwrap();
+    }
+    dbm.reopen_db().unwrap();
+
+    // the value that does not decode is shown as hex, the others as JSON
+    let (_, rows) = dbm.get_all_dyn("docs").unwrap();
+    assert_eq!(rows[0], ("bad".to_string(), "0xc1".to_string()));
+
+    // edit round trip changing only "two"
+    let json = redbcli::rows_to_json_embedded(&rows).unwrap();
+    let edited = json.replace("\"two\": {}", "\"two\": {\"n\": 1}");
+    assert_ne!(json, edited);
+    let image = dbm.table_image("docs").unwrap();
+    let rows = redbcli::json_to_rows_embedded(&edited).unwrap();
+    dbm.update_all_dyn("docs", rows).unwrap();
+    let stored = |dbm: &mut CommonDbManager, key: &str| {
+        dbm.close_db().unwrap();
+        let db = Database::open(dir.path().join("codecs.redb")).unwrap();
+        let txn = db.begin_read().unwrap();
+        let value = txn.open_table(DOCS).unwrap().get(key).unwrap().unwrap();
+        let value = value.value().to_vec();
+        drop(db);
+        dbm.reopen_db().unwrap();
+        value
+    };
+    assert_eq!(stored(&mut dbm, "one"), UNSORTED);
+    assert_eq!(stored(&mut dbm, "bad"), [0xc1]);
+    assert_eq!(stored(&mut dbm, "two"), [0x81, 0xa1, b'n', 0x01]);
+
+    // undo puts back the stored bytes of every row
+    dbm.undo(image).unwrap();
+    assert_eq!(stored(&mut dbm, "one"), UNSORTED);
+    assert_eq!(stored(&mut dbm, "two"), [0x80]);
+}